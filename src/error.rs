@@ -234,3 +234,30 @@ impl<R: Debug, S: Debug> Error for ReuniteError<R, S> {}
 
 /// Result type of `.reunite()` on splittable stream types.
 pub type ReuniteResult<T, R, S> = Result<T, ReuniteError<R, S>>;
+
+/// Error returned by listeners which have been shut down through a
+/// [`ShutdownHandle`](crate::local_socket::ShutdownHandle), wrapped in an [`io::Error`] of kind
+/// [`Other`](io::ErrorKind::Other).
+///
+/// Use [`ListenerShutdownError::is`] to tell it apart from other errors.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct ListenerShutdownError;
+impl ListenerShutdownError {
+	/// Returns `true` if the given error was caused by the listener having been shut down.
+	#[inline]
+	pub fn is(err: &io::Error) -> bool {
+		err.get_ref().is_some_and(|e| e.is::<Self>())
+	}
+}
+impl Display for ListenerShutdownError {
+	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+		f.write_str("the listener has been shut down")
+	}
+}
+impl Error for ListenerShutdownError {}
+impl From<ListenerShutdownError> for io::Error {
+	#[inline]
+	fn from(e: ListenerShutdownError) -> Self {
+		io::Error::other(e)
+	}
+}
@@ -39,8 +39,9 @@
 //! -	No `.shutdown()` – your communication protocol must manually negotiate end of transmission.
 //! 	Notably, `.read_to_string()` and `.read_all()` will always block indefinitely at some point.
 //! -	No datagram sockets – the difference in semantics between connectionless datagram Unix-domain
-//! 	sockets and connection-based named message pipes on Windows does not allow bridging those two
-//! 	into a common API. You can emulate datagrams on top of streams anyway, so no big deal, right?
+//!   	sockets and connection-based named message pipes on Windows does not allow bridging those two
+//!   	into a common API. The [`framing`] module emulates datagrams on top of streams by prefixing
+//!   	messages with their length.
 
 #[macro_use]
 mod enumdef;
@@ -54,10 +55,33 @@ mod stream {
 }
 mod listener {
 	pub(super) mod r#enum;
+	pub(super) mod exhaustion;
+	pub(super) mod shutdown;
 	pub(super) mod r#trait;
 }
 
-pub use {listener::r#enum::*, name::*, name_type_support::*, stream::r#enum::*, to_name::*};
+#[cfg(unix)]
+pub(crate) use listener::exhaustion::{is_exhaustion, Exhaustion};
+pub(crate) use listener::shutdown::{shutdown_error, ShutdownState};
+pub use {
+	listener::exhaustion::{ExhaustionEvent, ExhaustionPolicy},
+	listener::r#enum::*,
+	listener::shutdown::ShutdownHandle,
+	name::*,
+	name_type_support::*,
+	stream::r#enum::*,
+	to_name::*,
+};
+
+pub mod framing;
+#[cfg(feature = "jsonrpc")]
+#[cfg_attr(feature = "doc_cfg", doc(cfg(feature = "jsonrpc")))]
+pub mod jsonrpc;
+pub mod server;
+pub mod single_instance;
+#[cfg(feature = "serde")]
+#[cfg_attr(feature = "doc_cfg", doc(cfg(feature = "serde")))]
+pub mod typed;
 
 /// Traits representing the interface of local sockets.
 pub mod traits {
@@ -85,8 +109,22 @@ pub mod prelude {
 #[cfg(feature = "tokio")]
 #[cfg_attr(feature = "doc_cfg", doc(cfg(feature = "tokio")))]
 pub mod tokio {
+	#[cfg(feature = "codec")]
+	#[cfg_attr(feature = "doc_cfg", doc(cfg(feature = "codec")))]
+	pub mod codec;
+	pub mod framing;
+	#[cfg(feature = "jsonrpc")]
+	#[cfg_attr(feature = "doc_cfg", doc(cfg(feature = "jsonrpc")))]
+	pub mod jsonrpc;
 	mod listener;
+	pub mod mux;
+	pub mod pubsub;
+	pub mod rpc;
+	pub mod server;
 	mod stream;
+	#[cfg(feature = "serde")]
+	#[cfg_attr(feature = "doc_cfg", doc(cfg(feature = "serde")))]
+	pub mod typed;
 	pub use {listener::*, stream::*};
 }
 
@@ -27,6 +27,13 @@ macro_rules! dispatch_asraw {
 				dispatch!(Self: x in self => (*x).as_handle())
 			}
 		}
+		#[cfg(unix)]
+		impl AsFd for $ty {
+			#[inline]
+			fn as_fd(&self) -> BorrowedFd<'_> {
+				dispatch!(Self: x in self => (*x).as_fd())
+			}
+		}
 	};
 }
 
@@ -0,0 +1,440 @@
+//! Length-prefixed message framing on top of local socket streams.
+//!
+//! Local sockets are byte streams, which means that they don't preserve the boundaries between
+//! separate writes. [`Framed`] restores those boundaries by prefixing each sent message (a
+//! *frame*) with its length, and by reading exactly one such message per call on the receiving
+//! side. It works on top of anything that implements [`Read`] and/or [`Write`], including
+//! [streams](super::Stream) and their [receive](super::RecvHalf) and [send](super::SendHalf) halves.
+//!
+//! The encoding of the length prefix is configured with [`FramingOptions`], which both ends must
+//! agree on. Incoming frames larger than the [maximum frame
+//! size](FramingOptions::max_frame_size) are rejected with an error before any memory is
+//! allocated for them.
+//!
+//! A Tokio version is available in the [`tokio::framing`](super::tokio::framing) module when the
+//! `tokio` feature is enabled.
+//!
+//! # Examples
+//! ```no_run
+//! use interprocess::local_socket::{framing::Framed, prelude::*, Stream};
+//!
+//! let (a, b) = Stream::pair()?;
+//! let (mut a, mut b) = (Framed::new(a), Framed::new(b));
+//! a.send_frame(b"Hello")?;
+//! a.send_frame(b"world!")?;
+//! assert_eq!(b.recv_frame()?, Some(&b"Hello"[..]));
+//! assert_eq!(b.recv_frame()?, Some(&b"world!"[..]));
+//! # std::io::Result::<()>::Ok(())
+//! ```
+
+use super::{traits::Stream as _, RecvHalf, SendHalf, Stream};
+use crate::error::{ReuniteError, ReuniteResult};
+use std::{
+	fmt::{self, Debug, Formatter},
+	io::{self, prelude::*},
+	ops::Range,
+};
+
+/// Encoding of the length prefix of frames.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Default)]
+pub enum LengthPrefix {
+	/// 32-bit unsigned integer.
+	#[default]
+	U32,
+	/// 64-bit unsigned integer.
+	U64,
+	/// Unsigned [LEB128](https://en.wikipedia.org/wiki/LEB128) variable-length integer, taking up
+	/// one byte for lengths below 128 and at most ten bytes for larger ones. Not affected by
+	/// [endianness](Endianness).
+	Varint,
+}
+
+/// Byte order of fixed-size length prefixes.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Default)]
+pub enum Endianness {
+	/// Most significant byte first, also known as network byte order.
+	Big,
+	/// Least significant byte first.
+	#[default]
+	Little,
+}
+
+/// Options for [`Framed`], which must be the same on both ends of the connection.
+#[non_exhaustive]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct FramingOptions {
+	/// The encoding of the length prefix.
+	///
+	/// The default value is [`LengthPrefix::U32`].
+	pub length_prefix: LengthPrefix,
+	/// The byte order of the length prefix.
+	///
+	/// The default value is [`Endianness::Little`].
+	pub endianness: Endianness,
+	/// The maximum size of a frame, not counting the length prefix, that can be sent or received.
+	/// Attempting to send a larger frame fails with [`InvalidInput`](io::ErrorKind::InvalidInput),
+	/// while receiving a length prefix exceeding this size fails with
+	/// [`InvalidData`](io::ErrorKind::InvalidData).
+	///
+	/// The default value is [`DEFAULT_MAX_FRAME_SIZE`](Self::DEFAULT_MAX_FRAME_SIZE).
+	pub max_frame_size: usize,
+}
+impl FramingOptions {
+	/// The default value of [`max_frame_size`](Self::max_frame_size), which is 16 MiB.
+	pub const DEFAULT_MAX_FRAME_SIZE: usize = 16 * 1024 * 1024;
+
+	/// Starts with the default parameters. Identical to `Default::default()`.
+	pub const fn new() -> Self {
+		Self {
+			length_prefix: LengthPrefix::U32,
+			endianness: Endianness::Little,
+			max_frame_size: Self::DEFAULT_MAX_FRAME_SIZE,
+		}
+	}
+	/// Sets the encoding of the length prefix.
+	///
+	/// See the [associated field](#structfield.length_prefix) for more.
+	#[must_use = "this is not an in-place operation"]
+	#[inline]
+	pub fn length_prefix(mut self, length_prefix: LengthPrefix) -> Self {
+		self.length_prefix = length_prefix;
+		self
+	}
+	/// Sets the byte order of the length prefix.
+	///
+	/// See the [associated field](#structfield.endianness) for more.
+	#[must_use = "this is not an in-place operation"]
+	#[inline]
+	pub fn endianness(mut self, endianness: Endianness) -> Self {
+		self.endianness = endianness;
+		self
+	}
+	/// Sets the maximum frame size.
+	///
+	/// See the [associated field](#structfield.max_frame_size) for more.
+	#[must_use = "this is not an in-place operation"]
+	#[inline]
+	pub fn max_frame_size(mut self, max_frame_size: usize) -> Self {
+		self.max_frame_size = max_frame_size;
+		self
+	}
+
+	/// Appends the length prefix for a frame of size `len` to `buf`.
+	pub(crate) fn encode_prefix(&self, len: usize, buf: &mut impl Extend<u8>) -> io::Result<()> {
+		if len > self.max_frame_size {
+			return Err(io::Error::new(
+				io::ErrorKind::InvalidInput,
+				"frame exceeds maximum frame size",
+			));
+		}
+		let too_large = || {
+			io::Error::new(
+				io::ErrorKind::InvalidInput,
+				"frame too large for length prefix",
+			)
+		};
+		let big = self.endianness == Endianness::Big;
+		match self.length_prefix {
+			LengthPrefix::U32 => {
+				let len = u32::try_from(len).map_err(|_| too_large())?;
+				buf.extend(if big {
+					len.to_be_bytes()
+				} else {
+					len.to_le_bytes()
+				});
+			}
+			LengthPrefix::U64 => {
+				let len = u64::try_from(len).map_err(|_| too_large())?;
+				buf.extend(if big {
+					len.to_be_bytes()
+				} else {
+					len.to_le_bytes()
+				});
+			}
+			LengthPrefix::Varint => {
+				let mut len = u64::try_from(len).map_err(|_| too_large())?;
+				loop {
+					let byte = (len & 0x7f) as u8;
+					len >>= 7;
+					if len == 0 {
+						buf.extend([byte]);
+						break;
+					}
+					buf.extend([byte | 0x80]);
+				}
+			}
+		}
+		Ok(())
+	}
+	/// Decodes a length prefix from the beginning of `buf`, returning the length of the prefix and
+	/// the size of the frame, or `None` if `buf` doesn't hold a whole prefix yet.
+	pub(crate) fn decode_prefix(&self, buf: &[u8]) -> io::Result<Option<(usize, usize)>> {
+		let big = self.endianness == Endianness::Big;
+		let (prefix_len, len) = match self.length_prefix {
+			LengthPrefix::U32 => {
+				let Some(Ok(bytes)) = buf.get(..4).map(<[u8; 4]>::try_from) else {
+					return Ok(None);
+				};
+				let len = if big {
+					u32::from_be_bytes(bytes)
+				} else {
+					u32::from_le_bytes(bytes)
+				};
+				(4, u64::from(len))
+			}
+			LengthPrefix::U64 => {
+				let Some(Ok(bytes)) = buf.get(..8).map(<[u8; 8]>::try_from) else {
+					return Ok(None);
+				};
+				let len = if big {
+					u64::from_be_bytes(bytes)
+				} else {
+					u64::from_le_bytes(bytes)
+				};
+				(8, len)
+			}
+			LengthPrefix::Varint => {
+				let mut len = 0_u64;
+				let mut end = None;
+				for ((&byte, shift), prefix_len) in buf.iter().zip((0..64).step_by(7)).zip(1..) {
+					let bits = u64::from(byte & 0x7f);
+					if bits.checked_shl(shift).and_then(|b| b.checked_shr(shift)) != Some(bits) {
+						return Err(invalid_prefix());
+					}
+					len |= bits << shift;
+					if byte & 0x80 == 0 {
+						end = Some(prefix_len);
+						break;
+					}
+				}
+				match end {
+					Some(end) => (end, len),
+					None if buf.len() >= 10 => return Err(invalid_prefix()),
+					None => return Ok(None),
+				}
+			}
+		};
+		match usize::try_from(len) {
+			Ok(len) if len <= self.max_frame_size => Ok(Some((prefix_len, len))),
+			_ => Err(io::Error::new(
+				io::ErrorKind::InvalidData,
+				"received frame exceeds maximum frame size",
+			)),
+		}
+	}
+}
+impl Default for FramingOptions {
+	#[inline]
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+fn invalid_prefix() -> io::Error {
+	io::Error::new(io::ErrorKind::InvalidData, "invalid varint length prefix")
+}
+
+/// Receive buffer shared by the sync and async versions of `Framed`.
+#[derive(Default)]
+pub(crate) struct RecvBuf {
+	buf: Vec<u8>,
+	/// The amount of bytes at the start of `buf` which belong to frames that have already been
+	/// returned.
+	consumed: usize,
+	/// The amount of bytes at the start of `buf` which have been read from the stream.
+	filled: usize,
+}
+impl RecvBuf {
+	const READ_CHUNK: usize = 8192;
+
+	/// Discards the frame returned by the previous call to `.frame()`.
+	pub fn discard_consumed(&mut self) {
+		if self.consumed != 0 {
+			self.buf.copy_within(self.consumed..self.filled, 0);
+			self.filled = self.filled.saturating_sub(self.consumed);
+			self.consumed = 0;
+		}
+	}
+	/// Locates the next complete frame in the buffer.
+	pub fn next_frame(&self, opts: &FramingOptions) -> io::Result<Option<Range<usize>>> {
+		self.next_frame_with(|avail| opts.decode_prefix(avail))
+	}
+	/// Locates the next complete frame in the buffer, using `decode_prefix` to parse whatever
+	/// precedes the frame into its length and the length of the frame.
+	pub fn next_frame_with(
+		&self,
+		decode_prefix: impl FnOnce(&[u8]) -> io::Result<Option<(usize, usize)>>,
+	) -> io::Result<Option<Range<usize>>> {
+		let avail = self.buf.get(..self.filled).unwrap_or_default();
+		let Some((prefix_len, len)) = decode_prefix(avail)? else {
+			return Ok(None);
+		};
+		// Both of these are bounded by the maximum frame size and by the amount of data in the
+		// buffer.
+		let end = prefix_len.saturating_add(len);
+		Ok((end <= self.filled).then_some(prefix_len..end))
+	}
+	/// Marks the given frame as consumed and returns its contents.
+	pub fn take_frame(&mut self, frame: Range<usize>) -> &[u8] {
+		self.consumed = frame.end;
+		self.buf.get(frame).unwrap_or_default()
+	}
+	/// Returns the part of the buffer into which more data is to be read.
+	pub fn spare(&mut self) -> &mut [u8] {
+		let want = self.filled.saturating_add(Self::READ_CHUNK);
+		if self.buf.len() < want {
+			self.buf.resize(want, 0);
+		}
+		self.buf.get_mut(self.filled..).unwrap_or_default()
+	}
+	/// Marks `n` bytes of the spare part of the buffer as filled, returning an error if end-of-file
+	/// was reached in the middle of a frame.
+	pub fn advance(&mut self, n: usize) -> io::Result<bool> {
+		if n == 0 {
+			return if self.filled == 0 {
+				Ok(false)
+			} else {
+				Err(io::Error::new(
+					io::ErrorKind::UnexpectedEof,
+					"end of file in the middle of a frame",
+				))
+			};
+		}
+		self.filled = self.filled.saturating_add(n).min(self.buf.len());
+		Ok(true)
+	}
+}
+
+/// Wrapper around a byte stream which sends and receives length-prefixed frames.
+///
+/// See the [module-level documentation](self) for more.
+///
+/// Data that has been read from the stream but not yet returned as a frame is stored in an
+/// internal buffer, and is lost if the stream is taken out of the wrapper with
+/// [`.into_inner()`](Self::into_inner). Frame receive errors other than interrupts leave the
+/// stream in an unknown state, after which it should be dropped.
+pub struct Framed<T> {
+	inner: T,
+	opts: FramingOptions,
+	rbuf: RecvBuf,
+	wbuf: Vec<u8>,
+}
+impl<T> Framed<T> {
+	/// Wraps the given stream with the default [options](FramingOptions).
+	#[inline]
+	pub fn new(inner: T) -> Self {
+		Self::with_options(inner, FramingOptions::new())
+	}
+	/// Wraps the given stream with the given options.
+	#[inline]
+	pub fn with_options(inner: T, options: FramingOptions) -> Self {
+		Self {
+			inner,
+			opts: options,
+			rbuf: RecvBuf::default(),
+			wbuf: Vec::new(),
+		}
+	}
+	/// Returns the options the wrapper was created with.
+	#[inline]
+	pub fn options(&self) -> &FramingOptions {
+		&self.opts
+	}
+	/// Borrows the wrapped stream.
+	#[inline]
+	pub fn get_ref(&self) -> &T {
+		&self.inner
+	}
+	/// Mutably borrows the wrapped stream. Reading from it directly will desynchronize the framing.
+	#[inline]
+	pub fn get_mut(&mut self) -> &mut T {
+		&mut self.inner
+	}
+	/// Unwraps the stream, discarding any buffered received data.
+	#[inline]
+	pub fn into_inner(self) -> T {
+		self.inner
+	}
+}
+impl<T: Write> Framed<T> {
+	/// Sends one frame containing `frame`.
+	pub fn send_frame(&mut self, frame: &[u8]) -> io::Result<()> {
+		self.wbuf.clear();
+		self.opts.encode_prefix(frame.len(), &mut self.wbuf)?;
+		self.wbuf.extend_from_slice(frame);
+		self.inner.write_all(&self.wbuf)?;
+		self.inner.flush()
+	}
+}
+impl<T: Read> Framed<T> {
+	/// Receives one frame, returning `None` if the stream reaches end-of-file at a frame boundary.
+	///
+	/// The returned slice borrows the receive buffer of the wrapper, which is reused for subsequent
+	/// frames.
+	pub fn recv_frame(&mut self) -> io::Result<Option<&[u8]>> {
+		self.rbuf.discard_consumed();
+		loop {
+			if let Some(frame) = self.rbuf.next_frame(&self.opts)? {
+				return Ok(Some(self.rbuf.take_frame(frame)));
+			}
+			let n = match self.inner.read(self.rbuf.spare()) {
+				Ok(n) => n,
+				Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+				Err(e) => return Err(e),
+			};
+			if !self.rbuf.advance(n)? {
+				return Ok(None);
+			}
+		}
+	}
+}
+impl Framed<Stream> {
+	/// Splits the stream into a receive half and a send half, keeping the framing state of both
+	/// directions.
+	pub fn split(self) -> (Framed<RecvHalf>, Framed<SendHalf>) {
+		let (rh, sh) = self.inner.split();
+		let rh = Framed {
+			inner: rh,
+			opts: self.opts,
+			rbuf: self.rbuf,
+			wbuf: Vec::new(),
+		};
+		let sh = Framed {
+			inner: sh,
+			opts: self.opts,
+			rbuf: RecvBuf::default(),
+			wbuf: self.wbuf,
+		};
+		(rh, sh)
+	}
+	/// Attempts to reunite a receive half with a send half to yield the original stream back,
+	/// returning both halves as an error if they belong to different streams.
+	///
+	/// The options of the receive half are used for the reunited stream.
+	#[allow(clippy::result_large_err)]
+	pub fn reunite(
+		rh: Framed<RecvHalf>,
+		sh: Framed<SendHalf>,
+	) -> ReuniteResult<Self, Framed<RecvHalf>, Framed<SendHalf>> {
+		match Stream::reunite(rh.inner, sh.inner) {
+			Ok(inner) => Ok(Framed {
+				inner,
+				opts: rh.opts,
+				rbuf: rh.rbuf,
+				wbuf: sh.wbuf,
+			}),
+			Err(ReuniteError { rh: rhi, sh: shi }) => Err(ReuniteError {
+				rh: Framed { inner: rhi, ..rh },
+				sh: Framed { inner: shi, ..sh },
+			}),
+		}
+	}
+}
+impl<T: Debug> Debug for Framed<T> {
+	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+		f.debug_struct("Framed")
+			.field("inner", &self.inner)
+			.field("options", &self.opts)
+			.finish_non_exhaustive()
+	}
+}
@@ -0,0 +1,585 @@
+//! JSON-RPC 2.0 messages and the message framing of the Language Server Protocol.
+//!
+//! The [base protocol] of the LSP precedes each message with a header section in the style of
+//! HTTP, of which only the `Content-Length` field is required, followed by an empty line:
+//! ```text
+//! Content-Length: 46\r\n
+//! \r\n
+//! {"jsonrpc":"2.0","method":"ping","params":[1]}
+//! ```
+//! [`LspFramed`] sends and receives messages framed that way, regardless of their contents. The
+//! [JSON-RPC 2.0] messages that are customarily sent with it are represented by [`Request`], which
+//! also covers notifications, and [`Response`], with errors described by [`ErrorObject`]. All of
+//! them can be converted from a [`serde_json::Value`] and implement `Serialize` and `Deserialize`.
+//!
+//! A Tokio-based client, which correlates responses with requests, and server, which dispatches
+//! requests to a handler, are available in the [`tokio::jsonrpc`](super::tokio::jsonrpc) module
+//! when the `tokio` feature is enabled.
+//!
+//! [base protocol]: https://microsoft.github.io/language-server-protocol/specifications/base/0.9/specification/
+//! [JSON-RPC 2.0]: https://www.jsonrpc.org/specification
+//!
+//! # Examples
+//! ```no_run
+//! use interprocess::local_socket::{
+//! 	jsonrpc::{LspFramed, Message, Request, Response},
+//! 	prelude::*,
+//! 	Stream,
+//! };
+//! use serde_json::json;
+//!
+//! let (a, b) = Stream::pair()?;
+//! let (mut client, mut server) = (LspFramed::new(a), LspFramed::new(b));
+//!
+//! let request = Request::new("ping", Some(json!([1])), 1);
+//! client.send_message(&serde_json::to_vec(&request)?)?;
+//!
+//! if let Some(msg) = server.recv_message()? {
+//! 	if let Ok(Message::Request(request)) = serde_json::from_slice(msg) {
+//! 		let response = Response::success(request.id, json!("pong"));
+//! 		server.send_message(&serde_json::to_vec(&response)?)?;
+//! 	}
+//! }
+//!
+//! let response: Option<Response> = client.recv_message()?.map(serde_json::from_slice).transpose()?;
+//! assert_eq!(response.map(|r| r.result), Some(Ok(json!("pong"))));
+//! # std::io::Result::<()>::Ok(())
+//! ```
+
+use super::framing::RecvBuf;
+use serde::{
+	de::{self, Deserializer},
+	ser::{SerializeMap, Serializer},
+	Deserialize, Serialize,
+};
+use serde_json::{Map, Value};
+use std::{
+	error::Error,
+	fmt::{self, Debug, Display, Formatter},
+	io::{self, prelude::*},
+};
+
+/// The default maximum size of the content of a message, which is 16 MiB.
+pub const DEFAULT_MAX_MESSAGE_SIZE: usize = 16 * 1024 * 1024;
+
+/// The method of the notification which asks the other end to cancel a request, as specified by
+/// the LSP. Its parameters are an object with the ID of the request in the `id` field.
+///
+/// JSON-RPC 2.0 itself doesn't specify a way to cancel requests.
+pub const CANCEL_METHOD: &str = "$/cancelRequest";
+
+/// The maximum size of a header section, beyond which it is rejected.
+const MAX_HEADER_SIZE: usize = 8192;
+
+fn invalid_header(msg: &str) -> io::Error {
+	io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+/// Parses the header section at the start of `buf` into its length, including the empty line,
+/// and the length of the content.
+pub(crate) fn decode_header(
+	buf: &[u8],
+	max_message_size: usize,
+) -> io::Result<Option<(usize, usize)>> {
+	let Some(end) = buf.windows(4).position(|w| w == b"\r\n\r\n") else {
+		return if buf.len() > MAX_HEADER_SIZE {
+			Err(invalid_header("header section is too long"))
+		} else {
+			Ok(None)
+		};
+	};
+	if end > MAX_HEADER_SIZE {
+		return Err(invalid_header("header section is too long"));
+	}
+	let mut len = None;
+	for line in buf.get(..end).unwrap_or_default().split(|&b| b == b'\n') {
+		let line = line.strip_suffix(b"\r").unwrap_or(line);
+		let mut parts = line.splitn(2, |&b| b == b':');
+		let (Some(name), Some(value)) = (parts.next(), parts.next()) else {
+			return Err(invalid_header("malformed header field"));
+		};
+		if !name.eq_ignore_ascii_case(b"Content-Length") {
+			continue;
+		}
+		let value = std::str::from_utf8(value)
+			.ok()
+			.and_then(|v| v.trim().parse::<usize>().ok())
+			.ok_or_else(|| invalid_header("invalid Content-Length"))?;
+		if len.replace(value).is_some() {
+			return Err(invalid_header("duplicate Content-Length"));
+		}
+	}
+	let len = len.ok_or_else(|| invalid_header("missing Content-Length"))?;
+	if len > max_message_size {
+		return Err(invalid_header("message exceeds maximum size"));
+	}
+	Ok(Some((end.saturating_add(4), len)))
+}
+
+/// Appends the header section for a message of length `len` to `buf`.
+pub(crate) fn encode_header(
+	len: usize,
+	max_message_size: usize,
+	buf: &mut Vec<u8>,
+) -> io::Result<()> {
+	if len > max_message_size {
+		return Err(io::Error::new(
+			io::ErrorKind::InvalidInput,
+			"message exceeds maximum size",
+		));
+	}
+	buf.extend_from_slice(format!("Content-Length: {len}\r\n\r\n").as_bytes());
+	Ok(())
+}
+
+/// Wrapper around a byte stream which sends and receives messages framed with `Content-Length`
+/// headers.
+///
+/// See the [module-level documentation](self) for more.
+///
+/// Header fields other than `Content-Length`, such as `Content-Type`, are accepted and ignored
+/// when receiving and never sent. Messages with content larger than the maximum message size are
+/// rejected with an error before any memory is allocated for them. As with
+/// [`Framed`](super::framing::Framed), data that has been read from the stream but not yet returned
+/// as a message is lost if the stream is taken out of the wrapper, and receive errors other than
+/// interrupts leave the stream in an unknown state.
+pub struct LspFramed<T> {
+	inner: T,
+	max_message_size: usize,
+	rbuf: RecvBuf,
+	wbuf: Vec<u8>,
+}
+impl<T> LspFramed<T> {
+	/// Wraps the given stream with the [default maximum message size](DEFAULT_MAX_MESSAGE_SIZE).
+	#[inline]
+	pub fn new(inner: T) -> Self {
+		Self::with_max_message_size(inner, DEFAULT_MAX_MESSAGE_SIZE)
+	}
+	/// Wraps the given stream with the given maximum message size.
+	#[inline]
+	pub fn with_max_message_size(inner: T, max_message_size: usize) -> Self {
+		Self {
+			inner,
+			max_message_size,
+			rbuf: RecvBuf::default(),
+			wbuf: Vec::new(),
+		}
+	}
+	/// Returns the maximum message size, not counting the header section.
+	#[inline]
+	pub fn max_message_size(&self) -> usize {
+		self.max_message_size
+	}
+	/// Borrows the wrapped stream.
+	#[inline]
+	pub fn get_ref(&self) -> &T {
+		&self.inner
+	}
+	/// Mutably borrows the wrapped stream. Reading from it directly will desynchronize the framing.
+	#[inline]
+	pub fn get_mut(&mut self) -> &mut T {
+		&mut self.inner
+	}
+	/// Unwraps the stream, discarding any buffered received data.
+	#[inline]
+	pub fn into_inner(self) -> T {
+		self.inner
+	}
+}
+impl<T: Write> LspFramed<T> {
+	/// Sends one message with `content` as its content.
+	pub fn send_message(&mut self, content: &[u8]) -> io::Result<()> {
+		self.wbuf.clear();
+		encode_header(content.len(), self.max_message_size, &mut self.wbuf)?;
+		self.wbuf.extend_from_slice(content);
+		self.inner.write_all(&self.wbuf)?;
+		self.inner.flush()
+	}
+}
+impl<T: Read> LspFramed<T> {
+	/// Receives one message and returns its content, or `None` if the stream reaches end-of-file
+	/// at a message boundary.
+	///
+	/// The returned slice borrows the receive buffer of the wrapper, which is reused for subsequent
+	/// messages.
+	pub fn recv_message(&mut self) -> io::Result<Option<&[u8]>> {
+		self.rbuf.discard_consumed();
+		let max = self.max_message_size;
+		loop {
+			if let Some(msg) = self.rbuf.next_frame_with(|b| decode_header(b, max))? {
+				return Ok(Some(self.rbuf.take_frame(msg)));
+			}
+			let n = match self.inner.read(self.rbuf.spare()) {
+				Ok(n) => n,
+				Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+				Err(e) => return Err(e),
+			};
+			if !self.rbuf.advance(n)? {
+				return Ok(None);
+			}
+		}
+	}
+}
+impl<T: Debug> Debug for LspFramed<T> {
+	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+		f.debug_struct("LspFramed")
+			.field("inner", &self.inner)
+			.field("max_message_size", &self.max_message_size)
+			.finish_non_exhaustive()
+	}
+}
+
+fn invalid_request(msg: &str) -> ErrorObject {
+	ErrorObject::new(ErrorObject::INVALID_REQUEST, msg)
+}
+
+/// Implements `Deserialize` in terms of `TryFrom<Value>`.
+macro_rules! deserialize_via_value {
+	($($ty:ident)+) => {$(
+		impl<'de> Deserialize<'de> for $ty {
+			fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+				Self::try_from(Value::deserialize(deserializer)?)
+					.map_err(|e| de::Error::custom(e.message))
+			}
+		}
+	)+};
+}
+deserialize_via_value!(Id ErrorObject Request Response Message);
+
+/// Identifier of a request, chosen by the client and copied into the response by the server.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Id {
+	/// An integer ID.
+	Number(i64),
+	/// A string ID.
+	String(String),
+}
+impl From<i64> for Id {
+	#[inline]
+	fn from(id: i64) -> Self {
+		Self::Number(id)
+	}
+}
+impl From<String> for Id {
+	#[inline]
+	fn from(id: String) -> Self {
+		Self::String(id)
+	}
+}
+impl From<&str> for Id {
+	#[inline]
+	fn from(id: &str) -> Self {
+		Self::String(id.to_owned())
+	}
+}
+impl TryFrom<Value> for Id {
+	type Error = ErrorObject;
+	fn try_from(value: Value) -> Result<Self, ErrorObject> {
+		match value {
+			Value::Number(n) => n
+				.as_i64()
+				.map(Self::Number)
+				.ok_or_else(|| invalid_request("ID is not an integer")),
+			Value::String(s) => Ok(Self::String(s)),
+			_ => Err(invalid_request("ID is neither a number nor a string")),
+		}
+	}
+}
+impl Display for Id {
+	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::Number(n) => Display::fmt(n, f),
+			Self::String(s) => Display::fmt(s, f),
+		}
+	}
+}
+impl Serialize for Id {
+	fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+		match self {
+			Self::Number(n) => serializer.serialize_i64(*n),
+			Self::String(s) => serializer.serialize_str(s),
+		}
+	}
+}
+
+/// Error object of a [`Response`] to a failed request.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ErrorObject {
+	/// The error code. The range from -32768 to -32000 is reserved for errors defined by the
+	/// JSON-RPC and LSP specifications, some of which are available as associated constants.
+	pub code: i64,
+	/// A short description of the error.
+	pub message: String,
+	/// Additional information about the error.
+	pub data: Option<Value>,
+}
+impl ErrorObject {
+	/// Invalid JSON was received.
+	pub const PARSE_ERROR: i64 = -32700;
+	/// The received JSON is not a valid request.
+	pub const INVALID_REQUEST: i64 = -32600;
+	/// The method does not exist or is not available.
+	pub const METHOD_NOT_FOUND: i64 = -32601;
+	/// The parameters of the method are invalid.
+	pub const INVALID_PARAMS: i64 = -32602;
+	/// Internal error of the server.
+	pub const INTERNAL_ERROR: i64 = -32603;
+	/// The request was cancelled, as defined by the LSP.
+	pub const REQUEST_CANCELLED: i64 = -32800;
+
+	/// Creates an error object without additional data.
+	#[inline]
+	pub fn new(code: i64, message: impl Into<String>) -> Self {
+		Self {
+			code,
+			message: message.into(),
+			data: None,
+		}
+	}
+	/// Attaches additional data to the error object.
+	#[must_use = "this is not an in-place operation"]
+	#[inline]
+	pub fn with_data(mut self, data: Value) -> Self {
+		self.data = Some(data);
+		self
+	}
+	/// Creates a [`METHOD_NOT_FOUND`](Self::METHOD_NOT_FOUND) error for the given method.
+	pub fn method_not_found(method: &str) -> Self {
+		Self::new(
+			Self::METHOD_NOT_FOUND,
+			format!("method not found: {method}"),
+		)
+	}
+	/// Creates an [`INVALID_PARAMS`](Self::INVALID_PARAMS) error.
+	#[inline]
+	pub fn invalid_params(message: impl Into<String>) -> Self {
+		Self::new(Self::INVALID_PARAMS, message)
+	}
+	/// Creates an [`INTERNAL_ERROR`](Self::INTERNAL_ERROR) error.
+	#[inline]
+	pub fn internal_error(message: impl Into<String>) -> Self {
+		Self::new(Self::INTERNAL_ERROR, message)
+	}
+}
+impl TryFrom<Value> for ErrorObject {
+	type Error = ErrorObject;
+	fn try_from(value: Value) -> Result<Self, ErrorObject> {
+		let Value::Object(mut obj) = value else {
+			return Err(invalid_request("error is not an object"));
+		};
+		let code = obj
+			.get("code")
+			.and_then(Value::as_i64)
+			.ok_or_else(|| invalid_request("error code is not an integer"))?;
+		let Some(Value::String(message)) = obj.remove("message") else {
+			return Err(invalid_request("error message is not a string"));
+		};
+		Ok(Self {
+			code,
+			message,
+			data: obj.remove("data"),
+		})
+	}
+}
+impl Display for ErrorObject {
+	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+		write!(f, "{} (error {})", self.message, self.code)
+	}
+}
+impl Error for ErrorObject {}
+impl Serialize for ErrorObject {
+	fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+		let mut map = serializer.serialize_map(None)?;
+		map.serialize_entry("code", &self.code)?;
+		map.serialize_entry("message", &self.message)?;
+		if let Some(data) = &self.data {
+			map.serialize_entry("data", data)?;
+		}
+		map.end()
+	}
+}
+
+/// Request or notification, the latter being a request without an ID to which no response is
+/// sent.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Request {
+	/// The name of the method to be invoked.
+	pub method: String,
+	/// The parameters of the method, which must be an array or an object if present.
+	pub params: Option<Value>,
+	/// The ID of the request, or `None` for notifications.
+	pub id: Option<Id>,
+}
+impl Request {
+	/// Creates a request with the given ID.
+	#[inline]
+	pub fn new(method: impl Into<String>, params: Option<Value>, id: impl Into<Id>) -> Self {
+		Self {
+			method: method.into(),
+			params,
+			id: Some(id.into()),
+		}
+	}
+	/// Creates a notification.
+	#[inline]
+	pub fn notification(method: impl Into<String>, params: Option<Value>) -> Self {
+		Self {
+			method: method.into(),
+			params,
+			id: None,
+		}
+	}
+	/// Returns `true` if the request is a notification, i.e. has no ID.
+	#[inline]
+	pub fn is_notification(&self) -> bool {
+		self.id.is_none()
+	}
+}
+impl TryFrom<Value> for Request {
+	type Error = ErrorObject;
+	fn try_from(value: Value) -> Result<Self, ErrorObject> {
+		match Message::try_from(value)? {
+			Message::Request(request) => Ok(request),
+			Message::Response(..) => Err(invalid_request("expected a request, got a response")),
+		}
+	}
+}
+impl Serialize for Request {
+	fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+		let mut map = serializer.serialize_map(None)?;
+		map.serialize_entry("jsonrpc", "2.0")?;
+		map.serialize_entry("method", &self.method)?;
+		if let Some(params) = &self.params {
+			map.serialize_entry("params", params)?;
+		}
+		if let Some(id) = &self.id {
+			map.serialize_entry("id", id)?;
+		}
+		map.end()
+	}
+}
+
+/// Response to a [`Request`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct Response {
+	/// The ID of the request, or `None` if it could not be determined because the request was
+	/// malformed.
+	pub id: Option<Id>,
+	/// The result of the request.
+	pub result: Result<Value, ErrorObject>,
+}
+impl Response {
+	/// Creates a response to a successful request.
+	#[inline]
+	pub fn success(id: Option<Id>, result: Value) -> Self {
+		Self {
+			id,
+			result: Ok(result),
+		}
+	}
+	/// Creates a response to a failed request.
+	#[inline]
+	pub fn error(id: Option<Id>, error: ErrorObject) -> Self {
+		Self {
+			id,
+			result: Err(error),
+		}
+	}
+}
+impl TryFrom<Value> for Response {
+	type Error = ErrorObject;
+	fn try_from(value: Value) -> Result<Self, ErrorObject> {
+		match Message::try_from(value)? {
+			Message::Response(response) => Ok(response),
+			Message::Request(..) => Err(invalid_request("expected a response, got a request")),
+		}
+	}
+}
+impl Serialize for Response {
+	fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+		let mut map = serializer.serialize_map(None)?;
+		map.serialize_entry("jsonrpc", "2.0")?;
+		match &self.result {
+			Ok(result) => map.serialize_entry("result", result)?,
+			Err(error) => map.serialize_entry("error", error)?,
+		}
+		map.serialize_entry("id", &self.id)?;
+		map.end()
+	}
+}
+
+/// Any JSON-RPC message other than a batch, which is an array of messages.
+///
+/// Conversion from a [`Value`] fails with an [`INVALID_REQUEST`](ErrorObject::INVALID_REQUEST)
+/// error object if the value is not a valid message.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Message {
+	/// A request or notification.
+	Request(Request),
+	/// A response.
+	Response(Response),
+}
+impl From<Request> for Message {
+	#[inline]
+	fn from(request: Request) -> Self {
+		Self::Request(request)
+	}
+}
+impl From<Response> for Message {
+	#[inline]
+	fn from(response: Response) -> Self {
+		Self::Response(response)
+	}
+}
+impl TryFrom<Value> for Message {
+	type Error = ErrorObject;
+	fn try_from(value: Value) -> Result<Self, ErrorObject> {
+		let Value::Object(mut obj) = value else {
+			return Err(invalid_request("message is not an object"));
+		};
+		if obj.get("jsonrpc").and_then(Value::as_str) != Some("2.0") {
+			return Err(invalid_request("missing or unsupported JSON-RPC version"));
+		}
+		match obj.remove("method") {
+			Some(method) => parse_request(method, obj).map(Self::Request),
+			None => parse_response(obj).map(Self::Response),
+		}
+	}
+}
+fn parse_request(method: Value, mut obj: Map<String, Value>) -> Result<Request, ErrorObject> {
+	let Value::String(method) = method else {
+		return Err(invalid_request("method is not a string"));
+	};
+	let params = match obj.remove("params") {
+		None => None,
+		Some(params @ (Value::Array(..) | Value::Object(..))) => Some(params),
+		Some(..) => return Err(invalid_request("params are neither an array nor an object")),
+	};
+	let id = obj.remove("id").map(Id::try_from).transpose()?;
+	Ok(Request { method, params, id })
+}
+fn parse_response(mut obj: Map<String, Value>) -> Result<Response, ErrorObject> {
+	let id = match obj.remove("id") {
+		None => return Err(invalid_request("message has neither a method nor an ID")),
+		Some(Value::Null) => None,
+		Some(id) => Some(Id::try_from(id)?),
+	};
+	let result = match (obj.remove("result"), obj.remove("error")) {
+		(Some(result), None) => Ok(result),
+		(None, Some(error)) => Err(ErrorObject::try_from(error)?),
+		_ => {
+			return Err(invalid_request(
+				"response must have either a result or an error",
+			))
+		}
+	};
+	Ok(Response { id, result })
+}
+impl Serialize for Message {
+	fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+		match self {
+			Self::Request(request) => request.serialize(serializer),
+			Self::Response(response) => response.serialize(serializer),
+		}
+	}
+}
@@ -1,6 +1,6 @@
 use super::r#trait;
-use crate::local_socket::{Name, Stream};
-use std::io;
+use crate::local_socket::{ExhaustionPolicy, Name, ShutdownHandle, Stream};
+use std::{io, time::Duration};
 #[cfg(unix)]
 use {crate::os::unix::uds_local_socket as uds_impl, std::os::unix::prelude::*};
 #[cfg(windows)]
@@ -32,13 +32,23 @@ mkenum!(
 /// [`.do_not_reclaim_name_on_drop()`](Self::do_not_reclaim_name_on_drop) or
 /// [`bind_without_name_reclamation()`](Self::bind_without_name_reclamation).
 ///
+/// Because of a bug, version 2.0.0 of Interprocess didn't perform name reclamation at all. Programs
+/// which worked around that by deleting the socket file themselves after dropping the listener will
+/// now see that deletion fail with [`NotFound`](io::ErrorKind::NotFound).
+///
 /// Note that the socket file can be unlinked by other programs at any time, retaining the inode the
 /// listener is bound to but making it inaccessible to peers if it was at its last hardlink. If that
 /// happens and another listener takes the same path before the first one performs name reclamation,
-/// the socket file deletion wouldn't correspond to the listener being closed, instead deleting the
-/// socket file of the second listener. If the second listener also performs name reclamation, the
-/// ensuing deletion will silently fail. Due to the awful design of Unix, this issue cannot be
-/// mitigated.
+/// the socket file at the path no longer corresponds to the first listener. To avoid deleting the
+/// socket file of the second listener, name reclamation checks that the path still refers to the
+/// inode it was bound to (by device and inode number) before unlinking it. The check and the
+/// deletion aren't atomic, so a listener taking the path in between the two can still lose its
+/// socket file, but the window for that is narrow.
+///
+/// This is also what allows
+/// [`bind_replacing()`](super::super::traits::Listener::bind_replacing) to replace the socket file
+/// of a running listener in a single atomic rename without the old listener deleting it afterwards,
+/// leaving no point in time at which clients would find no socket at the path.
 ///
 /// # Examples
 ///
@@ -156,14 +166,30 @@ impl r#trait::Listener for Listener {
 		dispatch::bind_without_name_reclamation(name)
 	}
 	#[inline]
+	fn bind_replacing(name: Name<'_>) -> io::Result<Self> {
+		dispatch::bind_replacing(name)
+	}
+	#[inline]
 	fn accept(&self) -> io::Result<Stream> {
 		dispatch!(Self: x in self => x.accept()).map(Stream::from)
 	}
 	#[inline]
+	fn accept_timeout(&self, timeout: Duration) -> io::Result<Stream> {
+		dispatch!(Self: x in self => x.accept_timeout(timeout)).map(Stream::from)
+	}
+	#[inline]
+	fn shutdown_handle(&self) -> io::Result<ShutdownHandle> {
+		dispatch!(Self: x in self => x.shutdown_handle())
+	}
+	#[inline]
 	fn set_nonblocking(&self, nonblocking: bool) -> io::Result<()> {
 		dispatch!(Self: x in self => x.set_nonblocking(nonblocking))
 	}
 	#[inline]
+	fn set_exhaustion_policy(&mut self, policy: Option<ExhaustionPolicy>) -> io::Result<()> {
+		dispatch!(Self: x in self => x.set_exhaustion_policy(policy))
+	}
+	#[inline]
 	fn do_not_reclaim_name_on_drop(&mut self) {
 		dispatch!(Self: x in self => x.do_not_reclaim_name_on_drop())
 	}
@@ -179,6 +205,28 @@ impl From<Listener> for OwnedHandle {
 	}
 }
 
+#[cfg(unix)]
+#[cfg_attr(feature = "doc_cfg", doc(cfg(unix)))]
+impl AsFd for Listener {
+	#[inline]
+	fn as_fd(&self) -> BorrowedFd<'_> {
+		match self {
+			Listener::UdSocket(l) => l.as_fd(),
+		}
+	}
+}
+
+/// Creates a [`UdSocket`](Listener::UdSocket) listener which does not perform
+/// [name reclamation](Listener#name-reclamation).
+#[cfg(unix)]
+#[cfg_attr(feature = "doc_cfg", doc(cfg(unix)))]
+impl From<OwnedFd> for Listener {
+	#[inline]
+	fn from(fd: OwnedFd) -> Self {
+		Self::UdSocket(fd.into())
+	}
+}
+
 #[cfg(unix)]
 #[cfg_attr(feature = "doc_cfg", doc(cfg(unix)))]
 impl From<Listener> for OwnedFd {
@@ -0,0 +1,240 @@
+use std::{
+	fmt::{self, Debug, Formatter},
+	io,
+	sync::Arc,
+	time::Duration,
+};
+#[cfg(unix)]
+use {
+	crate::{poison_error, LOCK_POISON},
+	std::{fs::File, os::unix::io::OwnedFd, sync::Mutex},
+};
+
+type Callback = Arc<dyn Fn(&ExhaustionEvent<'_>) + Send + Sync>;
+
+/// How a listener deals with running out of file descriptors, set with
+/// [`.set_exhaustion_policy()`](super::super::traits::Listener::set_exhaustion_policy).
+///
+/// When the process or the system runs out of file descriptors, accepting fails with `EMFILE` or
+/// `ENFILE` without taking the pending connection off the backlog, which means that the next call
+/// fails in the same way right away, and a server loop that keeps accepting ends up spinning. With
+/// a policy set, the listener instead:
+/// -	closes a spare file descriptor it keeps for this purpose, uses it to accept the pending
+///   	connection and closes that immediately, so that the client learns of the failure instead of
+///   	waiting in the backlog forever, then reopens the spare;
+/// -	reports the failure to the [callback](Self::on_exhaustion), if there is one;
+/// -	waits for an exponentially growing amount of time before trying again, which resets once a
+///   	connection is accepted successfully.
+///
+/// Waiting happens within `.accept()` in blocking mode and on Tokio, which thus doesn't return the
+/// error at all. In nonblocking mode, the error is returned after the pending connection is shed,
+/// and backing off is left to the caller, guided by the [`backoff`](ExhaustionEvent::backoff)
+/// reported to the callback.
+///
+/// # Platform-specific behavior
+/// ## Windows
+/// Named pipe listeners are not limited by anything like the file descriptor table, so the policy
+/// has no effect.
+#[derive(Clone)]
+#[non_exhaustive]
+pub struct ExhaustionPolicy {
+	/// Whether to keep a spare file descriptor open (to `/dev/null`) for shedding pending
+	/// connections.
+	///
+	/// The default value is `true`.
+	pub reserve_fd: bool,
+	/// How long to wait after the first failure.
+	///
+	/// The default value is 10 milliseconds.
+	pub initial_backoff: Duration,
+	/// The longest the wait after a failure can grow to.
+	///
+	/// The default value is 1 second.
+	pub max_backoff: Duration,
+	/// Function to call every time accepting fails because of file descriptor exhaustion.
+	///
+	/// The default value is `None`.
+	pub on_exhaustion: Option<Callback>,
+}
+impl ExhaustionPolicy {
+	/// Creates a policy with default values.
+	#[inline]
+	pub const fn new() -> Self {
+		Self {
+			reserve_fd: true,
+			initial_backoff: Duration::from_millis(10),
+			max_backoff: Duration::from_secs(1),
+			on_exhaustion: None,
+		}
+	}
+	/// Sets whether a spare file descriptor is kept.
+	///
+	/// See the [associated field](#structfield.reserve_fd) for more.
+	#[must_use = "this is not an in-place operation"]
+	#[inline]
+	pub fn reserve_fd(mut self, reserve_fd: bool) -> Self {
+		self.reserve_fd = reserve_fd;
+		self
+	}
+	/// Sets the wait after the first failure.
+	///
+	/// See the [associated field](#structfield.initial_backoff) for more.
+	#[must_use = "this is not an in-place operation"]
+	#[inline]
+	pub fn initial_backoff(mut self, initial_backoff: Duration) -> Self {
+		self.initial_backoff = initial_backoff;
+		self
+	}
+	/// Sets the longest wait after a failure.
+	///
+	/// See the [associated field](#structfield.max_backoff) for more.
+	#[must_use = "this is not an in-place operation"]
+	#[inline]
+	pub fn max_backoff(mut self, max_backoff: Duration) -> Self {
+		self.max_backoff = max_backoff;
+		self
+	}
+	/// Sets the function to call on every failure.
+	///
+	/// See the [associated field](#structfield.on_exhaustion) for more.
+	#[must_use = "this is not an in-place operation"]
+	#[inline]
+	pub fn on_exhaustion(
+		mut self,
+		on_exhaustion: impl Fn(&ExhaustionEvent<'_>) + Send + Sync + 'static,
+	) -> Self {
+		self.on_exhaustion = Some(Arc::new(on_exhaustion));
+		self
+	}
+}
+impl Default for ExhaustionPolicy {
+	#[inline]
+	fn default() -> Self {
+		Self::new()
+	}
+}
+impl Debug for ExhaustionPolicy {
+	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+		f.debug_struct("ExhaustionPolicy")
+			.field("reserve_fd", &self.reserve_fd)
+			.field("initial_backoff", &self.initial_backoff)
+			.field("max_backoff", &self.max_backoff)
+			.field("on_exhaustion", &self.on_exhaustion.is_some())
+			.finish()
+	}
+}
+
+/// Information about a failure to accept a connection because of file descriptor exhaustion,
+/// passed to the [callback](ExhaustionPolicy::on_exhaustion) of an [`ExhaustionPolicy`].
+#[derive(Debug)]
+#[non_exhaustive]
+pub struct ExhaustionEvent<'a> {
+	/// The error accepting failed with.
+	pub error: &'a io::Error,
+	/// Whether a pending connection was accepted with the spare file descriptor and closed.
+	pub shed_connection: bool,
+	/// How long the listener waits before trying again.
+	pub backoff: Duration,
+	/// The number of such failures since the policy was set, including this one.
+	pub count: u64,
+}
+
+/// A policy in effect on a listener, together with its state.
+#[cfg(unix)]
+pub(crate) struct Exhaustion {
+	policy: ExhaustionPolicy,
+	state: Mutex<ExhaustionState>,
+}
+#[cfg(unix)]
+struct ExhaustionState {
+	reserve: Option<OwnedFd>,
+	/// The wait after the next failure, or zero if the last accept succeeded.
+	backoff: Duration,
+	count: u64,
+}
+#[cfg(unix)]
+impl Exhaustion {
+	pub fn new(policy: ExhaustionPolicy) -> io::Result<Self> {
+		let reserve = policy.reserve_fd.then(open_reserve).transpose()?;
+		Ok(Self {
+			policy,
+			state: Mutex::new(ExhaustionState {
+				reserve,
+				backoff: Duration::ZERO,
+				count: 0,
+			}),
+		})
+	}
+	/// Handles a failure to accept a connection, using `shed` to accept the pending connection and
+	/// close it once a file descriptor is freed up. Returns how long to wait before trying again.
+	pub fn handle(&self, error: &io::Error, shed: impl FnOnce() -> bool) -> io::Result<Duration> {
+		let (shed_connection, backoff, count) = {
+			let mut state = self.state.lock().map_err(poison_error)?;
+			let shed_connection = match state.reserve.take() {
+				Some(reserve) => {
+					drop(reserve);
+					let shed = shed();
+					// If another thread took the descriptor in the meantime, this is retried the
+					// next time around.
+					state.reserve = open_reserve().ok();
+					shed
+				}
+				None => {
+					if self.policy.reserve_fd {
+						state.reserve = open_reserve().ok();
+					}
+					false
+				}
+			};
+			let backoff = if state.backoff.is_zero() {
+				self.policy.initial_backoff
+			} else {
+				state.backoff
+			};
+			state.backoff = backoff.saturating_mul(2).min(self.policy.max_backoff);
+			state.count = state.count.saturating_add(1);
+			(
+				shed_connection,
+				backoff.min(self.policy.max_backoff),
+				state.count,
+			)
+		};
+		if let Some(callback) = &self.policy.on_exhaustion {
+			callback(&ExhaustionEvent {
+				error,
+				shed_connection,
+				backoff,
+				count,
+			});
+		}
+		Ok(backoff)
+	}
+	#[inline]
+	pub fn policy(&self) -> &ExhaustionPolicy {
+		&self.policy
+	}
+	/// Resets the backoff after a connection is accepted successfully.
+	pub fn reset(&self) {
+		let mut state = self.state.lock().expect(LOCK_POISON);
+		state.backoff = Duration::ZERO;
+	}
+}
+#[cfg(unix)]
+impl Debug for Exhaustion {
+	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+		f.debug_struct("Exhaustion")
+			.field("policy", &self.policy)
+			.finish_non_exhaustive()
+	}
+}
+
+#[cfg(unix)]
+fn open_reserve() -> io::Result<OwnedFd> {
+	File::open("/dev/null").map(OwnedFd::from)
+}
+
+/// Returns whether accepting failed because there are no file descriptors left.
+#[cfg(unix)]
+pub(crate) fn is_exhaustion(error: &io::Error) -> bool {
+	matches!(error.raw_os_error(), Some(libc::EMFILE | libc::ENFILE))
+}
@@ -0,0 +1,94 @@
+use crate::error::ListenerShutdownError;
+use std::{
+	fmt::{self, Debug, Formatter},
+	io,
+	sync::{
+		atomic::{AtomicBool, Ordering::*},
+		Arc,
+	},
+};
+#[cfg(unix)]
+use std::{
+	io::Write,
+	os::unix::{io::AsFd, net::UnixStream, prelude::BorrowedFd},
+};
+
+/// A handle which interrupts the blocking operations of a [`Listener`](super::super::Listener),
+/// obtained with [`.shutdown_handle()`](super::super::traits::Listener::shutdown_handle).
+///
+/// Once [`.shutdown()`](Self::shutdown) is called,
+/// [`.accept()`](super::super::traits::Listener::accept) and
+/// [`.accept_timeout()`](super::super::traits::Listener::accept_timeout) fail with a
+/// [`ListenerShutdownError`], including calls which were already blocked at the time, and
+/// [`.incoming()`](super::super::traits::ListenerExt::incoming) ends. The handle can be cloned and
+/// sent to other threads, such as one which handles signals.
+#[derive(Clone)]
+pub struct ShutdownHandle(pub(crate) Arc<ShutdownState>);
+impl ShutdownHandle {
+	/// Shuts the listener down. This cannot be undone.
+	#[inline]
+	pub fn shutdown(&self) {
+		self.0.shutdown();
+	}
+	/// Returns `true` if the listener has been shut down.
+	#[inline]
+	pub fn is_shut_down(&self) -> bool {
+		self.0.is_shut_down()
+	}
+}
+impl Debug for ShutdownHandle {
+	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+		f.debug_struct("ShutdownHandle")
+			.field("shut_down", &self.is_shut_down())
+			.finish()
+	}
+}
+
+/// State shared between a listener and its shutdown handles.
+///
+/// On Unix, a socket pair is used to wake up threads waiting for a connection: shutting down makes
+/// its receiving end readable forever, which `poll()` in the listener picks up.
+pub(crate) struct ShutdownState {
+	shut_down: AtomicBool,
+	#[cfg(unix)]
+	wake_rx: UnixStream,
+	#[cfg(unix)]
+	wake_tx: UnixStream,
+}
+impl ShutdownState {
+	pub fn new() -> io::Result<Self> {
+		#[cfg(unix)]
+		let (wake_rx, wake_tx) = {
+			let (rx, tx) = UnixStream::pair()?;
+			tx.set_nonblocking(true)?;
+			(rx, tx)
+		};
+		Ok(Self {
+			shut_down: AtomicBool::new(false),
+			#[cfg(unix)]
+			wake_rx,
+			#[cfg(unix)]
+			wake_tx,
+		})
+	}
+	pub fn shutdown(&self) {
+		if !self.shut_down.swap(true, AcqRel) {
+			// The byte is never received, so if the buffer is somehow full, it's readable already.
+			#[cfg(unix)]
+			let _ = (&self.wake_tx).write(&[0]);
+		}
+	}
+	#[inline]
+	pub fn is_shut_down(&self) -> bool {
+		self.shut_down.load(Acquire)
+	}
+	#[cfg(unix)]
+	#[inline]
+	pub fn wake_fd(&self) -> BorrowedFd<'_> {
+		self.wake_rx.as_fd()
+	}
+}
+
+pub(crate) fn shutdown_error() -> io::Error {
+	ListenerShutdownError.into()
+}
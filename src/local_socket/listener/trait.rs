@@ -1,8 +1,10 @@
+use super::{exhaustion::ExhaustionPolicy, shutdown::ShutdownHandle};
 use crate::{
+	error::ListenerShutdownError,
 	local_socket::{stream::r#trait::Stream, Name},
 	Sealed,
 };
-use std::{io, iter::FusedIterator};
+use std::{io, iter::FusedIterator, time::Duration};
 
 /// Local socket server implementations.
 ///
@@ -23,6 +25,28 @@ pub trait Listener: Sized + Sealed {
 	/// memory allocation.
 	fn bind_without_name_reclamation(name: Name<'_>) -> io::Result<Self>;
 
+	/// Like [`bind()`](Listener::bind), but atomically replaces the socket file of any listener
+	/// already bound to the name instead of failing with [`AddrInUse`](io::ErrorKind::AddrInUse).
+	///
+	/// This is meant for restarting a server: clients connect to the old listener right up until
+	/// the new one takes over, and never find the socket missing in between. The old listener keeps
+	/// accepting the connections already queued on its socket, but no new ones, and doesn't unlink
+	/// the socket file of the new one once dropped.
+	///
+	/// # Platform-specific behavior
+	/// ## Unix
+	/// The socket is bound to a temporary path in the same directory, made by appending a suffix to
+	/// the path of the name, or by replacing the file name with the suffix if the former is too
+	/// long to fit into a socket address, which is then renamed over the path of the name.
+	/// Namespaced names cannot be replaced, and are bound to the same way as with `bind()`.
+	///
+	/// Only a socket file is replaced: if the path is taken by any other kind of file, this fails
+	/// with [`AddrInUse`](io::ErrorKind::AddrInUse), the same as `bind()`. The check and the
+	/// rename aren't atomic, so a file created at the path in between the two is still replaced.
+	/// ## Windows
+	/// Named pipes have no files to replace, so this is the same as `bind()`.
+	fn bind_replacing(name: Name<'_>) -> io::Result<Self>;
+
 	/// Listens for incoming connections to the socket, blocking until a client is connected.
 	///
 	/// See [`.incoming()`](ListenerExt::incoming) for a convenient way to create a main loop for a
@@ -30,6 +54,39 @@ pub trait Listener: Sized + Sealed {
 	// TODO incoming
 	fn accept(&self) -> io::Result<Self::Stream>;
 
+	/// Like [`.accept()`](Self::accept), but gives up after the given amount of time, failing with
+	/// an error of kind [`TimedOut`](io::ErrorKind::TimedOut).
+	///
+	/// In nonblocking mode, this behaves the same as `.accept()`.
+	fn accept_timeout(&self, timeout: Duration) -> io::Result<Self::Stream>;
+
+	/// Returns a handle which can be used to shut the listener down from another thread, making
+	/// [`.accept()`](Self::accept) calls that are blocked at the time or made afterwards fail with
+	/// a [`ListenerShutdownError`] and [`.incoming()`](ListenerExt::incoming) end.
+	///
+	/// # Platform-specific behavior
+	/// ## Unix
+	/// A socket pair is created the first time this method or
+	/// [`.accept_timeout()`](Self::accept_timeout) is called, and waiting for connections is
+	/// performed with `poll()` from then on, with the socket switched to nonblocking mode.
+	///
+	/// The nonblocking mode is shared by all file descriptors duplicated from the socket, including
+	/// ones in other processes, such as the workers of a pre-fork server or the process a listener
+	/// was handed over to. Listeners from this crate keep blocking in `.accept()` regardless, but
+	/// other code accepting connections on the same socket, such as a `UnixListener` from the
+	/// standard library, gets [`WouldBlock`](io::ErrorKind::WouldBlock) errors instead.
+	/// ## Windows
+	/// Named pipes cannot be waited on together with other objects without overlapped I/O, so the
+	/// listener is switched to nonblocking mode internally and checked for new connections every 10
+	/// milliseconds from the first time this method or `.accept_timeout()` is called onwards.
+	///
+	/// Overlapped I/O isn't used because it would have to be enabled for the streams produced by
+	/// the listener as well, and those perform synchronous I/O, which overlapped pipe handles do not
+	/// support. Polling delays accepting a connection or noticing a shutdown by up to 10
+	/// milliseconds, which is insignificant next to the cost of establishing a connection in most
+	/// programs, and costs little CPU time, since the thread sleeps in between.
+	fn shutdown_handle(&self) -> io::Result<ShutdownHandle>;
+
 	/// Enables or disables the nonblocking mode for the listener. By default, it is disabled.
 	///
 	/// In nonblocking mode, calling [`.accept()`] and iterating through [`.incoming()`] will
@@ -45,6 +102,15 @@ pub trait Listener: Sized + Sealed {
 	/// [`.incoming()`]: ListenerExt::incoming
 	fn set_nonblocking(&self, nonblocking: bool) -> io::Result<()>;
 
+	/// Sets how the listener deals with running out of file descriptors, or restores the default
+	/// behavior of returning the error right away if `None` is passed.
+	///
+	/// See [`ExhaustionPolicy`] for more.
+	///
+	/// # Errors
+	/// Fails if the spare file descriptor cannot be opened.
+	fn set_exhaustion_policy(&mut self, policy: Option<ExhaustionPolicy>) -> io::Result<()>;
+
 	/// Disables [name reclamation](#name-reclamation) on the listener.
 	// TODO link this
 	fn do_not_reclaim_name_on_drop(&mut self);
@@ -53,8 +119,11 @@ pub trait Listener: Sized + Sealed {
 
 /// Methods derived from the interface of [`Listener`].
 pub trait ListenerExt: Listener {
-	/// Creates an infinite iterator which calls [`.accept()`](Self::accept) with each iteration.
-	/// Used together with `for` loops to conveniently create a main loop for a socket server.
+	/// Creates an iterator which calls [`.accept()`](Self::accept) with each iteration. Used together
+	/// with `for` loops to conveniently create a main loop for a socket server.
+	///
+	/// The iterator is infinite unless the listener is shut down through a [`ShutdownHandle`], in
+	/// which case it ends.
 	#[inline]
 	fn incoming(&self) -> Incoming<'_, Self> {
 		self.into()
@@ -62,7 +131,8 @@ pub trait ListenerExt: Listener {
 }
 impl<T: Listener> ListenerExt for T {}
 
-/// An infinite iterator over incoming client connections of a [`Listener`].
+/// An iterator over incoming client connections of a [`Listener`], which only ends when the listener
+/// is [shut down](Listener::shutdown_handle).
 ///
 /// This iterator is created by the [`incoming()`](ListenerExt::incoming) method on
 /// [`ListenerExt`] – see its documentation for more.
@@ -78,10 +148,10 @@ impl<'a, L: Listener> From<&'a L> for Incoming<'a, L> {
 impl<L: Listener> Iterator for Incoming<'_, L> {
 	type Item = io::Result<L::Stream>;
 	fn next(&mut self) -> Option<Self::Item> {
-		Some(self.listener.accept())
-	}
-	fn size_hint(&self) -> (usize, Option<usize>) {
-		(usize::MAX, None)
+		match self.listener.accept() {
+			Err(e) if ListenerShutdownError::is(&e) => None,
+			els => Some(els),
+		}
 	}
 }
 impl<L: Listener> FusedIterator for Incoming<'_, L> {}
@@ -0,0 +1,524 @@
+//! A thread pool server runner for local socket listeners.
+//!
+//! [`serve()`] accepts connections from a [`Listener`] on a background thread and hands each of
+//! them to one of a fixed number of worker threads, which call the handler with it. Connections
+//! are only accepted while fewer than the [maximum number](ServerOptions::max_connections) of them
+//! are being handled or waiting for a worker, so that a burst of clients waits in the backlog of
+//! the listener instead of piling up in memory.
+//!
+//! A handler which panics only ends the connection it was handling: the panic is reported by the
+//! panic hook, as usual, and the worker thread moves on to the next connection.
+//!
+//! # Examples
+//! ```no_run
+//! use interprocess::local_socket::{
+//! 	prelude::*,
+//! 	server::{serve, ServerOptions},
+//! 	Listener, Stream, ToNsName,
+//! };
+//! use std::io::{self, BufRead, BufReader, Write};
+//!
+//! let listener = Listener::bind("example-server.sock".to_ns_name()?)?;
+//! let server = serve(
+//! 	listener,
+//! 	|conn: Stream| {
+//! 		let mut conn = BufReader::new(conn);
+//! 		let mut line = String::new();
+//! 		if conn.read_line(&mut line).is_ok() {
+//! 			let _ = conn.get_mut().write_all(line.as_bytes());
+//! 		}
+//! 	},
+//! 	ServerOptions::new().max_connections(64),
+//! )?;
+//!
+//! // Stop the server from elsewhere, such as a signal handling thread.
+//! let shutdown = server.shutdown_handle();
+//! std::thread::spawn(move || shutdown.shutdown());
+//!
+//! server.join()?;
+//! # io::Result::<()>::Ok(())
+//! ```
+//!
+//! # Running programs per connection
+//! [`serve_exec()`] runs a program for every connection instead, with the connection as its
+//! standard input and output, in the manner of `inetd`. This turns tools which read requests from
+//! standard input and write responses to standard output into local socket services:
+//! ```no_run
+//! use interprocess::local_socket::{
+//! 	prelude::*,
+//! 	server::{serve_exec, ExecOptions},
+//! 	Listener, ToFsName,
+//! };
+//! use std::process::Command;
+//!
+//! let listener = Listener::bind("/run/example/bc.sock".to_fs_name()?)?;
+//! let server = serve_exec(listener, || Command::new("bc"), ExecOptions::new())?;
+//! server.join()?;
+//! # std::io::Result::<()>::Ok(())
+//! ```
+
+use super::{
+	traits::{Listener as _, Stream as _},
+	Listener, Stream,
+};
+use crate::{poison_error, LOCK_POISON};
+#[cfg(unix)]
+use std::os::unix::io::{AsFd, OwnedFd};
+#[cfg(windows)]
+use std::os::windows::io::OwnedHandle;
+use std::{
+	collections::VecDeque,
+	fmt::{self, Debug, Formatter},
+	io,
+	num::NonZeroUsize,
+	panic::{catch_unwind, AssertUnwindSafe},
+	process::{Child, Command, Stdio},
+	sync::{Arc, Condvar, Mutex},
+	thread::{self, JoinHandle},
+	time::{Duration, Instant},
+};
+
+/// How often the listener is polled for new connections, which bounds how long shutting down
+/// takes.
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+/// How long to wait after the first transient failure to accept a connection, doubling with every
+/// consecutive failure after that.
+pub(crate) const INITIAL_BACKOFF: Duration = Duration::from_millis(10);
+/// The longest the wait after a transient failure to accept a connection can grow to.
+pub(crate) const MAX_BACKOFF: Duration = Duration::from_secs(1);
+
+/// Returns whether accepting a connection failed in a way which is expected to clear up on its own,
+/// such as the process running out of file descriptors or the client giving up before it was
+/// accepted, in which case servers back off and try again instead of stopping.
+pub(crate) fn is_transient(error: &io::Error) -> bool {
+	#[cfg(unix)]
+	if matches!(
+		error.raw_os_error(),
+		Some(libc::EMFILE | libc::ENFILE | libc::ENOBUFS | libc::EPROTO)
+	) {
+		return true;
+	}
+	matches!(
+		error.kind(),
+		io::ErrorKind::ConnectionAborted
+			| io::ErrorKind::ConnectionReset
+			| io::ErrorKind::Interrupted
+			| io::ErrorKind::OutOfMemory
+	)
+}
+
+/// Returns the wait after a transient failure to accept a connection and updates `backoff` for the
+/// next one.
+pub(crate) fn next_backoff(backoff: &mut Duration) -> Duration {
+	let wait = if backoff.is_zero() {
+		INITIAL_BACKOFF
+	} else {
+		*backoff
+	};
+	*backoff = wait.saturating_mul(2).min(MAX_BACKOFF);
+	wait
+}
+
+/// Configuration of [`serve()`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub struct ServerOptions {
+	/// The number of worker threads, each of which handles one connection at a time, or `None` to
+	/// use the [available parallelism](thread::available_parallelism) of the system.
+	///
+	/// The default value is `None`.
+	pub worker_threads: Option<NonZeroUsize>,
+	/// The maximum number of accepted connections which are being handled or waiting for a free
+	/// worker thread, or `None` to use the number of worker threads. No more connections are
+	/// accepted while this many are open.
+	///
+	/// The default value is `None`.
+	pub max_connections: Option<NonZeroUsize>,
+}
+impl ServerOptions {
+	/// Creates an options table with default values.
+	#[inline]
+	pub const fn new() -> Self {
+		Self {
+			worker_threads: None,
+			max_connections: None,
+		}
+	}
+	/// Sets the number of worker threads. Zero is treated as `None`.
+	///
+	/// See the [associated field](#structfield.worker_threads) for more.
+	#[must_use = "this is not an in-place operation"]
+	#[inline]
+	pub fn worker_threads(mut self, worker_threads: usize) -> Self {
+		self.worker_threads = NonZeroUsize::new(worker_threads);
+		self
+	}
+	/// Sets the maximum number of open connections. Zero is treated as `None`.
+	///
+	/// See the [associated field](#structfield.max_connections) for more.
+	#[must_use = "this is not an in-place operation"]
+	#[inline]
+	pub fn max_connections(mut self, max_connections: usize) -> Self {
+		self.max_connections = NonZeroUsize::new(max_connections);
+		self
+	}
+}
+impl Default for ServerOptions {
+	#[inline]
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+/// Configuration of [`serve_exec()`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub struct ExecOptions {
+	/// Configuration of the thread pool, each thread of which waits for one child process at a
+	/// time. The number of worker threads is thus the largest number of child processes that run
+	/// at once.
+	///
+	/// The default value is [`ServerOptions::new()`].
+	pub server: ServerOptions,
+	/// Whether to tell the child process who is on the other end of the connection, through the
+	/// environment variables that UCSPI's `unixserver` sets:
+	/// -	`PROTO`, set to `UNIX`;
+	/// -	`UNIXREMOTEEUID` and `UNIXREMOTEEGID`, the effective user and group IDs of the client;
+	/// -	`UNIXREMOTEPID`, the process ID of the client, where the platform provides it.
+	///
+	/// The default value is `false`.
+	///
+	/// # Platform-specific behavior
+	/// ## Unix
+	/// The credentials are available on Linux, Android, macOS, iOS and the BSDs, and the process ID
+	/// only on Linux and Android. Connections are closed without running the program if the
+	/// credentials cannot be retrieved.
+	/// ## Windows
+	/// Named pipes don't provide credentials in this form, so this has no effect.
+	pub peer_credentials: bool,
+}
+impl ExecOptions {
+	/// Creates an options table with default values.
+	#[inline]
+	pub const fn new() -> Self {
+		Self {
+			server: ServerOptions::new(),
+			peer_credentials: false,
+		}
+	}
+	/// Sets the configuration of the thread pool.
+	///
+	/// See the [associated field](#structfield.server) for more.
+	#[must_use = "this is not an in-place operation"]
+	#[inline]
+	pub fn server(mut self, server: ServerOptions) -> Self {
+		self.server = server;
+		self
+	}
+	/// Sets whether the credentials of the client are passed to the child process.
+	///
+	/// See the [associated field](#structfield.peer_credentials) for more.
+	#[must_use = "this is not an in-place operation"]
+	#[inline]
+	pub fn peer_credentials(mut self, peer_credentials: bool) -> Self {
+		self.peer_credentials = peer_credentials;
+		self
+	}
+}
+impl Default for ExecOptions {
+	#[inline]
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+#[derive(Default)]
+struct State {
+	/// Accepted connections waiting for a worker thread.
+	queue: VecDeque<Stream>,
+	/// The number of connections that are queued or being handled.
+	open: usize,
+	shutdown: bool,
+}
+
+#[derive(Default)]
+struct Shared {
+	state: Mutex<State>,
+	/// Notified whenever the state changes.
+	changed: Condvar,
+}
+impl Shared {
+	fn shutdown(&self) {
+		self.state.lock().expect(LOCK_POISON).shutdown = true;
+		self.changed.notify_all();
+	}
+}
+
+/// Starts serving connections accepted from `listener` by calling `handler` on a pool of worker
+/// threads, returning a handle to the running server.
+///
+/// The listener is switched to nonblocking mode and polled, so that the server can stop accepting
+/// as soon as it's [shut down](Server::shutdown).
+///
+/// Failures to accept a connection which are expected to clear up on their own, such as running
+/// out of file descriptors (`EMFILE` and `ENFILE`) or the client hanging up before it is accepted
+/// (`ECONNABORTED`), make the server wait for a while and try again, with the wait growing from
+/// 10 milliseconds up to 1 second as long as they keep happening. Setting an
+/// [`ExhaustionPolicy`](super::ExhaustionPolicy) on the listener beforehand additionally sheds the
+/// pending connection and reports running out of file descriptors. Any other failure stops the
+/// server.
+///
+/// # Errors
+/// Fails if the listener cannot be switched to nonblocking mode or if a thread cannot be spawned.
+pub fn serve<H>(listener: Listener, handler: H, options: ServerOptions) -> io::Result<Server>
+where
+	H: Fn(Stream) + Send + Sync + 'static,
+{
+	listener.set_nonblocking(true)?;
+	let worker_threads = options
+		.worker_threads
+		.or_else(|| thread::available_parallelism().ok())
+		.map_or(1, NonZeroUsize::get);
+	let max_connections = options
+		.max_connections
+		.map_or(worker_threads, NonZeroUsize::get);
+	let shared = Arc::new(Shared::default());
+	let handler = Arc::new(handler);
+
+	let mut workers = Vec::with_capacity(worker_threads);
+	for _ in 0..worker_threads {
+		let worker = {
+			let (shared, handler) = (Arc::clone(&shared), Arc::clone(&handler));
+			thread::Builder::new()
+				.name("interprocess server worker".to_owned())
+				.spawn(move || work(&shared, &*handler))
+		};
+		match worker {
+			Ok(worker) => workers.push(worker),
+			Err(e) => {
+				shared.shutdown();
+				return Err(e);
+			}
+		}
+	}
+	let acceptor = {
+		let shared = Arc::clone(&shared);
+		thread::Builder::new()
+			.name("interprocess server acceptor".to_owned())
+			.spawn(move || {
+				let rslt = accept_loop(&listener, &shared, max_connections);
+				// Lets the workers finish the connections that are left and exit.
+				shared.shutdown();
+				rslt
+			})
+	};
+	let acceptor = match acceptor {
+		Ok(acceptor) => acceptor,
+		Err(e) => {
+			shared.shutdown();
+			return Err(e);
+		}
+	};
+	Ok(Server {
+		shared,
+		acceptor,
+		workers,
+	})
+}
+
+/// Like [`serve()`], but handles every connection by spawning the command returned by `command`
+/// with the connection as its standard input and output, then waiting for the child process to
+/// exit.
+///
+/// Standard input and output are always set to the connection, overriding what `command` sets
+/// them to. Everything else, including standard error, is left as configured. The connection is
+/// closed once the child process has closed it and exited, or right away if it cannot be spawned.
+///
+/// # Errors
+/// Same as [`serve()`].
+pub fn serve_exec<C>(listener: Listener, command: C, options: ExecOptions) -> io::Result<Server>
+where
+	C: Fn() -> Command + Send + Sync + 'static,
+{
+	let peer_credentials = options.peer_credentials;
+	let handler = move |conn| {
+		// Failures only close the connection, since there is no one to report them to.
+		if let Ok(mut child) = spawn(conn, command(), peer_credentials) {
+			let _ = child.wait();
+		}
+	};
+	serve(listener, handler, options.server)
+}
+
+fn spawn(conn: Stream, mut command: Command, peer_credentials: bool) -> io::Result<Child> {
+	#[cfg(unix)]
+	if peer_credentials {
+		let cred = crate::os::unix::peer_credentials(conn.as_fd())?;
+		command
+			.env("PROTO", "UNIX")
+			.env("UNIXREMOTEEUID", cred.euid.to_string())
+			.env("UNIXREMOTEEGID", cred.egid.to_string());
+		match cred.pid {
+			Some(pid) => command.env("UNIXREMOTEPID", pid.to_string()),
+			None => command.env_remove("UNIXREMOTEPID"),
+		};
+	}
+	#[cfg(windows)]
+	let _ = peer_credentials;
+	let (stdin, stdout) = into_stdio(conn)?;
+	// The command is dropped once this returns, which closes the copies of the connection it holds,
+	// so that the client sees the end of the stream as soon as the child process closes it.
+	command.stdin(stdin).stdout(stdout).spawn()
+}
+
+#[cfg(unix)]
+fn into_stdio(conn: Stream) -> io::Result<(Stdio, Stdio)> {
+	let fd = OwnedFd::from(conn);
+	let dup = fd.try_clone()?;
+	Ok((fd.into(), dup.into()))
+}
+#[cfg(windows)]
+fn into_stdio(conn: Stream) -> io::Result<(Stdio, Stdio)> {
+	// Streams fresh out of `.accept()` aren't split, so this doesn't fail.
+	let handle = OwnedHandle::try_from(conn)
+		.map_err(|_| io::Error::other("cannot take ownership of the stream"))?;
+	let dup = handle.try_clone()?;
+	Ok((handle.into(), dup.into()))
+}
+
+fn accept_loop(listener: &Listener, shared: &Shared, max_connections: usize) -> io::Result<()> {
+	let mut backoff = Duration::ZERO;
+	loop {
+		{
+			let mut state = shared.state.lock().map_err(poison_error)?;
+			while state.open >= max_connections && !state.shutdown {
+				state = shared.changed.wait(state).map_err(poison_error)?;
+			}
+			if state.shutdown {
+				return Ok(());
+			}
+		}
+		match listener.accept() {
+			Ok(conn) => {
+				backoff = Duration::ZERO;
+				// Streams inherit the nonblocking mode of the listener on some platforms. A
+				// connection which can't be switched back is closed right away.
+				if conn.set_nonblocking(false).is_err() {
+					continue;
+				}
+				let mut state = shared.state.lock().map_err(poison_error)?;
+				state.queue.push_back(conn);
+				state.open = state.open.saturating_add(1);
+				shared.changed.notify_all();
+			}
+			Err(e) if e.kind() == io::ErrorKind::WouldBlock => wait(shared, POLL_INTERVAL)?,
+			Err(e) if is_transient(&e) => wait(shared, next_backoff(&mut backoff))?,
+			Err(e) => return Err(e),
+		}
+	}
+}
+
+/// Waits for `timeout` or until the server is shut down, whichever comes first.
+fn wait(shared: &Shared, timeout: Duration) -> io::Result<()> {
+	let mut state = shared.state.lock().map_err(poison_error)?;
+	let start = Instant::now();
+	while !state.shutdown {
+		let Some(left) = timeout.checked_sub(start.elapsed()) else {
+			break;
+		};
+		state = shared
+			.changed
+			.wait_timeout(state, left)
+			.map_err(poison_error)?
+			.0;
+	}
+	Ok(())
+}
+
+fn work(shared: &Shared, handler: &(dyn Fn(Stream) + Sync)) {
+	loop {
+		let conn = {
+			let mut state = shared.state.lock().expect(LOCK_POISON);
+			loop {
+				if let Some(conn) = state.queue.pop_front() {
+					break conn;
+				}
+				if state.shutdown {
+					return;
+				}
+				state = shared.changed.wait(state).expect(LOCK_POISON);
+			}
+		};
+		// The panic hook has already reported the panic by the time it's caught.
+		let _ = catch_unwind(AssertUnwindSafe(|| handler(conn)));
+		let mut state = shared.state.lock().expect(LOCK_POISON);
+		state.open = state.open.saturating_sub(1);
+		shared.changed.notify_all();
+	}
+}
+
+/// A running server, as returned by [`serve()`].
+///
+/// Dropping it doesn't stop the server, which keeps running in the background.
+pub struct Server {
+	shared: Arc<Shared>,
+	acceptor: JoinHandle<io::Result<()>>,
+	workers: Vec<JoinHandle<()>>,
+}
+impl Server {
+	/// Returns a handle which can be used to shut down the server from another thread.
+	pub fn shutdown_handle(&self) -> ShutdownHandle {
+		ShutdownHandle(Arc::clone(&self.shared))
+	}
+	/// Stops accepting new connections, then waits for the connections that have already been
+	/// accepted to be handled.
+	///
+	/// # Errors
+	/// Same as [`.join()`](Self::join).
+	pub fn shutdown(self) -> io::Result<()> {
+		self.shared.shutdown();
+		self.join()
+	}
+	/// Waits for the server to stop, either because it was shut down through a [`ShutdownHandle`]
+	/// or because accepting a connection failed, and for the connections that have already been
+	/// accepted to be handled.
+	///
+	/// # Errors
+	/// Returns the error which made accepting a connection fail, if that is why the server stopped.
+	pub fn join(self) -> io::Result<()> {
+		let rslt = self
+			.acceptor
+			.join()
+			.unwrap_or_else(|_| Err(io::Error::other("acceptor thread panicked")));
+		for worker in self.workers {
+			let _ = worker.join();
+		}
+		rslt
+	}
+}
+impl Debug for Server {
+	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+		f.debug_struct("Server")
+			.field("workers", &self.workers.len())
+			.finish_non_exhaustive()
+	}
+}
+
+/// A handle which shuts down a [`Server`] when [`.shutdown()`](Self::shutdown) is called, obtained
+/// with [`Server::shutdown_handle()`].
+#[derive(Clone)]
+pub struct ShutdownHandle(Arc<Shared>);
+impl ShutdownHandle {
+	/// Makes the server stop accepting new connections. Connections which have already been
+	/// accepted are still handled.
+	///
+	/// This doesn't wait for the server to stop – use [`Server::join()`] for that.
+	pub fn shutdown(&self) {
+		self.0.shutdown();
+	}
+}
+impl Debug for ShutdownHandle {
+	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+		f.debug_struct("ShutdownHandle").finish_non_exhaustive()
+	}
+}
@@ -0,0 +1,378 @@
+//! Single-instance applications, in which the first process to start keeps running and any process
+//! started after it hands its command-line arguments (or any other payload) over to it and exits.
+//!
+//! [`SingleInstance::acquire()`] binds a listener to the given name, making the process the
+//! [primary instance](Primary). If the name is taken, it connects to the primary instance instead,
+//! making the process a [secondary instance](Secondary), which is to [send](Secondary::send) its
+//! payload and exit. The primary instance [receives](Primary::recv) those payloads one by one,
+//! typically on a thread of its own, and is expected to act on them, such as by opening the files
+//! passed on the command line in a new window.
+//!
+//! A primary instance which crashes without unwinding leaves its socket file behind if the name is
+//! a filesystem path. Such a socket file accepts no connections, which is how `.acquire()` tells it
+//! from the socket of a live primary instance, and is deleted to make way for the new one. Files
+//! which aren't sockets are never deleted.
+//!
+//! # Examples
+//! ```no_run
+//! use interprocess::local_socket::{
+//! 	single_instance::SingleInstance,
+//! 	ToNsName,
+//! };
+//! use std::{env, thread};
+//!
+//! let name = "example-app.sock".to_ns_name()?;
+//! match SingleInstance::acquire(name)? {
+//! 	SingleInstance::Primary(primary) => {
+//! 		thread::spawn(move || loop {
+//! 			match primary.recv().and_then(|msg| msg.args()) {
+//! 				Ok(args) => println!("Started again with {args:?}"),
+//! 				Err(e) => eprintln!("Failed to receive from another instance: {e}"),
+//! 			}
+//! 		});
+//! 		// Run the application...
+//! 	}
+//! 	SingleInstance::Secondary(secondary) => {
+//! 		secondary.send_args(env::args_os().skip(1))?;
+//! 		return Ok(());
+//! 	}
+//! }
+//! # std::io::Result::<()>::Ok(())
+//! ```
+
+use super::{framing::Framed, traits::Listener as _, traits::Stream as _, Listener, Name, Stream};
+#[cfg(windows)]
+use std::os::windows::ffi::{OsStrExt, OsStringExt};
+use std::{
+	ffi::{OsStr, OsString},
+	io::{self, prelude::*},
+	time::{Duration, Instant},
+};
+#[cfg(unix)]
+use std::{
+	fs,
+	os::unix::{
+		ffi::{OsStrExt, OsStringExt},
+		fs::{FileTypeExt, MetadataExt},
+		io::{AsFd, AsRawFd},
+	},
+	path::Path,
+};
+
+/// How many times binding and connecting are attempted before giving up, each attempt being
+/// thwarted by the primary instance exiting or another process deleting a stale socket file at the
+/// same time.
+const MAX_ATTEMPTS: u32 = 8;
+/// Sent back by the primary instance once it has received the payload.
+const ACK: u8 = 1;
+/// How often a named pipe is checked for data while receiving a payload, since there is no way to
+/// wait for it to become readable.
+#[cfg(windows)]
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// The role of the process among the instances of an application, as determined by
+/// [`acquire()`](Self::acquire).
+///
+/// See the [module-level documentation](self) for more.
+#[derive(Debug)]
+pub enum SingleInstance {
+	/// No other instance is running, and this one is now the primary one.
+	Primary(Primary),
+	/// Another instance is the primary one, and is connected to.
+	Secondary(Secondary),
+}
+impl SingleInstance {
+	/// Becomes either the primary instance by binding to the name, or a secondary instance by
+	/// connecting to the primary instance bound to it, deleting the socket file of a crashed
+	/// primary instance if there is one in the way.
+	///
+	/// # Errors
+	/// Fails if binding fails for a reason other than the name being taken, or if connecting fails
+	/// for a reason other than there being no primary instance. On Unix, fails with
+	/// [`AddrInUse`](io::ErrorKind::AddrInUse) if the path of the name is taken by a file which
+	/// isn't a socket, which is left alone.
+	///
+	/// # Platform-specific behavior
+	/// ## Unix
+	/// Deleting a stale socket file is only done if it's still the same socket file (by device and
+	/// inode number) that refused the connection, so that a process which starts at the same time
+	/// and becomes the primary instance first doesn't lose its socket file. A narrow window between
+	/// that check and the deletion remains.
+	pub fn acquire(name: Name<'_>) -> io::Result<Self> {
+		for _ in 0..MAX_ATTEMPTS {
+			match Listener::bind(name.borrow()) {
+				Ok(listener) => return Ok(Self::Primary(Primary::new(listener))),
+				Err(e) if is_taken(&e) => {}
+				Err(e) => return Err(e),
+			}
+			#[cfg(unix)]
+			let file = path_metadata(&name);
+			match Stream::connect(name.borrow()) {
+				Ok(conn) => {
+					return Ok(Self::Secondary(Secondary {
+						conn: Framed::new(conn),
+					}))
+				}
+				// No one is listening on the socket file, which means that it was left behind by
+				// a primary instance that crashed.
+				// Connecting to a file which isn't a socket is refused too.
+				#[cfg(unix)]
+				Err(e) if e.kind() == io::ErrorKind::ConnectionRefused && name.is_path() => {
+					let Some(meta) = file else {
+						// The file was created after it was examined.
+						continue;
+					};
+					if !meta.file_type().is_socket() {
+						return Err(io::Error::new(
+							io::ErrorKind::AddrInUse,
+							"the path is taken by a file which is not a socket",
+						));
+					}
+					let still_there = path_metadata(&name)
+						.is_some_and(|now| (now.dev(), now.ino()) == (meta.dev(), meta.ino()));
+					if still_there {
+						let _ = fs::remove_file(name.raw());
+					}
+				}
+				// The primary instance has exited in the meantime.
+				Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+				Err(e) => return Err(e),
+			}
+		}
+		Err(io::Error::new(
+			io::ErrorKind::AddrInUse,
+			"could not become either the primary or a secondary instance",
+		))
+	}
+}
+
+/// Returns whether binding failed because another listener holds the name.
+fn is_taken(e: &io::Error) -> bool {
+	// Creating the first instance of a named pipe which already exists is denied.
+	e.kind() == io::ErrorKind::AddrInUse
+		|| (cfg!(windows) && e.kind() == io::ErrorKind::PermissionDenied)
+}
+
+#[cfg(unix)]
+fn path_metadata(name: &Name<'_>) -> Option<fs::Metadata> {
+	if !name.is_path() {
+		return None;
+	}
+	// The path itself is what gets unlinked, so symlinks aren't followed.
+	fs::symlink_metadata(Path::new(name.raw())).ok()
+}
+
+/// The primary instance of an application, which receives payloads from secondary instances.
+#[derive(Debug)]
+pub struct Primary {
+	listener: Listener,
+	recv_timeout: Duration,
+}
+impl Primary {
+	fn new(listener: Listener) -> Self {
+		Self {
+			listener,
+			recv_timeout: Duration::from_secs(10),
+		}
+	}
+	/// Waits for a secondary instance to connect and receives its payload.
+	///
+	/// Once connected, the secondary instance has until the [receive
+	/// timeout](Self::set_recv_timeout) runs out to send its payload in full, so that a client which
+	/// connects and then stalls cannot keep payloads from other secondary instances from being
+	/// received.
+	///
+	/// # Errors
+	/// Fails if accepting the connection fails, if the secondary instance disconnects before it
+	/// sends its payload in full or if the payload is larger than the [default maximum frame
+	/// size](super::framing::FramingOptions::DEFAULT_MAX_FRAME_SIZE). Fails with
+	/// [`TimedOut`](io::ErrorKind::TimedOut) if the receive timeout runs out first. Failures only
+	/// affect the connection at hand, and payloads from other secondary instances can still be
+	/// received.
+	pub fn recv(&self) -> io::Result<Message> {
+		let conn = self.listener.accept()?;
+		conn.set_nonblocking(true)?;
+		let mut framed = Framed::new(Deadline {
+			conn: &conn,
+			deadline: Instant::now().checked_add(self.recv_timeout),
+		});
+		let payload = framed
+			.recv_frame()?
+			.ok_or(io::ErrorKind::UnexpectedEof)?
+			.to_vec();
+		conn.set_nonblocking(false)?;
+		(&conn).write_all(&[ACK])?;
+		Ok(Message { payload })
+	}
+	/// Sets how long [`.recv()`](Self::recv) gives a secondary instance to send its payload after
+	/// it connects.
+	///
+	/// The default value is 10 seconds.
+	#[inline]
+	pub fn set_recv_timeout(&mut self, timeout: Duration) {
+		self.recv_timeout = timeout;
+	}
+	/// Returns a reference to the listener, which can be used to set it up to be waited on in some
+	/// other way, such as in nonblocking mode.
+	#[inline]
+	pub fn listener(&self) -> &Listener {
+		&self.listener
+	}
+	/// Unwraps the listener. Dropping it makes way for the next instance to become the primary one.
+	#[inline]
+	pub fn into_listener(self) -> Listener {
+		self.listener
+	}
+}
+
+/// Reads from a connection in nonblocking mode, waiting for it to become readable until the
+/// deadline, if there is one, has passed.
+struct Deadline<'a> {
+	conn: &'a Stream,
+	deadline: Option<Instant>,
+}
+impl Read for Deadline<'_> {
+	fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+		loop {
+			let mut conn = self.conn;
+			match conn.read(buf) {
+				Err(e) if e.kind() == io::ErrorKind::WouldBlock => {}
+				rslt => return rslt,
+			}
+			let left = self
+				.deadline
+				.map(|deadline| deadline.saturating_duration_since(Instant::now()));
+			if left.is_some_and(|left| left.is_zero()) {
+				return Err(io::Error::new(
+					io::ErrorKind::TimedOut,
+					"secondary instance took too long to send its payload",
+				));
+			}
+			wait_readable(self.conn, left);
+		}
+	}
+}
+
+/// Waits for the connection to become readable for up to `timeout`, or indefinitely if it's
+/// `None`. Returns early on errors, which the next read picks up.
+#[cfg(unix)]
+fn wait_readable(conn: &Stream, timeout: Option<Duration>) {
+	let mut fd = libc::pollfd {
+		fd: conn.as_fd().as_raw_fd(),
+		events: libc::POLLIN,
+		revents: 0,
+	};
+	// Rounded up, so as not to spin for the last fraction of a millisecond.
+	let timeout = timeout.map_or(-1, |t| {
+		libc::c_int::try_from(t.as_micros().div_ceil(1000)).unwrap_or(libc::c_int::MAX)
+	});
+	unsafe { libc::poll(&mut fd, 1, timeout) };
+}
+#[cfg(windows)]
+fn wait_readable(_: &Stream, timeout: Option<Duration>) {
+	std::thread::sleep(timeout.map_or(POLL_INTERVAL, |t| t.min(POLL_INTERVAL)));
+}
+
+/// A secondary instance of an application, connected to the primary one.
+#[derive(Debug)]
+pub struct Secondary {
+	conn: Framed<Stream>,
+}
+impl Secondary {
+	/// Sends the payload to the primary instance, waiting until it has been received.
+	///
+	/// # Errors
+	/// Fails if sending fails, if the payload is larger than the [default maximum frame
+	/// size](super::framing::FramingOptions::DEFAULT_MAX_FRAME_SIZE) or if the primary instance
+	/// disconnects before acknowledging that it received the payload.
+	pub fn send(mut self, payload: &[u8]) -> io::Result<()> {
+		self.conn.send_frame(payload)?;
+		let mut ack = [0];
+		self.conn.get_mut().read_exact(&mut ack)?;
+		if ack != [ACK] {
+			return Err(io::Error::new(
+				io::ErrorKind::InvalidData,
+				"primary instance sent a malformed acknowledgement",
+			));
+		}
+		Ok(())
+	}
+	/// Sends the given arguments, which the primary instance can retrieve with
+	/// [`Message::args()`], as the payload. Typically, those are the command-line arguments of the
+	/// process, without the program name, as returned by `env::args_os().skip(1)`.
+	///
+	/// # Errors
+	/// Same as [`.send()`](Self::send).
+	pub fn send_args(self, args: impl IntoIterator<Item = impl AsRef<OsStr>>) -> io::Result<()> {
+		let mut payload = Framed::new(Vec::new());
+		for arg in args {
+			payload.send_frame(&os_str_to_bytes(arg.as_ref()))?;
+		}
+		self.send(&payload.into_inner())
+	}
+	/// Returns a reference to the connection to the primary instance.
+	#[inline]
+	pub fn conn(&self) -> &Stream {
+		self.conn.get_ref()
+	}
+}
+
+/// A payload sent by a secondary instance, as received by [`Primary::recv()`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Message {
+	payload: Vec<u8>,
+}
+impl Message {
+	/// Returns the payload.
+	#[inline]
+	pub fn payload(&self) -> &[u8] {
+		&self.payload
+	}
+	/// Unwraps the payload.
+	#[inline]
+	pub fn into_payload(self) -> Vec<u8> {
+		self.payload
+	}
+	/// Decodes the arguments sent with [`Secondary::send_args()`].
+	///
+	/// # Errors
+	/// Fails with [`InvalidData`](io::ErrorKind::InvalidData) if the payload wasn't sent by
+	/// `.send_args()`.
+	pub fn args(&self) -> io::Result<Vec<OsString>> {
+		let mut payload = Framed::new(self.payload.as_slice());
+		let mut args = Vec::new();
+		while let Some(arg) = payload.recv_frame()? {
+			args.push(bytes_to_os_string(arg)?);
+		}
+		Ok(args)
+	}
+}
+
+#[cfg(unix)]
+fn os_str_to_bytes(s: &OsStr) -> Vec<u8> {
+	s.as_bytes().to_vec()
+}
+#[cfg(windows)]
+fn os_str_to_bytes(s: &OsStr) -> Vec<u8> {
+	s.encode_wide().flat_map(u16::to_le_bytes).collect()
+}
+
+#[cfg(unix)]
+fn bytes_to_os_string(bytes: &[u8]) -> io::Result<OsString> {
+	Ok(OsString::from_vec(bytes.to_vec()))
+}
+#[cfg(windows)]
+fn bytes_to_os_string(bytes: &[u8]) -> io::Result<OsString> {
+	let chunks = bytes.chunks_exact(2);
+	if !chunks.remainder().is_empty() {
+		return Err(io::Error::new(
+			io::ErrorKind::InvalidData,
+			"argument has an odd number of bytes",
+		));
+	}
+	let wide = chunks
+		.filter_map(|c| <[u8; 2]>::try_from(c).ok())
+		.map(u16::from_le_bytes)
+		.collect::<Vec<_>>();
+	Ok(OsString::from_wide(&wide))
+}
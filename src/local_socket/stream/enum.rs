@@ -115,6 +115,10 @@ impl r#trait::Stream for Stream {
 		dispatch::connect(name)
 	}
 	#[inline]
+	fn pair() -> io::Result<(Self, Self)> {
+		dispatch::pair()
+	}
+	#[inline]
 	fn set_nonblocking(&self, nonblocking: bool) -> io::Result<()> {
 		dispatch!(Self: x in self => x.set_nonblocking(nonblocking))
 	}
@@ -22,6 +22,13 @@ pub trait Stream: Sized + Read + RefRead + Write + RefWrite + Sealed {
 	/// Connects to a remote local socket server.
 	fn connect(name: Name<'_>) -> io::Result<Self>;
 
+	/// Creates a pair of streams connected to each other without binding a listener to a name.
+	///
+	/// Much like with [unnamed pipes](crate::unnamed_pipe), the resulting streams can only be used
+	/// by other processes by having one of them inherited by a child process or sent to another
+	/// process in some other way, but unlike unnamed pipes, communication is bidirectional.
+	fn pair() -> io::Result<(Self, Self)>;
+
 	/// Enables or disables the nonblocking mode for the stream. By default, it is disabled.
 	///
 	/// In nonblocking mode, receiving and sending immediately returns with the
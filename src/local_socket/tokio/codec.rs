@@ -0,0 +1,215 @@
+//! Codecs for using Tokio local socket streams with [`tokio_util::codec`].
+//!
+//! [`Stream`](super::Stream), [`RecvHalf`](super::RecvHalf) and [`SendHalf`](super::SendHalf)
+//! implement Tokio's `AsyncRead` and/or `AsyncWrite`, so they can be wrapped in
+//! [`Framed`](tokio_util::codec::Framed), [`FramedRead`](tokio_util::codec::FramedRead) and
+//! [`FramedWrite`](tokio_util::codec::FramedWrite) to get a `Stream` of incoming messages and a
+//! `Sink` of outgoing ones. This module provides two codecs with size limits for that purpose:
+//! -	[`FrameCodec`], for length-prefixed frames in the same format as the [`framing`] module;
+//! -	[`LineCodec`], for newline-delimited messages.
+//!
+//! # Examples
+//! ```no_run
+//! # #[tokio::main]
+//! # async fn main() -> std::io::Result<()> {
+//! use futures::{SinkExt, StreamExt};
+//! use interprocess::local_socket::tokio::{codec::LineCodec, Stream};
+//! use tokio_util::codec::Framed;
+//!
+//! let (a, b) = Stream::pair().await?;
+//! let (mut a, mut b) = (Framed::new(a, LineCodec::new()), Framed::new(b, LineCodec::new()));
+//! a.send(&b"Hello world!"[..]).await?;
+//! assert_eq!(b.next().await.transpose()?.as_deref(), Some(&b"Hello world!"[..]));
+//! # Ok(()) }
+//! ```
+//!
+//! [`framing`]: crate::local_socket::framing
+
+use crate::local_socket::framing::FramingOptions;
+use bytes::{Buf, Bytes, BytesMut};
+use std::io;
+use tokio_util::codec::{Decoder, Encoder};
+
+/// Codec for length-prefixed frames, compatible with the [`Framed`](super::framing::Framed)
+/// wrappers from the [`framing`](crate::local_socket::framing) module when configured with the
+/// same [options](FramingOptions).
+///
+/// Frames larger than the [maximum frame size](FramingOptions::max_frame_size) are rejected
+/// before buffer space is reserved for them.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct FrameCodec {
+	opts: FramingOptions,
+}
+impl FrameCodec {
+	/// Creates a codec with the default [options](FramingOptions).
+	#[inline]
+	pub const fn new() -> Self {
+		Self::with_options(FramingOptions::new())
+	}
+	/// Creates a codec with the given options.
+	#[inline]
+	pub const fn with_options(options: FramingOptions) -> Self {
+		Self { opts: options }
+	}
+	/// Returns the options the codec was created with.
+	#[inline]
+	pub fn options(&self) -> &FramingOptions {
+		&self.opts
+	}
+}
+impl From<FramingOptions> for FrameCodec {
+	#[inline]
+	fn from(options: FramingOptions) -> Self {
+		Self::with_options(options)
+	}
+}
+impl Decoder for FrameCodec {
+	type Item = BytesMut;
+	type Error = io::Error;
+	fn decode(&mut self, src: &mut BytesMut) -> io::Result<Option<BytesMut>> {
+		let Some((prefix_len, len)) = self.opts.decode_prefix(src)? else {
+			return Ok(None);
+		};
+		let total = prefix_len.saturating_add(len);
+		if src.len() < total {
+			src.reserve(total.saturating_sub(src.len()));
+			return Ok(None);
+		}
+		src.advance(prefix_len);
+		Ok(Some(src.split_to(len)))
+	}
+}
+impl Encoder<&[u8]> for FrameCodec {
+	type Error = io::Error;
+	fn encode(&mut self, frame: &[u8], dst: &mut BytesMut) -> io::Result<()> {
+		self.opts.encode_prefix(frame.len(), dst)?;
+		dst.extend_from_slice(frame);
+		Ok(())
+	}
+}
+impl Encoder<Bytes> for FrameCodec {
+	type Error = io::Error;
+	#[inline]
+	fn encode(&mut self, frame: Bytes, dst: &mut BytesMut) -> io::Result<()> {
+		self.encode(&frame[..], dst)
+	}
+}
+
+/// Codec for newline-delimited messages.
+///
+/// Decoded messages don't include the terminating `\n`. Unlike
+/// [`LinesCodec`](tokio_util::codec::LinesCodec), messages are not required to be valid UTF-8,
+/// and errors are reported as [`io::Error`]s:
+/// -	receiving a message longer than the maximum length fails with
+///   	[`InvalidData`](io::ErrorKind::InvalidData), after which the stream should be dropped;
+/// -	sending a message longer than the maximum length or containing a `\n` fails with
+///   	[`InvalidInput`](io::ErrorKind::InvalidInput).
+///
+/// If the stream ends with an unterminated message, it is decoded as the last message.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct LineCodec {
+	max_length: usize,
+	/// How much of the buffer has already been searched for a newline.
+	searched: usize,
+}
+impl LineCodec {
+	/// The default maximum length of a message, which is 64 KiB.
+	pub const DEFAULT_MAX_LENGTH: usize = 64 * 1024;
+
+	/// Creates a codec with the [default maximum message length](Self::DEFAULT_MAX_LENGTH).
+	#[inline]
+	pub const fn new() -> Self {
+		Self::with_max_length(Self::DEFAULT_MAX_LENGTH)
+	}
+	/// Creates a codec with the given maximum message length, not counting the newline.
+	#[inline]
+	pub const fn with_max_length(max_length: usize) -> Self {
+		Self {
+			max_length,
+			searched: 0,
+		}
+	}
+	/// Returns the maximum message length.
+	#[inline]
+	pub fn max_length(&self) -> usize {
+		self.max_length
+	}
+
+	fn too_long() -> io::Error {
+		io::Error::new(
+			io::ErrorKind::InvalidData,
+			"received message exceeds maximum length",
+		)
+	}
+}
+impl Default for LineCodec {
+	#[inline]
+	fn default() -> Self {
+		Self::new()
+	}
+}
+impl Decoder for LineCodec {
+	type Item = BytesMut;
+	type Error = io::Error;
+	fn decode(&mut self, src: &mut BytesMut) -> io::Result<Option<BytesMut>> {
+		let unsearched = src.get(self.searched..).unwrap_or_default();
+		let Some(pos) = unsearched.iter().position(|&b| b == b'\n') else {
+			self.searched = src.len();
+			return if src.len() > self.max_length {
+				Err(Self::too_long())
+			} else {
+				Ok(None)
+			};
+		};
+		let len = self.searched.saturating_add(pos);
+		self.searched = 0;
+		if len > self.max_length {
+			return Err(Self::too_long());
+		}
+		let line = src.split_to(len);
+		src.advance(1);
+		Ok(Some(line))
+	}
+	fn decode_eof(&mut self, src: &mut BytesMut) -> io::Result<Option<BytesMut>> {
+		if let Some(line) = self.decode(src)? {
+			return Ok(Some(line));
+		}
+		self.searched = 0;
+		Ok((!src.is_empty()).then(|| src.split()))
+	}
+}
+impl Encoder<&[u8]> for LineCodec {
+	type Error = io::Error;
+	fn encode(&mut self, line: &[u8], dst: &mut BytesMut) -> io::Result<()> {
+		if line.len() > self.max_length {
+			return Err(io::Error::new(
+				io::ErrorKind::InvalidInput,
+				"message exceeds maximum length",
+			));
+		}
+		if line.contains(&b'\n') {
+			return Err(io::Error::new(
+				io::ErrorKind::InvalidInput,
+				"message contains a newline",
+			));
+		}
+		dst.reserve(line.len().saturating_add(1));
+		dst.extend_from_slice(line);
+		dst.extend_from_slice(b"\n");
+		Ok(())
+	}
+}
+impl Encoder<Bytes> for LineCodec {
+	type Error = io::Error;
+	#[inline]
+	fn encode(&mut self, line: Bytes, dst: &mut BytesMut) -> io::Result<()> {
+		self.encode(&line[..], dst)
+	}
+}
+impl Encoder<&str> for LineCodec {
+	type Error = io::Error;
+	#[inline]
+	fn encode(&mut self, line: &str, dst: &mut BytesMut) -> io::Result<()> {
+		self.encode(line.as_bytes(), dst)
+	}
+}
@@ -0,0 +1,161 @@
+//! Length-prefixed message framing on top of Tokio local socket streams.
+//!
+//! This is the Tokio counterpart of the [sync framing module](crate::local_socket::framing), with
+//! which it shares the [options](FramingOptions) and the wire format.
+//!
+//! # Examples
+//! ```no_run
+//! # #[tokio::main]
+//! # async fn main() -> std::io::Result<()> {
+//! use interprocess::local_socket::tokio::{framing::Framed, Stream};
+//!
+//! let (a, b) = Stream::pair().await?;
+//! let (mut a, mut b) = (Framed::new(a), Framed::new(b));
+//! a.send_frame(b"Hello").await?;
+//! a.send_frame(b"world!").await?;
+//! assert_eq!(b.recv_frame().await?, Some(&b"Hello"[..]));
+//! assert_eq!(b.recv_frame().await?, Some(&b"world!"[..]));
+//! # Ok(()) }
+//! ```
+
+use super::{RecvHalf, SendHalf, Stream};
+pub use crate::local_socket::framing::{Endianness, FramingOptions, LengthPrefix};
+use crate::{
+	error::{ReuniteError, ReuniteResult},
+	local_socket::framing::RecvBuf,
+};
+use std::{
+	fmt::{self, Debug, Formatter},
+	io,
+};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+/// Wrapper around an asynchronous byte stream which sends and receives length-prefixed frames.
+///
+/// See the [sync version](crate::local_socket::framing::Framed) for more.
+///
+/// The futures returned by [`.recv_frame()`](Self::recv_frame) and
+/// [`.send_frame()`](Self::send_frame) are cancel-safe only in the sense that dropping them leaves
+/// the wrapper usable for other operations in the other direction; a cancelled receive or send
+/// leaves the framing in an unknown state.
+pub struct Framed<T> {
+	inner: T,
+	opts: FramingOptions,
+	rbuf: RecvBuf,
+	wbuf: Vec<u8>,
+}
+impl<T> Framed<T> {
+	/// Wraps the given stream with the default [options](FramingOptions).
+	#[inline]
+	pub fn new(inner: T) -> Self {
+		Self::with_options(inner, FramingOptions::new())
+	}
+	/// Wraps the given stream with the given options.
+	#[inline]
+	pub fn with_options(inner: T, options: FramingOptions) -> Self {
+		Self {
+			inner,
+			opts: options,
+			rbuf: RecvBuf::default(),
+			wbuf: Vec::new(),
+		}
+	}
+	/// Returns the options the wrapper was created with.
+	#[inline]
+	pub fn options(&self) -> &FramingOptions {
+		&self.opts
+	}
+	/// Borrows the wrapped stream.
+	#[inline]
+	pub fn get_ref(&self) -> &T {
+		&self.inner
+	}
+	/// Mutably borrows the wrapped stream. Reading from it directly will desynchronize the framing.
+	#[inline]
+	pub fn get_mut(&mut self) -> &mut T {
+		&mut self.inner
+	}
+	/// Unwraps the stream, discarding any buffered received data.
+	#[inline]
+	pub fn into_inner(self) -> T {
+		self.inner
+	}
+}
+impl<T: AsyncWrite + Unpin> Framed<T> {
+	/// Sends one frame containing `frame`.
+	pub async fn send_frame(&mut self, frame: &[u8]) -> io::Result<()> {
+		self.wbuf.clear();
+		self.opts.encode_prefix(frame.len(), &mut self.wbuf)?;
+		self.wbuf.extend_from_slice(frame);
+		self.inner.write_all(&self.wbuf).await?;
+		self.inner.flush().await
+	}
+}
+impl<T: AsyncRead + Unpin> Framed<T> {
+	/// Receives one frame, returning `None` if the stream reaches end-of-file at a frame boundary.
+	///
+	/// The returned slice borrows the receive buffer of the wrapper, which is reused for subsequent
+	/// frames.
+	pub async fn recv_frame(&mut self) -> io::Result<Option<&[u8]>> {
+		self.rbuf.discard_consumed();
+		loop {
+			if let Some(frame) = self.rbuf.next_frame(&self.opts)? {
+				return Ok(Some(self.rbuf.take_frame(frame)));
+			}
+			let n = self.inner.read(self.rbuf.spare()).await?;
+			if !self.rbuf.advance(n)? {
+				return Ok(None);
+			}
+		}
+	}
+}
+impl Framed<Stream> {
+	/// Splits the stream into a receive half and a send half, keeping the framing state of both
+	/// directions.
+	pub fn split(self) -> (Framed<RecvHalf>, Framed<SendHalf>) {
+		let (rh, sh) = self.inner.split();
+		let rh = Framed {
+			inner: rh,
+			opts: self.opts,
+			rbuf: self.rbuf,
+			wbuf: Vec::new(),
+		};
+		let sh = Framed {
+			inner: sh,
+			opts: self.opts,
+			rbuf: RecvBuf::default(),
+			wbuf: self.wbuf,
+		};
+		(rh, sh)
+	}
+	/// Attempts to reunite a receive half with a send half to yield the original stream back,
+	/// returning both halves as an error if they belong to different streams.
+	///
+	/// The options of the receive half are used for the reunited stream.
+	#[allow(clippy::result_large_err)]
+	pub fn reunite(
+		rh: Framed<RecvHalf>,
+		sh: Framed<SendHalf>,
+	) -> ReuniteResult<Self, Framed<RecvHalf>, Framed<SendHalf>> {
+		match Stream::reunite(rh.inner, sh.inner) {
+			Ok(inner) => Ok(Framed {
+				inner,
+				opts: rh.opts,
+				rbuf: rh.rbuf,
+				wbuf: sh.wbuf,
+			}),
+			Err(ReuniteError { rh: rhi, sh: shi }) => Err(ReuniteError {
+				rh: Framed { inner: rhi, ..rh },
+				sh: Framed { inner: shi, ..sh },
+			}),
+		}
+	}
+}
+impl<T: Debug> Debug for Framed<T> {
+	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+		f.debug_struct("Framed")
+			.field("inner", &self.inner)
+			.field("options", &self.opts)
+			.finish_non_exhaustive()
+	}
+}
@@ -0,0 +1,558 @@
+//! JSON-RPC 2.0 client and server on top of Tokio local socket streams.
+//!
+//! Messages are framed with `Content-Length` headers as in the Language Server Protocol; see the
+//! [sync module](crate::local_socket::jsonrpc) for the message types and the framing, the latter of
+//! which is available here as the asynchronous [`LspFramed`].
+//!
+//! A [`Client`] sends requests and notifications, correlating responses with requests by their ID,
+//! and a connection is served by passing it to [`serve()`] along with a request handler. Both
+//! support batches and cancellation: dropping the future of a request sends a
+//! [`$/cancelRequest`](CANCEL_METHOD) notification for it, upon which the server aborts the task
+//! running the handler and responds with a [`REQUEST_CANCELLED`](ErrorObject::REQUEST_CANCELLED)
+//! error.
+//!
+//! # Examples
+//! ```no_run
+//! # #[tokio::main]
+//! # async fn main() -> std::io::Result<()> {
+//! use interprocess::local_socket::tokio::{
+//! 	jsonrpc::{serve, Client, ErrorObject, Request},
+//! 	Stream,
+//! };
+//! use serde_json::json;
+//!
+//! let (a, b) = Stream::pair().await?;
+//! tokio::spawn(serve(b, |request: Request| async move {
+//! 	match request.method.as_str() {
+//! 		"ping" => Ok(json!("pong")),
+//! 		method => Err(ErrorObject::method_not_found(method)),
+//! 	}
+//! }));
+//!
+//! let client = Client::new(a);
+//! assert_eq!(client.request("ping", None).await?, Ok(json!("pong")));
+//! let results = client.batch().request("ping", None).request("pong", None).send().await?;
+//! assert_eq!(results[0], Ok(json!("pong")));
+//! assert_eq!(results[1].as_ref().map_err(|e| e.code), Err(ErrorObject::METHOD_NOT_FOUND));
+//! # Ok(()) }
+//! ```
+
+use super::{RecvHalf, SendHalf, Stream};
+pub use crate::local_socket::jsonrpc::{
+	ErrorObject, Id, Message, Request, Response, CANCEL_METHOD, DEFAULT_MAX_MESSAGE_SIZE,
+};
+use crate::{
+	local_socket::{
+		framing::RecvBuf,
+		jsonrpc::{decode_header, encode_header},
+	},
+	poison_error, LOCK_POISON,
+};
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::{
+	collections::HashMap,
+	fmt::{self, Debug, Formatter},
+	future::Future,
+	io,
+	sync::{
+		atomic::{AtomicI64, Ordering::Relaxed},
+		Arc, Mutex,
+	},
+};
+use tokio::{
+	io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
+	sync::{mpsc, oneshot, Mutex as AsyncMutex},
+	task::{AbortHandle, JoinHandle},
+};
+
+/// Wrapper around an asynchronous byte stream which sends and receives messages framed with
+/// `Content-Length` headers.
+///
+/// See the [sync version](crate::local_socket::jsonrpc::LspFramed) for more.
+pub struct LspFramed<T> {
+	inner: T,
+	max_message_size: usize,
+	rbuf: RecvBuf,
+	wbuf: Vec<u8>,
+}
+impl<T> LspFramed<T> {
+	/// Wraps the given stream with the [default maximum message size](DEFAULT_MAX_MESSAGE_SIZE).
+	#[inline]
+	pub fn new(inner: T) -> Self {
+		Self::with_max_message_size(inner, DEFAULT_MAX_MESSAGE_SIZE)
+	}
+	/// Wraps the given stream with the given maximum message size.
+	#[inline]
+	pub fn with_max_message_size(inner: T, max_message_size: usize) -> Self {
+		Self {
+			inner,
+			max_message_size,
+			rbuf: RecvBuf::default(),
+			wbuf: Vec::new(),
+		}
+	}
+	/// Returns the maximum message size, not counting the header section.
+	#[inline]
+	pub fn max_message_size(&self) -> usize {
+		self.max_message_size
+	}
+	/// Borrows the wrapped stream.
+	#[inline]
+	pub fn get_ref(&self) -> &T {
+		&self.inner
+	}
+	/// Mutably borrows the wrapped stream. Reading from it directly will desynchronize the framing.
+	#[inline]
+	pub fn get_mut(&mut self) -> &mut T {
+		&mut self.inner
+	}
+	/// Unwraps the stream, discarding any buffered received data.
+	#[inline]
+	pub fn into_inner(self) -> T {
+		self.inner
+	}
+}
+impl<T: AsyncWrite + Unpin> LspFramed<T> {
+	/// Sends one message with `content` as its content.
+	pub async fn send_message(&mut self, content: &[u8]) -> io::Result<()> {
+		self.wbuf.clear();
+		encode_header(content.len(), self.max_message_size, &mut self.wbuf)?;
+		self.wbuf.extend_from_slice(content);
+		self.inner.write_all(&self.wbuf).await?;
+		self.inner.flush().await
+	}
+}
+impl<T: AsyncRead + Unpin> LspFramed<T> {
+	/// Receives one message and returns its content, or `None` if the stream reaches end-of-file
+	/// at a message boundary.
+	///
+	/// The returned slice borrows the receive buffer of the wrapper, which is reused for subsequent
+	/// messages.
+	pub async fn recv_message(&mut self) -> io::Result<Option<&[u8]>> {
+		self.rbuf.discard_consumed();
+		let max = self.max_message_size;
+		loop {
+			if let Some(msg) = self.rbuf.next_frame_with(|b| decode_header(b, max))? {
+				return Ok(Some(self.rbuf.take_frame(msg)));
+			}
+			let n = self.inner.read(self.rbuf.spare()).await?;
+			if !self.rbuf.advance(n)? {
+				return Ok(None);
+			}
+		}
+	}
+}
+impl<T: Debug> Debug for LspFramed<T> {
+	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+		f.debug_struct("LspFramed")
+			.field("inner", &self.inner)
+			.field("max_message_size", &self.max_message_size)
+			.finish_non_exhaustive()
+	}
+}
+
+type Outbox = mpsc::UnboundedSender<Vec<u8>>;
+
+/// Serializes a message and queues it for sending.
+fn enqueue(outbox: &Outbox, msg: &impl Serialize) -> io::Result<()> {
+	let msg = serde_json::to_vec(msg)?;
+	outbox.send(msg).map_err(|_| connection_closed())
+}
+fn connection_closed() -> io::Error {
+	io::Error::new(
+		io::ErrorKind::ConnectionAborted,
+		"JSON-RPC connection closed",
+	)
+}
+
+/// Sends queued messages until the queue is closed or sending fails.
+async fn write_loop(mut framed: LspFramed<SendHalf>, mut queue: mpsc::UnboundedReceiver<Vec<u8>>) {
+	while let Some(msg) = queue.recv().await {
+		if framed.send_message(&msg).await.is_err() {
+			break;
+		}
+	}
+}
+
+type CallResult = Result<Value, ErrorObject>;
+
+#[derive(Default)]
+struct Pending {
+	calls: HashMap<Id, oneshot::Sender<CallResult>>,
+	/// Set once the connection is closed, after which no more calls are accepted.
+	closed: bool,
+}
+
+struct ClientShared {
+	outbox: Outbox,
+	pending: Arc<Mutex<Pending>>,
+	next_id: AtomicI64,
+	incoming: AsyncMutex<mpsc::UnboundedReceiver<Request>>,
+	reader: AbortHandle,
+}
+impl Drop for ClientShared {
+	fn drop(&mut self) {
+		self.reader.abort();
+	}
+}
+
+/// JSON-RPC client over a Tokio local socket stream.
+///
+/// Requests are assigned consecutive integer IDs, and responses are matched with them by a
+/// background task, so that any number of requests can be in flight at once. The client can be
+/// cloned cheaply to make requests from multiple tasks; the connection is closed once all clones
+/// are dropped.
+///
+/// Requests and notifications sent by the server are queued without a limit until they are taken
+/// with [`.next_incoming()`](Self::next_incoming).
+///
+/// See the [module-level documentation](self) for an example.
+#[derive(Clone)]
+pub struct Client(Arc<ClientShared>);
+impl Client {
+	/// Creates a client which communicates over the given stream.
+	///
+	/// # Panics
+	/// Must be called from within a Tokio runtime, since tasks for sending and receiving messages
+	/// are spawned on it.
+	pub fn new(stream: Stream) -> Self {
+		let (rh, sh) = stream.split();
+		let (outbox, queue) = mpsc::unbounded_channel();
+		let (incoming_tx, incoming) = mpsc::unbounded_channel();
+		let pending = Arc::new(Mutex::new(Pending::default()));
+		tokio::spawn(write_loop(LspFramed::new(sh), queue));
+		let reader = tokio::spawn(client_read_loop(
+			LspFramed::new(rh),
+			Arc::clone(&pending),
+			incoming_tx,
+		));
+		Self(Arc::new(ClientShared {
+			outbox,
+			pending,
+			next_id: AtomicI64::new(1),
+			incoming: AsyncMutex::new(incoming),
+			reader: reader.abort_handle(),
+		}))
+	}
+
+	fn next_id(&self) -> Id {
+		Id::Number(self.0.next_id.fetch_add(1, Relaxed))
+	}
+	fn register(&self, id: Id) -> io::Result<oneshot::Receiver<CallResult>> {
+		let mut pending = self.0.pending.lock().map_err(poison_error)?;
+		if pending.closed {
+			return Err(connection_closed());
+		}
+		let (tx, rx) = oneshot::channel();
+		pending.calls.insert(id, tx);
+		Ok(rx)
+	}
+
+	/// Sends a request and waits for its response.
+	///
+	/// The outer `Result` reports failure to communicate with the server, while the inner one is
+	/// the result of the request as reported by the server. Dropping the returned future before it
+	/// completes sends a [`$/cancelRequest`](CANCEL_METHOD) notification for the request.
+	pub async fn request(
+		&self,
+		method: impl Into<String>,
+		params: Option<Value>,
+	) -> io::Result<CallResult> {
+		let id = self.next_id();
+		let response = self.register(id.clone())?;
+		let _guard = CancelOnDrop {
+			client: self,
+			ids: vec![id.clone()],
+		};
+		enqueue(&self.0.outbox, &Request::new(method, params, id))?;
+		response.await.map_err(|_| connection_closed())
+	}
+	/// Sends a notification.
+	///
+	/// This only queues the notification for sending, and thus doesn't wait.
+	pub fn notify(&self, method: impl Into<String>, params: Option<Value>) -> io::Result<()> {
+		enqueue(&self.0.outbox, &Request::notification(method, params))
+	}
+	/// Starts building a batch of requests and notifications to be sent together.
+	#[inline]
+	pub fn batch(&self) -> Batch<'_> {
+		Batch {
+			client: self,
+			requests: Vec::new(),
+		}
+	}
+	/// Waits for the next request or notification sent by the server, returning `None` once the
+	/// connection is closed.
+	///
+	/// Requests can be responded to with [`.respond()`](Self::respond).
+	pub async fn next_incoming(&self) -> Option<Request> {
+		self.0.incoming.lock().await.recv().await
+	}
+	/// Sends a response to a request received from the server.
+	pub fn respond(&self, response: &Response) -> io::Result<()> {
+		enqueue(&self.0.outbox, response)
+	}
+}
+impl Debug for Client {
+	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+		f.debug_struct("Client").finish_non_exhaustive()
+	}
+}
+
+/// Dispatches received responses to pending calls and everything else to the incoming queue.
+async fn client_read_loop(
+	mut framed: LspFramed<RecvHalf>,
+	pending: Arc<Mutex<Pending>>,
+	incoming: mpsc::UnboundedSender<Request>,
+) {
+	while let Ok(Some(msg)) = framed.recv_message().await {
+		// There's nobody to report malformed messages to.
+		let Ok(value) = serde_json::from_slice::<Value>(msg) else {
+			continue;
+		};
+		let msgs = match value {
+			Value::Array(msgs) => msgs,
+			msg => vec![msg],
+		};
+		for msg in msgs {
+			match Message::try_from(msg) {
+				Ok(Message::Response(Response {
+					id: Some(id),
+					result,
+				})) => {
+					let call = pending.lock().expect(LOCK_POISON).calls.remove(&id);
+					if let Some(call) = call {
+						let _ = call.send(result);
+					}
+				}
+				Ok(Message::Request(request)) => {
+					let _ = incoming.send(request);
+				}
+				_ => {}
+			}
+		}
+	}
+	let mut pending = pending.lock().expect(LOCK_POISON);
+	pending.closed = true;
+	pending.calls.clear();
+}
+
+/// Sends cancellation notifications for the calls among `ids` that are still pending.
+struct CancelOnDrop<'a> {
+	client: &'a Client,
+	ids: Vec<Id>,
+}
+impl Drop for CancelOnDrop<'_> {
+	fn drop(&mut self) {
+		let mut pending = self.client.0.pending.lock().expect(LOCK_POISON);
+		for id in self.ids.drain(..) {
+			if pending.calls.remove(&id).is_some() {
+				let cancel = Request::notification(CANCEL_METHOD, Some(json!({ "id": id })));
+				let _ = enqueue(&self.client.0.outbox, &cancel);
+			}
+		}
+	}
+}
+
+/// Batch of requests and notifications, created by [`Client::batch()`].
+#[derive(Debug)]
+pub struct Batch<'a> {
+	client: &'a Client,
+	requests: Vec<Request>,
+}
+impl Batch<'_> {
+	/// Adds a request to the batch.
+	#[must_use = "this is not an in-place operation"]
+	pub fn request(mut self, method: impl Into<String>, params: Option<Value>) -> Self {
+		let id = self.client.next_id();
+		self.requests.push(Request::new(method, params, id));
+		self
+	}
+	/// Adds a notification to the batch.
+	#[must_use = "this is not an in-place operation"]
+	pub fn notify(mut self, method: impl Into<String>, params: Option<Value>) -> Self {
+		self.requests.push(Request::notification(method, params));
+		self
+	}
+	/// Sends the batch and waits for the responses to all of its requests, which are returned in
+	/// the order in which the requests were added. Nothing is sent if the batch is empty.
+	///
+	/// As with [`Client::request()`], dropping the returned future before it completes sends
+	/// cancellation notifications for the requests that haven't been responded to.
+	pub async fn send(self) -> io::Result<Vec<CallResult>> {
+		if self.requests.is_empty() {
+			return Ok(Vec::new());
+		}
+		let ids: Vec<Id> = self.requests.iter().filter_map(|r| r.id.clone()).collect();
+		let mut guard = CancelOnDrop {
+			client: self.client,
+			ids: Vec::with_capacity(ids.len()),
+		};
+		let mut responses = Vec::with_capacity(ids.len());
+		for id in ids {
+			responses.push(self.client.register(id.clone())?);
+			guard.ids.push(id);
+		}
+		enqueue(&self.client.0.outbox, &self.requests)?;
+		let mut results = Vec::with_capacity(responses.len());
+		for response in responses {
+			results.push(response.await.map_err(|_| connection_closed())?);
+		}
+		Ok(results)
+	}
+}
+
+/// Serves JSON-RPC requests received over the given stream until the client closes or resets the
+/// connection.
+///
+/// Each request is handled by calling `handler` and running the returned future in its own task,
+/// with no limit on the number of requests being handled at once. Responses are sent in the order
+/// in which requests complete, and those to a batch are sent together once all of its requests
+/// complete. Responses to notifications are discarded. Errors in the handling of requests are
+/// reported to the client rather than returned:
+/// -	malformed messages receive [`PARSE_ERROR`](ErrorObject::PARSE_ERROR) and
+///   	[`INVALID_REQUEST`](ErrorObject::INVALID_REQUEST) error responses;
+/// -	requests cancelled with a [`$/cancelRequest`](CANCEL_METHOD) notification receive a
+///   	[`REQUEST_CANCELLED`](ErrorObject::REQUEST_CANCELLED) error response;
+/// -	requests whose handler panics receive an [`INTERNAL_ERROR`](ErrorObject::INTERNAL_ERROR)
+///   	error response.
+///
+/// Requests that are still being handled once the connection is closed are cancelled.
+///
+/// # Errors
+/// Fails if receiving from the stream fails or if the framing of the received data is invalid.
+pub async fn serve<H, Fut>(stream: Stream, handler: H) -> io::Result<()>
+where
+	H: Fn(Request) -> Fut + Send + Sync + 'static,
+	Fut: Future<Output = CallResult> + Send + 'static,
+{
+	let (rh, sh) = stream.split();
+	let (outbox, queue) = mpsc::unbounded_channel();
+	tokio::spawn(write_loop(LspFramed::new(sh), queue));
+	let server = Arc::new(Server {
+		handler,
+		in_flight: Mutex::default(),
+	});
+	let mut framed = LspFramed::new(rh);
+	let rslt = loop {
+		let msg = match framed.recv_message().await {
+			Ok(Some(msg)) => msg,
+			Ok(None) => break Ok(()),
+			// Clients that disconnect with responses left unread reset the connection.
+			Err(e) if e.kind() == io::ErrorKind::ConnectionReset => break Ok(()),
+			Err(e) => break Err(e),
+		};
+		let value = match serde_json::from_slice::<Value>(msg) {
+			Ok(value) => value,
+			Err(e) => {
+				let error = ErrorObject::new(ErrorObject::PARSE_ERROR, e.to_string());
+				let _ = enqueue(&outbox, &Response::error(None, error));
+				continue;
+			}
+		};
+		match value {
+			Value::Array(msgs) if msgs.is_empty() => {
+				let error = ErrorObject::new(ErrorObject::INVALID_REQUEST, "empty batch");
+				let _ = enqueue(&outbox, &Response::error(None, error));
+			}
+			Value::Array(msgs) => {
+				let dispatched: Vec<_> = msgs.into_iter().map(|m| server.dispatch(m)).collect();
+				let (server, outbox) = (Arc::clone(&server), outbox.clone());
+				tokio::spawn(async move {
+					let mut responses = Vec::with_capacity(dispatched.len());
+					for dispatched in dispatched {
+						responses.extend(server.finish(dispatched).await);
+					}
+					if !responses.is_empty() {
+						let _ = enqueue(&outbox, &responses);
+					}
+				});
+			}
+			msg => {
+				let dispatched = server.dispatch(msg);
+				let (server, outbox) = (Arc::clone(&server), outbox.clone());
+				tokio::spawn(async move {
+					if let Some(response) = server.finish(dispatched).await {
+						let _ = enqueue(&outbox, &response);
+					}
+				});
+			}
+		}
+	};
+	for task in server.in_flight.lock().expect(LOCK_POISON).values() {
+		task.abort();
+	}
+	rslt
+}
+
+struct Server<H> {
+	handler: H,
+	/// Tasks handling requests with IDs, which can be cancelled.
+	in_flight: Mutex<HashMap<Id, AbortHandle>>,
+}
+
+enum Dispatched {
+	Done(Option<Response>),
+	Running(Option<Id>, JoinHandle<CallResult>),
+}
+
+impl<H, Fut> Server<H>
+where
+	H: Fn(Request) -> Fut,
+	Fut: Future<Output = CallResult> + Send + 'static,
+{
+	/// Starts handling a message that isn't a batch.
+	fn dispatch(&self, msg: Value) -> Dispatched {
+		let id = msg.get("id").cloned().and_then(|id| Id::try_from(id).ok());
+		let request = match Message::try_from(msg) {
+			Ok(Message::Request(request)) => request,
+			// Responses are only expected if the server sends requests, which this one doesn't.
+			Ok(Message::Response(..)) => return Dispatched::Done(None),
+			Err(e) => return Dispatched::Done(Some(Response::error(id, e))),
+		};
+		if request.method == CANCEL_METHOD && request.is_notification() {
+			self.cancel(request.params);
+			return Dispatched::Done(None);
+		}
+		let id = request.id.clone();
+		let task = tokio::spawn((self.handler)(request));
+		if let Some(id) = &id {
+			let mut in_flight = self.in_flight.lock().expect(LOCK_POISON);
+			in_flight.insert(id.clone(), task.abort_handle());
+		}
+		Dispatched::Running(id, task)
+	}
+	fn cancel(&self, params: Option<Value>) {
+		let Some(id) = params
+			.and_then(|mut params| params.get_mut("id").map(Value::take))
+			.and_then(|id| Id::try_from(id).ok())
+		else {
+			return;
+		};
+		if let Some(task) = self.in_flight.lock().expect(LOCK_POISON).get(&id) {
+			task.abort();
+		}
+	}
+	/// Waits for a dispatched message to be handled and returns the response, if any.
+	async fn finish(&self, dispatched: Dispatched) -> Option<Response> {
+		let (id, task) = match dispatched {
+			Dispatched::Done(response) => return response,
+			Dispatched::Running(id, task) => (id, task),
+		};
+		let result = match task.await {
+			Ok(result) => result,
+			Err(e) if e.is_cancelled() => Err(ErrorObject::new(
+				ErrorObject::REQUEST_CANCELLED,
+				"request cancelled",
+			)),
+			Err(..) => Err(ErrorObject::internal_error("request handler panicked")),
+		};
+		let id = id?;
+		self.in_flight.lock().expect(LOCK_POISON).remove(&id);
+		Some(Response {
+			id: Some(id),
+			result,
+		})
+	}
+}
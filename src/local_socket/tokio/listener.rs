@@ -1,4 +1,7 @@
-use super::{super::Name, Stream};
+use super::{
+	super::{ExhaustionPolicy, Name},
+	Stream,
+};
 use std::io;
 
 impmod! {local_socket::tokio,
@@ -12,6 +15,9 @@ impmod! {local_socket::tokio,
 /// [Name reclamation](super::super::Stream#name-reclamation) is performed by default on
 /// backends that necessitate it.
 ///
+/// The server in the example below runs forever. The [`server`](super::server) module provides a
+/// runner which can be shut down gracefully and limits the number of connections.
+///
 /// # Examples
 ///
 /// ## Basic server
@@ -111,6 +117,12 @@ impl Listener {
 	pub fn bind_without_name_reclamation(name: Name<'_>) -> io::Result<Self> {
 		ListenerImpl::bind(name, false).map(Self)
 	}
+	/// Like [`bind()`](Self::bind), but atomically replaces the socket file of any listener already
+	/// bound to the name. See [the synchronous
+	/// version](super::super::traits::Listener::bind_replacing) for more.
+	pub fn bind_replacing(name: Name<'_>) -> io::Result<Self> {
+		ListenerImpl::bind_replacing(name).map(Self)
+	}
 
 	/// Listens for incoming connections to the socket, asynchronously waiting until a client is
 	/// connected.
@@ -119,6 +131,19 @@ impl Listener {
 		Ok(Stream(self.0.accept().await?))
 	}
 
+	/// Sets how the listener deals with running out of file descriptors, or restores the default
+	/// behavior of returning the error right away if `None` is passed.
+	///
+	/// Instead of failing, `.accept()` waits before trying again, without blocking the runtime.
+	/// See [`ExhaustionPolicy`] for more.
+	///
+	/// # Errors
+	/// Fails if the spare file descriptor cannot be opened.
+	#[inline]
+	pub fn set_exhaustion_policy(&mut self, policy: Option<ExhaustionPolicy>) -> io::Result<()> {
+		self.0.set_exhaustion_policy(policy)
+	}
+
 	/// Disables [name reclamation](super::super::Stream#name-reclamation) on the listener.
 	#[inline]
 	pub fn do_not_reclaim_name_on_drop(&mut self) {
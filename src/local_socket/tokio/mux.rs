@@ -0,0 +1,574 @@
+//! Multiplexing of many independent substreams over one Tokio local socket stream.
+//!
+//! A [`Session`] wraps a stream and lets either end [open](Session::open) numbered logical
+//! [`Substream`]s, which the other end [accepts](Session::accept). Substreams implement Tokio's
+//! `AsyncRead` and `AsyncWrite` and can be used independently of each other from different tasks,
+//! saving the need to make a separate connection for each of them.
+//!
+//! The design is modeled after [yamux], although the two are not wire-compatible:
+//! -	**Flow control** – each substream has a receive window, which is the amount of data that
+//!   	the other end is allowed to send before the receiving end has read it. Writes to a substream
+//!   	whose window is exhausted wait until the application on the other end reads some data,
+//!   	which means that a slow reader only ever holds up its own substream, never the others.
+//!   	Windows start at 256 KiB and can be made larger with
+//!   	[`SessionOptions::window_size`].
+//! -	**Half-close** – [shutting down](tokio::io::AsyncWriteExt::shutdown) a substream signals
+//!   	end-of-file to the reading side of the other end, while the other direction remains usable.
+//! -	**Reset** – dropping a substream which the other end can still send data to resets it,
+//!   	causing further writes on the other end to fail.
+//!
+//! Both ends must use the same [`Mode`] in opposite ways – one must be a
+//! [`Client`](Mode::Client) and the other a [`Server`](Mode::Server) – so that the substream IDs
+//! they allocate don't collide. The roles have no other effect: both ends can open and accept
+//! substreams.
+//!
+//! [yamux]: https://github.com/hashicorp/yamux/blob/master/spec.md
+//!
+//! # Examples
+//! ```no_run
+//! # #[tokio::main]
+//! # async fn main() -> std::io::Result<()> {
+//! use interprocess::local_socket::tokio::{
+//! 	mux::{Mode, Session},
+//! 	Stream,
+//! };
+//! use tokio::io::{AsyncReadExt, AsyncWriteExt};
+//!
+//! let (a, b) = Stream::pair().await?;
+//! let (client, server) = (Session::new(a, Mode::Client), Session::new(b, Mode::Server));
+//!
+//! let mut first = client.open()?;
+//! let mut second = client.open()?;
+//! second.write_all(b"second").await?;
+//! second.shutdown().await?;
+//! first.write_all(b"first").await?;
+//! first.shutdown().await?;
+//!
+//! let mut buf = String::new();
+//! server.accept().await?.read_to_string(&mut buf).await?;
+//! assert_eq!(buf, "first");
+//! buf.clear();
+//! server.accept().await?.read_to_string(&mut buf).await?;
+//! assert_eq!(buf, "second");
+//! # Ok(()) }
+//! ```
+
+use super::{framing::Framed, RecvHalf, Stream};
+use crate::{local_socket::framing::FramingOptions, poison_error, LOCK_POISON};
+use std::{
+	collections::{HashMap, VecDeque},
+	fmt::{self, Debug, Formatter},
+	io,
+	pin::Pin,
+	sync::{Arc, Mutex, MutexGuard, Weak},
+	task::{Context, Poll, Waker},
+};
+use tokio::{
+	io::{AsyncRead, AsyncWrite, ReadBuf},
+	sync::{mpsc, Mutex as AsyncMutex},
+	task::AbortHandle,
+};
+
+const OPEN: u8 = 0;
+const DATA: u8 = 1;
+const WINDOW_UPDATE: u8 = 2;
+const FIN: u8 = 3;
+const RESET: u8 = 4;
+/// The length of the kind and substream ID of a frame.
+const HEADER_LEN: usize = 5;
+/// The maximum amount of data sent in one frame.
+const MAX_DATA_LEN: usize = 16 * 1024;
+/// The receive window that both ends assume each substream starts with.
+const INITIAL_WINDOW: u32 = 256 * 1024;
+
+fn framing() -> FramingOptions {
+	FramingOptions::new().max_frame_size(HEADER_LEN.saturating_add(MAX_DATA_LEN))
+}
+fn encode(kind: u8, id: u32, payload: &[u8]) -> Vec<u8> {
+	let mut frame = Vec::with_capacity(payload.len().saturating_add(HEADER_LEN));
+	frame.push(kind);
+	frame.extend_from_slice(&id.to_le_bytes());
+	frame.extend_from_slice(payload);
+	frame
+}
+fn decode(frame: &[u8]) -> Option<(u8, u32, &[u8])> {
+	let (&kind, rest) = frame.split_first()?;
+	let id = rest.get(..4)?.try_into().ok().map(u32::from_le_bytes)?;
+	Some((kind, id, rest.get(4..)?))
+}
+
+fn session_closed() -> io::Error {
+	io::Error::new(
+		io::ErrorKind::ConnectionAborted,
+		"multiplexing session closed",
+	)
+}
+
+/// Which end of a [`Session`] the local end is, determining the IDs of the substreams it opens.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Mode {
+	/// Opens substreams with odd IDs.
+	Client,
+	/// Opens substreams with even IDs.
+	Server,
+}
+
+/// Options for [`Session`].
+#[non_exhaustive]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct SessionOptions {
+	/// The receive window of each substream, i.e. how much data the other end may send on a
+	/// substream before the local end reads it. Values below 256 KiB are treated as 256 KiB.
+	///
+	/// The default value is 256 KiB.
+	pub window_size: u32,
+}
+impl SessionOptions {
+	/// Starts with the default parameters. Identical to `Default::default()`.
+	pub const fn new() -> Self {
+		Self {
+			window_size: INITIAL_WINDOW,
+		}
+	}
+	/// Sets the receive window size.
+	///
+	/// See the [associated field](#structfield.window_size) for more.
+	#[must_use = "this is not an in-place operation"]
+	#[inline]
+	pub fn window_size(mut self, window_size: u32) -> Self {
+		self.window_size = window_size;
+		self
+	}
+}
+impl Default for SessionOptions {
+	#[inline]
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+fn wake(waker: &mut Option<Waker>) {
+	if let Some(waker) = waker.take() {
+		waker.wake();
+	}
+}
+
+#[derive(Default)]
+struct SubState {
+	rbuf: VecDeque<u8>,
+	/// How much more data the other end may send.
+	recv_window: u32,
+	/// How much data has been read since the window was last extended.
+	unacked: u32,
+	/// How much more data may be sent.
+	send_window: u32,
+	local_fin: bool,
+	remote_fin: bool,
+	remote_reset: bool,
+	read_waker: Option<Waker>,
+	write_waker: Option<Waker>,
+}
+impl SubState {
+	fn wake(&mut self) {
+		wake(&mut self.read_waker);
+		wake(&mut self.write_waker);
+	}
+}
+
+struct State {
+	substreams: HashMap<u32, SubState>,
+	next_id: Option<u32>,
+	/// The remainder of dividing the IDs of locally opened substreams by 2.
+	local_parity: u32,
+	closed: bool,
+}
+
+struct Shared {
+	state: Mutex<State>,
+	outbox: mpsc::UnboundedSender<Vec<u8>>,
+	incoming: AsyncMutex<mpsc::UnboundedReceiver<u32>>,
+	window_size: u32,
+	reader: Mutex<Option<AbortHandle>>,
+}
+impl Shared {
+	fn lock(&self) -> io::Result<MutexGuard<'_, State>> {
+		self.state.lock().map_err(poison_error)
+	}
+	fn send(&self, kind: u8, id: u32, payload: &[u8]) {
+		// If the writer is gone, so is the connection, which the reader will notice.
+		let _ = self.outbox.send(encode(kind, id, payload));
+	}
+	/// Creates the state of a new substream and grants the other end the configured window, which
+	/// has to be done after the substream is opened.
+	fn new_substream(&self, id: u32) -> SubState {
+		let extra = self.window_size.saturating_sub(INITIAL_WINDOW);
+		if extra > 0 {
+			self.send(WINDOW_UPDATE, id, &extra.to_le_bytes());
+		}
+		SubState {
+			recv_window: self.window_size,
+			send_window: INITIAL_WINDOW,
+			..SubState::default()
+		}
+	}
+}
+impl Drop for Shared {
+	fn drop(&mut self) {
+		if let Some(reader) = self.reader.get_mut().ok().and_then(Option::take) {
+			reader.abort();
+		}
+	}
+}
+
+/// Multiplexing session over a Tokio local socket stream.
+///
+/// See the [module-level documentation](self) for more.
+///
+/// The session can be cloned cheaply. The underlying connection is closed once all clones of the
+/// session and all of its substreams are dropped. If the connection is closed by the other end or
+/// the other end violates the protocol, writes to all substreams fail with
+/// [`ConnectionAborted`](io::ErrorKind::ConnectionAborted), as do reads from substreams which the
+/// other end hasn't shut down, once they have returned the data that has already been received.
+#[derive(Clone)]
+pub struct Session(Arc<Shared>);
+impl Session {
+	/// Starts a session with the default [options](SessionOptions).
+	///
+	/// # Panics
+	/// Must be called from within a Tokio runtime, since tasks for sending and receiving frames are
+	/// spawned on it.
+	#[inline]
+	pub fn new(stream: Stream, mode: Mode) -> Self {
+		Self::with_options(stream, mode, SessionOptions::new())
+	}
+	/// Starts a session with the given options.
+	///
+	/// # Panics
+	/// Must be called from within a Tokio runtime, since tasks for sending and receiving frames are
+	/// spawned on it.
+	pub fn with_options(stream: Stream, mode: Mode, options: SessionOptions) -> Self {
+		let (rh, sh) = stream.split();
+		let (outbox, mut queue) = mpsc::unbounded_channel::<Vec<u8>>();
+		let mut writer = Framed::with_options(sh, framing());
+		tokio::spawn(async move {
+			while let Some(frame) = queue.recv().await {
+				if writer.send_frame(&frame).await.is_err() {
+					break;
+				}
+			}
+		});
+		let (incoming_tx, incoming) = mpsc::unbounded_channel();
+		let first_id = match mode {
+			Mode::Client => 1,
+			Mode::Server => 2,
+		};
+		let shared = Arc::new(Shared {
+			state: Mutex::new(State {
+				substreams: HashMap::new(),
+				next_id: Some(first_id),
+				local_parity: first_id % 2,
+				closed: false,
+			}),
+			outbox,
+			incoming: AsyncMutex::new(incoming),
+			window_size: options.window_size.max(INITIAL_WINDOW),
+			reader: Mutex::new(None),
+		});
+		let reader = tokio::spawn(read_loop(
+			Framed::with_options(rh, framing()),
+			Arc::downgrade(&shared),
+			incoming_tx,
+		));
+		*shared.reader.lock().expect(LOCK_POISON) = Some(reader.abort_handle());
+		Self(shared)
+	}
+
+	/// Opens a new substream.
+	///
+	/// The other end is notified of the new substream immediately, and data can be written to it
+	/// right away.
+	///
+	/// # Errors
+	/// Fails with [`ConnectionAborted`](io::ErrorKind::ConnectionAborted) if the session is closed
+	/// and with [`Other`](io::ErrorKind::Other) if substream IDs have been exhausted.
+	pub fn open(&self) -> io::Result<Substream> {
+		let mut state = self.0.lock()?;
+		if state.closed {
+			return Err(session_closed());
+		}
+		let id = state
+			.next_id
+			.ok_or_else(|| io::Error::other("substream IDs exhausted"))?;
+		state.next_id = id.checked_add(2);
+		self.0.send(OPEN, id, &[]);
+		let sub = self.0.new_substream(id);
+		state.substreams.insert(id, sub);
+		drop(state);
+		Ok(Substream {
+			shared: Arc::clone(&self.0),
+			id,
+		})
+	}
+	/// Waits for the other end to open a substream.
+	///
+	/// # Errors
+	/// Fails with [`ConnectionAborted`](io::ErrorKind::ConnectionAborted) once the session is
+	/// closed and no more substreams are waiting to be accepted.
+	pub async fn accept(&self) -> io::Result<Substream> {
+		let id = self.0.incoming.lock().await.recv().await;
+		let id = id.ok_or_else(session_closed)?;
+		Ok(Substream {
+			shared: Arc::clone(&self.0),
+			id,
+		})
+	}
+}
+impl Debug for Session {
+	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+		f.debug_struct("Session")
+			.field("window_size", &self.0.window_size)
+			.finish_non_exhaustive()
+	}
+}
+
+/// Receives frames and applies them to the state of the session until the connection is closed.
+async fn read_loop(
+	mut framed: Framed<RecvHalf>,
+	shared: Weak<Shared>,
+	incoming: mpsc::UnboundedSender<u32>,
+) {
+	while let Ok(Some(frame)) = framed.recv_frame().await {
+		let Some(shared) = shared.upgrade() else {
+			return;
+		};
+		let Ok(mut state) = shared.lock() else {
+			return;
+		};
+		if !handle_frame(&shared, &mut state, &incoming, frame) {
+			break;
+		}
+	}
+	let Some(shared) = shared.upgrade() else {
+		return;
+	};
+	let Ok(mut state) = shared.lock() else {
+		return;
+	};
+	state.closed = true;
+	state.substreams.values_mut().for_each(SubState::wake);
+}
+
+/// Applies one frame to the state of the session, returning `false` if it violates the protocol.
+fn handle_frame(
+	shared: &Shared,
+	state: &mut State,
+	incoming: &mpsc::UnboundedSender<u32>,
+	frame: &[u8],
+) -> bool {
+	let Some((kind, id, payload)) = decode(frame) else {
+		return false;
+	};
+	if kind == OPEN {
+		// The other end must use the IDs of the opposite parity.
+		if id % 2 == state.local_parity || state.substreams.contains_key(&id) {
+			return false;
+		}
+		let sub = shared.new_substream(id);
+		state.substreams.insert(id, sub);
+		let _ = incoming.send(id);
+		return true;
+	}
+	// Frames for substreams that have been dropped are expected until the other end learns of
+	// that.
+	let Some(sub) = state.substreams.get_mut(&id) else {
+		return true;
+	};
+	match kind {
+		DATA => {
+			let Some(window) = u32::try_from(payload.len())
+				.ok()
+				.and_then(|len| sub.recv_window.checked_sub(len))
+			else {
+				return false;
+			};
+			if sub.remote_fin {
+				return false;
+			}
+			sub.recv_window = window;
+			sub.rbuf.extend(payload);
+			wake(&mut sub.read_waker);
+		}
+		WINDOW_UPDATE => {
+			let Some(Ok(increment)) = payload.get(..4).map(<[u8; 4]>::try_from) else {
+				return false;
+			};
+			let increment = u32::from_le_bytes(increment);
+			sub.send_window = sub.send_window.saturating_add(increment);
+			wake(&mut sub.write_waker);
+		}
+		FIN => {
+			sub.remote_fin = true;
+			wake(&mut sub.read_waker);
+		}
+		RESET => {
+			sub.remote_reset = true;
+			sub.wake();
+		}
+		_ => return false,
+	}
+	true
+}
+
+/// Logical substream of a [`Session`].
+///
+/// See the [module-level documentation](self) for more.
+///
+/// Writes are handed off to the session for sending without waiting for them to be written to the
+/// underlying stream, but never exceed the receive window of the other end. Shutting the substream
+/// down sends end-of-file to the other end. Dropping it does the same, and additionally resets the
+/// substream if the other end hasn't shut its side down.
+pub struct Substream {
+	shared: Arc<Shared>,
+	id: u32,
+}
+impl Substream {
+	/// Returns the ID of the substream, which is the same on both ends.
+	#[inline]
+	pub fn id(&self) -> u32 {
+		self.id
+	}
+	fn with_state<R>(
+		&self,
+		f: impl FnOnce(&Shared, bool, &mut SubState) -> io::Result<R>,
+	) -> io::Result<R> {
+		let mut state = self.shared.lock()?;
+		let closed = state.closed;
+		match state.substreams.get_mut(&self.id) {
+			Some(sub) => f(&self.shared, closed, sub),
+			None => Err(session_closed()),
+		}
+	}
+}
+impl AsyncRead for Substream {
+	fn poll_read(
+		self: Pin<&mut Self>,
+		cx: &mut Context<'_>,
+		buf: &mut ReadBuf<'_>,
+	) -> Poll<io::Result<()>> {
+		let window_size = self.shared.window_size;
+		let rslt = self.with_state(|shared, closed, sub| {
+			if sub.rbuf.is_empty() {
+				return if sub.remote_fin {
+					Ok(true)
+				} else if sub.remote_reset {
+					Err(io::Error::new(
+						io::ErrorKind::ConnectionReset,
+						"substream reset by the other end",
+					))
+				} else if closed {
+					Err(session_closed())
+				} else {
+					sub.read_waker = Some(cx.waker().clone());
+					Ok(false)
+				};
+			}
+			let (front, _) = sub.rbuf.as_slices();
+			let n = front.len().min(buf.remaining());
+			buf.put_slice(front.get(..n).unwrap_or_default());
+			sub.rbuf.drain(..n);
+			// n is bounded by the receive window, which is a u32.
+			sub.unacked = sub
+				.unacked
+				.saturating_add(u32::try_from(n).unwrap_or(u32::MAX));
+			if sub.unacked >= window_size / 2 && !sub.remote_fin && !closed {
+				shared.send(WINDOW_UPDATE, self.id, &sub.unacked.to_le_bytes());
+				sub.recv_window = sub.recv_window.saturating_add(sub.unacked);
+				sub.unacked = 0;
+			}
+			Ok(true)
+		});
+		match rslt {
+			Ok(true) => Poll::Ready(Ok(())),
+			Ok(false) => Poll::Pending,
+			Err(e) => Poll::Ready(Err(e)),
+		}
+	}
+}
+impl AsyncWrite for Substream {
+	fn poll_write(
+		self: Pin<&mut Self>,
+		cx: &mut Context<'_>,
+		buf: &[u8],
+	) -> Poll<io::Result<usize>> {
+		let rslt = self.with_state(|shared, closed, sub| {
+			if closed {
+				return Err(session_closed());
+			}
+			if sub.local_fin || sub.remote_reset {
+				return Err(io::Error::new(
+					io::ErrorKind::BrokenPipe,
+					"substream closed for writing",
+				));
+			}
+			if buf.is_empty() {
+				return Ok(Some(0));
+			}
+			let window = usize::try_from(sub.send_window).unwrap_or(usize::MAX);
+			let n = buf.len().min(window).min(MAX_DATA_LEN);
+			if n == 0 {
+				sub.write_waker = Some(cx.waker().clone());
+				return Ok(None);
+			}
+			shared.send(DATA, self.id, buf.get(..n).unwrap_or_default());
+			// n is bounded by the send window.
+			sub.send_window = sub
+				.send_window
+				.saturating_sub(u32::try_from(n).unwrap_or(u32::MAX));
+			Ok(Some(n))
+		});
+		match rslt {
+			Ok(Some(n)) => Poll::Ready(Ok(n)),
+			Ok(None) => Poll::Pending,
+			Err(e) => Poll::Ready(Err(e)),
+		}
+	}
+	#[inline]
+	fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+		Poll::Ready(Ok(()))
+	}
+	fn poll_shutdown(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+		Poll::Ready(self.with_state(|shared, closed, sub| {
+			if !sub.local_fin && !closed {
+				sub.local_fin = true;
+				shared.send(FIN, self.id, &[]);
+			}
+			Ok(())
+		}))
+	}
+}
+impl Drop for Substream {
+	fn drop(&mut self) {
+		let Ok(mut state) = self.shared.lock() else {
+			return;
+		};
+		let Some(sub) = state.substreams.remove(&self.id) else {
+			return;
+		};
+		if state.closed || sub.remote_reset {
+			return;
+		}
+		if !sub.local_fin {
+			self.shared.send(FIN, self.id, &[]);
+		}
+		if !sub.remote_fin {
+			self.shared.send(RESET, self.id, &[]);
+		}
+	}
+}
+impl Debug for Substream {
+	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+		f.debug_struct("Substream")
+			.field("id", &self.id)
+			.finish_non_exhaustive()
+	}
+}
@@ -0,0 +1,599 @@
+//! A publish/subscribe broker on top of Tokio local sockets.
+//!
+//! A [`Broker`] accepts connections from a [`Listener`] and serves each of them in its own pair of
+//! tasks. Clients, represented by [`Client`], subscribe to topics and publish messages to them;
+//! every message published to a topic is delivered to all clients subscribed to that topic at the
+//! time of publishing, including the publisher itself if it is subscribed.
+//!
+//! Messages waiting to be delivered to a client are held in a bounded per-client queue, so that a
+//! client which doesn't keep up with the rate of publishing cannot make the broker use an unbounded
+//! amount of memory. What happens when that queue is full is decided by the
+//! [`SlowSubscriberPolicy`].
+//!
+//! The protocol is built on [length-prefixed frames](super::framing), with [`FramingOptions`]
+//! which both the broker and the clients must agree on. Each frame starts with a byte for its kind:
+//! -	from clients, `0` to subscribe to and `1` to unsubscribe from the topic which makes up the
+//!   	rest of the frame, and `2` to publish a message;
+//! -	from the broker, `3` for a published message, `4` to acknowledge a subscription change and
+//!   	`5` to notify the client that it is being disconnected.
+//!
+//! Messages consist of the length of the topic as a little-endian `u16`, the topic and the payload.
+//!
+//! # Examples
+//! ```no_run
+//! # #[tokio::main]
+//! # async fn main() -> std::io::Result<()> {
+//! use interprocess::local_socket::{
+//! 	tokio::{
+//! 		pubsub::{Broker, Client},
+//! 		Listener, Stream,
+//! 	},
+//! 	ToNsName,
+//! };
+//!
+//! let name = "example-pubsub.sock".to_ns_name()?;
+//! let listener = Listener::bind(name.borrow())?;
+//! tokio::spawn(async move { Broker::new(listener).run().await });
+//!
+//! let mut subscriber = Client::new(Stream::connect(name.borrow()).await?);
+//! subscriber.subscribe("greetings").await?;
+//!
+//! let mut publisher = Client::new(Stream::connect(name).await?);
+//! publisher.publish("greetings", b"Hello").await?;
+//!
+//! let msg = subscriber.recv().await?.expect("broker closed the connection");
+//! assert_eq!((&*msg.topic, &*msg.payload), ("greetings", &b"Hello"[..]));
+//! # Ok(()) }
+//! ```
+
+use super::{framing::Framed, Listener, RecvHalf, SendHalf, Stream};
+use crate::{local_socket::framing::FramingOptions, LOCK_POISON};
+use std::{
+	collections::{HashMap, VecDeque},
+	fmt::{self, Debug, Formatter},
+	io,
+	pin::pin,
+	sync::{Arc, Mutex},
+};
+use tokio::{sync::Notify, task::AbortHandle};
+
+const SUBSCRIBE: u8 = 0;
+const UNSUBSCRIBE: u8 = 1;
+const PUBLISH: u8 = 2;
+const MESSAGE: u8 = 3;
+const ACK: u8 = 4;
+const DISCONNECTED: u8 = 5;
+
+fn invalid_data(msg: &str) -> io::Error {
+	io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+/// Encodes a frame consisting of the kind byte, the length of the topic as a little-endian `u16`,
+/// the topic and the payload.
+fn encode_message(kind: u8, topic: &str, payload: &[u8]) -> io::Result<Vec<u8>> {
+	let topic_len = u16::try_from(topic.len())
+		.map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "topic is too long"))?;
+	let mut frame = Vec::with_capacity(topic.len().saturating_add(payload.len()).saturating_add(3));
+	frame.push(kind);
+	frame.extend_from_slice(&topic_len.to_le_bytes());
+	frame.extend_from_slice(topic.as_bytes());
+	frame.extend_from_slice(payload);
+	Ok(frame)
+}
+
+/// Splits the body of a frame encoded with [`encode_message`] (without the kind byte) into the
+/// topic and the payload.
+fn decode_message(body: &[u8]) -> io::Result<(&str, &[u8])> {
+	let truncated = || invalid_data("message is truncated");
+	let (len, rest) = (
+		body.get(..2).ok_or_else(truncated)?,
+		body.get(2..).unwrap_or_default(),
+	);
+	let len = usize::from(u16::from_le_bytes([
+		len.first().copied().unwrap_or_default(),
+		len.get(1).copied().unwrap_or_default(),
+	]));
+	let (topic, payload) = (
+		rest.get(..len).ok_or_else(truncated)?,
+		rest.get(len..).unwrap_or_default(),
+	);
+	let topic = std::str::from_utf8(topic).map_err(|_| invalid_data("topic is not valid UTF-8"))?;
+	Ok((topic, payload))
+}
+
+/// What the broker does when a message is published to a subscriber whose queue is full.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Default)]
+pub enum SlowSubscriberPolicy {
+	/// The oldest message in the queue is discarded to make room for the new one. Publishers are
+	/// never held up, but slow subscribers silently miss messages.
+	#[default]
+	DropOldest,
+	/// The subscriber is sent a notice and disconnected; its [`recv()`](Client::recv) fails with
+	/// [`ConnectionAborted`](io::ErrorKind::ConnectionAborted) once the messages it has already
+	/// been sent are received.
+	Disconnect,
+	/// The publisher waits until there is room in the queue. No messages are lost, but a single
+	/// slow subscriber holds up every client publishing to its topics.
+	Block,
+}
+
+/// Configuration of a [`Broker`].
+#[derive(Copy, Clone, Debug)]
+#[non_exhaustive]
+pub struct BrokerOptions {
+	/// The maximum number of messages which can wait to be delivered to a single client.
+	///
+	/// The default value is 1024. A capacity of 0 is treated as 1.
+	pub queue_capacity: usize,
+	/// What to do when a client's queue is full.
+	///
+	/// The default value is [`SlowSubscriberPolicy::DropOldest`].
+	pub slow_subscriber_policy: SlowSubscriberPolicy,
+	/// The framing used for all connections, which clients must agree with.
+	///
+	/// The default value is [`FramingOptions::new()`].
+	pub framing: FramingOptions,
+}
+impl BrokerOptions {
+	/// Creates an options table with default values.
+	#[inline]
+	pub const fn new() -> Self {
+		Self {
+			queue_capacity: 1024,
+			slow_subscriber_policy: SlowSubscriberPolicy::DropOldest,
+			framing: FramingOptions::new(),
+		}
+	}
+	/// Sets the per-client queue capacity.
+	///
+	/// See the [associated field](#structfield.queue_capacity) for more.
+	#[must_use = "this is not an in-place operation"]
+	#[inline]
+	pub fn queue_capacity(mut self, queue_capacity: usize) -> Self {
+		self.queue_capacity = queue_capacity;
+		self
+	}
+	/// Sets the policy for slow subscribers.
+	///
+	/// See the [associated field](#structfield.slow_subscriber_policy) for more.
+	#[must_use = "this is not an in-place operation"]
+	#[inline]
+	pub fn slow_subscriber_policy(mut self, slow_subscriber_policy: SlowSubscriberPolicy) -> Self {
+		self.slow_subscriber_policy = slow_subscriber_policy;
+		self
+	}
+	/// Sets the framing options.
+	///
+	/// See the [associated field](#structfield.framing) for more.
+	#[must_use = "this is not an in-place operation"]
+	#[inline]
+	pub fn framing(mut self, framing: FramingOptions) -> Self {
+		self.framing = framing;
+		self
+	}
+}
+impl Default for BrokerOptions {
+	#[inline]
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+/// What the writer task of a connection is to do next.
+enum Next {
+	Send(Arc<[u8]>),
+	Disconnect,
+	Close,
+}
+
+#[derive(Default)]
+struct QueueState {
+	/// Published messages and control frames, in the order in which they are to be sent.
+	frames: VecDeque<Arc<[u8]>>,
+	/// How many of the frames are published messages, which are the only ones that count towards
+	/// the capacity of the queue.
+	messages: usize,
+	/// Set when the connection ends, after which nothing more is queued.
+	closed: bool,
+	/// Set when the client is to be disconnected under [`SlowSubscriberPolicy::Disconnect`].
+	disconnected: bool,
+}
+
+/// The outgoing queue of a single connection.
+struct Queue {
+	state: Mutex<QueueState>,
+	/// Notified whenever the state changes, which wakes both the writer task waiting for frames and
+	/// publishers waiting for room.
+	changed: Notify,
+	capacity: usize,
+	policy: SlowSubscriberPolicy,
+}
+impl Queue {
+	fn new(opts: &BrokerOptions) -> Self {
+		Self {
+			state: Mutex::default(),
+			changed: Notify::new(),
+			capacity: opts.queue_capacity.max(1),
+			policy: opts.slow_subscriber_policy,
+		}
+	}
+	/// Queues a published message, applying the slow subscriber policy if the queue is full.
+	///
+	/// Control frames neither count towards the capacity nor are ever dropped, so that a slow
+	/// subscriber can't lose the acknowledgement of a subscription change it is waiting for.
+	async fn publish(&self, frame: &Arc<[u8]>) {
+		loop {
+			// Registered before checking the state so that no notification is missed.
+			let changed = pin!(self.changed.notified());
+			let wait = {
+				let mut state = self.state.lock().expect(LOCK_POISON);
+				if state.closed || state.disconnected {
+					return;
+				}
+				if state.messages < self.capacity {
+					state.frames.push_back(Arc::clone(frame));
+					state.messages = state.messages.saturating_add(1);
+					false
+				} else {
+					match self.policy {
+						SlowSubscriberPolicy::DropOldest => {
+							// The queue being full means that there is at least one message in it.
+							if let Some(oldest) = state.frames.iter().position(|f| is_message(f)) {
+								state.frames.remove(oldest);
+							}
+							state.frames.push_back(Arc::clone(frame));
+							false
+						}
+						SlowSubscriberPolicy::Disconnect => {
+							state.disconnected = true;
+							false
+						}
+						SlowSubscriberPolicy::Block => true,
+					}
+				}
+			};
+			if !wait {
+				self.changed.notify_waiters();
+				return;
+			}
+			changed.await;
+		}
+	}
+	/// Queues a control frame, which is never dropped and never waits for room.
+	fn control(&self, frame: Arc<[u8]>) {
+		let mut state = self.state.lock().expect(LOCK_POISON);
+		if !state.closed {
+			state.frames.push_back(frame);
+			drop(state);
+			self.changed.notify_waiters();
+		}
+	}
+	fn close(&self) {
+		self.state.lock().expect(LOCK_POISON).closed = true;
+		self.changed.notify_waiters();
+	}
+	async fn next(&self) -> Next {
+		loop {
+			let changed = pin!(self.changed.notified());
+			{
+				let mut state = self.state.lock().expect(LOCK_POISON);
+				if state.disconnected {
+					return Next::Disconnect;
+				}
+				if let Some(frame) = state.frames.pop_front() {
+					if is_message(&frame) {
+						state.messages = state.messages.saturating_sub(1);
+					}
+					drop(state);
+					self.changed.notify_waiters();
+					return Next::Send(frame);
+				}
+				if state.closed {
+					return Next::Close;
+				}
+			}
+			changed.await;
+		}
+	}
+}
+
+fn is_message(frame: &[u8]) -> bool {
+	frame.first() == Some(&MESSAGE)
+}
+
+/// Subscribers of each topic, keyed by connection.
+type Topics = HashMap<String, HashMap<u64, Arc<Queue>>>;
+
+struct Shared {
+	topics: Mutex<Topics>,
+	opts: BrokerOptions,
+}
+impl Shared {
+	fn subscribe(&self, topic: &str, conn: u64, queue: &Arc<Queue>) {
+		let mut topics = self.topics.lock().expect(LOCK_POISON);
+		let subs = topics.entry(topic.to_owned()).or_default();
+		subs.insert(conn, Arc::clone(queue));
+	}
+	fn subscribers(&self, topic: &str) -> Vec<Arc<Queue>> {
+		let topics = self.topics.lock().expect(LOCK_POISON);
+		topics
+			.get(topic)
+			.map(|subs| subs.values().cloned().collect())
+			.unwrap_or_default()
+	}
+	fn unsubscribe(&self, topic: &str, conn: u64) {
+		let mut topics = self.topics.lock().expect(LOCK_POISON);
+		if let Some(subs) = topics.get_mut(topic) {
+			subs.remove(&conn);
+			if subs.is_empty() {
+				topics.remove(topic);
+			}
+		}
+	}
+}
+
+/// A publish/subscribe broker serving clients which connect to a [`Listener`].
+///
+/// See the [module-level documentation](self) for more.
+pub struct Broker {
+	listener: Listener,
+	shared: Arc<Shared>,
+}
+impl Broker {
+	/// Creates a broker with the default options.
+	pub fn new(listener: Listener) -> Self {
+		Self::with_options(listener, BrokerOptions::new())
+	}
+	/// Creates a broker with the given options.
+	pub fn with_options(listener: Listener, opts: BrokerOptions) -> Self {
+		let shared = Shared {
+			topics: Mutex::default(),
+			opts,
+		};
+		Self {
+			listener,
+			shared: Arc::new(shared),
+		}
+	}
+	/// Accepts and serves clients until accepting a connection fails, returning that error.
+	///
+	/// Each client is served in its own tasks, which keep running until it disconnects even if this
+	/// future is dropped or returns.
+	pub async fn run(&self) -> io::Result<()> {
+		for conn_id in 0_u64.. {
+			let conn = self.listener.accept().await?;
+			serve_connection(Arc::clone(&self.shared), conn_id, conn);
+		}
+		Ok(())
+	}
+}
+impl Debug for Broker {
+	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+		f.debug_struct("Broker")
+			.field("listener", &self.listener)
+			.field("opts", &self.shared.opts)
+			.finish_non_exhaustive()
+	}
+}
+
+/// Removes a connection from all topics and closes its queue when its reader task finishes or is
+/// aborted.
+struct Connection {
+	shared: Arc<Shared>,
+	id: u64,
+	queue: Arc<Queue>,
+}
+impl Drop for Connection {
+	fn drop(&mut self) {
+		let mut topics = self.shared.topics.lock().expect(LOCK_POISON);
+		topics.retain(|_, subs| {
+			subs.remove(&self.id);
+			!subs.is_empty()
+		});
+		drop(topics);
+		self.queue.close();
+	}
+}
+
+fn serve_connection(shared: Arc<Shared>, conn_id: u64, conn: Stream) {
+	let framing = shared.opts.framing;
+	let (rx, tx) = conn.split();
+	let queue = Arc::new(Queue::new(&shared.opts));
+	let conn = Connection {
+		shared,
+		id: conn_id,
+		queue: Arc::clone(&queue),
+	};
+	let reader = tokio::spawn(read_loop(conn, Framed::with_options(rx, framing)));
+	tokio::spawn(write_loop(
+		queue,
+		Framed::with_options(tx, framing),
+		reader.abort_handle(),
+	));
+}
+
+async fn read_loop(conn: Connection, mut rx: Framed<RecvHalf>) {
+	let ack: Arc<[u8]> = Arc::from(&[ACK][..]);
+	while let Ok(Some(frame)) = rx.recv_frame().await {
+		let Some((&kind, body)) = frame.split_first() else {
+			break;
+		};
+		match kind {
+			SUBSCRIBE | UNSUBSCRIBE => {
+				let Ok(topic) = std::str::from_utf8(body) else {
+					break;
+				};
+				if kind == SUBSCRIBE {
+					conn.shared.subscribe(topic, conn.id, &conn.queue);
+				} else {
+					conn.shared.unsubscribe(topic, conn.id);
+				}
+				conn.queue.control(Arc::clone(&ack));
+			}
+			PUBLISH => {
+				let Ok((topic, payload)) = decode_message(body) else {
+					break;
+				};
+				let Ok(msg) = encode_message(MESSAGE, topic, payload) else {
+					break;
+				};
+				let msg = Arc::<[u8]>::from(msg);
+				for sub in conn.shared.subscribers(topic) {
+					sub.publish(&msg).await;
+				}
+			}
+			_ => break,
+		}
+	}
+}
+
+async fn write_loop(queue: Arc<Queue>, mut tx: Framed<SendHalf>, reader: AbortHandle) {
+	loop {
+		match queue.next().await {
+			Next::Send(frame) => {
+				if tx.send_frame(&frame).await.is_err() {
+					break;
+				}
+			}
+			Next::Disconnect => {
+				let _ = tx.send_frame(&[DISCONNECTED]).await;
+				break;
+			}
+			Next::Close => break,
+		}
+	}
+	// Dropping the reader's half along with this one closes the connection, and the reader's
+	// cleanup keeps publishers from waiting on the queue.
+	reader.abort();
+}
+
+/// A message received from the broker.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Message {
+	/// The topic the message was published to.
+	pub topic: String,
+	/// The contents of the message.
+	pub payload: Vec<u8>,
+}
+
+/// A connection to a [`Broker`].
+///
+/// See the [module-level documentation](self) for more.
+#[derive(Debug)]
+pub struct Client {
+	framed: Framed<Stream>,
+	/// Messages received while waiting for an acknowledgement.
+	pending: VecDeque<Message>,
+}
+impl Client {
+	/// Wraps a stream connected to a broker, using the default framing options.
+	pub fn new(stream: Stream) -> Self {
+		Self::with_options(stream, FramingOptions::new())
+	}
+	/// Wraps a stream connected to a broker, using the given framing options.
+	pub fn with_options(stream: Stream, framing: FramingOptions) -> Self {
+		Self {
+			framed: Framed::with_options(stream, framing),
+			pending: VecDeque::new(),
+		}
+	}
+	/// Returns a reference to the underlying stream.
+	pub fn get_ref(&self) -> &Stream {
+		self.framed.get_ref()
+	}
+	/// Returns the underlying stream, discarding messages that have been received but not returned
+	/// by [`recv()`](Self::recv).
+	pub fn into_inner(self) -> Stream {
+		self.framed.into_inner()
+	}
+
+	/// Subscribes to a topic.
+	///
+	/// Once the returned future completes, every message published to the topic is delivered to this
+	/// client. Messages which arrive while waiting for the broker to confirm the subscription are
+	/// kept for [`recv()`](Self::recv).
+	pub async fn subscribe(&mut self, topic: &str) -> io::Result<()> {
+		self.change_subscription(SUBSCRIBE, topic).await
+	}
+	/// Unsubscribes from a topic.
+	///
+	/// Once the returned future completes, no more messages published to the topic are delivered to
+	/// this client, although ones already received may still be returned by
+	/// [`recv()`](Self::recv).
+	pub async fn unsubscribe(&mut self, topic: &str) -> io::Result<()> {
+		self.change_subscription(UNSUBSCRIBE, topic).await
+	}
+	async fn change_subscription(&mut self, kind: u8, topic: &str) -> io::Result<()> {
+		let mut frame = Vec::with_capacity(topic.len().saturating_add(1));
+		frame.push(kind);
+		frame.extend_from_slice(topic.as_bytes());
+		self.framed.send_frame(&frame).await?;
+		loop {
+			match self.recv_raw().await? {
+				Some(Received::Message(msg)) => self.pending.push_back(msg),
+				Some(Received::Ack) => return Ok(()),
+				None => {
+					return Err(io::Error::new(
+						io::ErrorKind::ConnectionAborted,
+						"broker closed the connection",
+					))
+				}
+			}
+		}
+	}
+
+	/// Publishes a message to a topic.
+	///
+	/// Topics longer than 65535 bytes are rejected with an error of kind
+	/// [`InvalidInput`](io::ErrorKind::InvalidInput).
+	pub async fn publish(&mut self, topic: &str, payload: &[u8]) -> io::Result<()> {
+		self.framed
+			.send_frame(&encode_message(PUBLISH, topic, payload)?)
+			.await
+	}
+
+	/// Receives the next message published to one of the subscribed topics, returning `None` if
+	/// the broker has closed the connection.
+	pub async fn recv(&mut self) -> io::Result<Option<Message>> {
+		if let Some(msg) = self.pending.pop_front() {
+			return Ok(Some(msg));
+		}
+		loop {
+			match self.recv_raw().await? {
+				Some(Received::Message(msg)) => return Ok(Some(msg)),
+				// Not expected outside of subscription changes, but harmless.
+				Some(Received::Ack) => {}
+				None => return Ok(None),
+			}
+		}
+	}
+
+	async fn recv_raw(&mut self) -> io::Result<Option<Received>> {
+		let Some(frame) = self.framed.recv_frame().await? else {
+			return Ok(None);
+		};
+		let Some((&kind, body)) = frame.split_first() else {
+			return Err(invalid_data("empty frame"));
+		};
+		match kind {
+			MESSAGE => {
+				let (topic, payload) = decode_message(body)?;
+				Ok(Some(Received::Message(Message {
+					topic: topic.to_owned(),
+					payload: payload.to_owned(),
+				})))
+			}
+			ACK => Ok(Some(Received::Ack)),
+			DISCONNECTED => Err(io::Error::new(
+				io::ErrorKind::ConnectionAborted,
+				"disconnected by the broker for falling behind",
+			)),
+			_ => Err(invalid_data("unknown frame kind")),
+		}
+	}
+}
+
+enum Received {
+	Message(Message),
+	Ack,
+}
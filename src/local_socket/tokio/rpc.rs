@@ -0,0 +1,401 @@
+//! Concurrent request/response calls over a single Tokio local socket stream.
+//!
+//! A [`Caller`] tags each outgoing request with an ID and routes the response with the same ID back
+//! to the call that is waiting for it, so that any number of tasks can make calls over one
+//! connection at the same time, and responses can arrive in any order. Calls can be given a
+//! timeout, and a call which is dropped before it completes, including by timing out, is cancelled
+//! on the other end. The other end of the connection is served with [`serve()`], which handles each
+//! request in its own task.
+//!
+//! Requests and responses are opaque byte strings, into which values can be serialized in any
+//! format. They are sent as frames in the format of the [`framing`](super::framing) module, each
+//! consisting of the following:
+//! -	one byte for the kind of frame: `0` for a request, `1` for the cancellation of a request, `2`
+//!   	for a response and `3` for an error response, which contains a UTF-8 error message;
+//! -	the ID of the call as a little-endian `u64`;
+//! -	the request or response, if any.
+//!
+//! # Examples
+//! ```no_run
+//! # #[tokio::main]
+//! # async fn main() -> std::io::Result<()> {
+//! use interprocess::local_socket::tokio::{
+//! 	rpc::{serve, Caller},
+//! 	Stream,
+//! };
+//!
+//! let (a, b) = Stream::pair().await?;
+//! tokio::spawn(serve(b, |mut request: Vec<u8>| async move {
+//! 	request.reverse();
+//! 	request
+//! }));
+//!
+//! let caller = Caller::new(a);
+//! let (r1, r2) = tokio::join!(caller.call(b"Hello"), caller.call(b"world!"));
+//! assert_eq!(r1?, b"olleH");
+//! assert_eq!(r2?, b"!dlrow");
+//! # Ok(()) }
+//! ```
+
+use super::{framing::Framed, RecvHalf, SendHalf, Stream};
+use crate::{local_socket::framing::FramingOptions, poison_error, LOCK_POISON};
+use std::{
+	collections::HashMap,
+	fmt::{self, Debug, Formatter},
+	future::Future,
+	io,
+	sync::{
+		atomic::{AtomicU64, Ordering::Relaxed},
+		Arc, Mutex,
+	},
+	time::Duration,
+};
+use tokio::{
+	sync::{mpsc, oneshot},
+	task::AbortHandle,
+};
+
+const REQUEST: u8 = 0;
+const CANCEL: u8 = 1;
+const RESPONSE: u8 = 2;
+const ERROR: u8 = 3;
+/// The length of the kind and ID of a frame.
+const HEADER_LEN: usize = 9;
+
+fn decode(frame: &[u8]) -> Option<(u8, u64, &[u8])> {
+	let (&kind, rest) = frame.split_first()?;
+	let id = rest.get(..8)?.try_into().ok().map(u64::from_le_bytes)?;
+	Some((kind, id, rest.get(8..)?))
+}
+
+/// Queue of encoded frames to be sent by a writer task.
+#[derive(Clone)]
+struct Outbox {
+	tx: mpsc::UnboundedSender<Vec<u8>>,
+	max_frame_size: usize,
+}
+impl Outbox {
+	fn send(&self, kind: u8, id: u64, payload: &[u8]) -> io::Result<()> {
+		if payload.len() > self.max_frame_size.saturating_sub(HEADER_LEN) {
+			return Err(io::Error::new(
+				io::ErrorKind::InvalidInput,
+				"message exceeds maximum frame size",
+			));
+		}
+		let mut frame = Vec::with_capacity(payload.len().saturating_add(HEADER_LEN));
+		frame.push(kind);
+		frame.extend_from_slice(&id.to_le_bytes());
+		frame.extend_from_slice(payload);
+		self.tx.send(frame).map_err(|_| connection_closed())
+	}
+}
+fn connection_closed() -> io::Error {
+	io::Error::new(io::ErrorKind::ConnectionAborted, "connection closed")
+}
+
+/// Spawns a task which sends queued frames until the queue is closed or sending fails.
+fn spawn_writer(sh: SendHalf, framing: FramingOptions) -> Outbox {
+	let (tx, mut queue) = mpsc::unbounded_channel::<Vec<u8>>();
+	let mut framed = Framed::with_options(sh, framing);
+	tokio::spawn(async move {
+		while let Some(frame) = queue.recv().await {
+			if framed.send_frame(&frame).await.is_err() {
+				break;
+			}
+		}
+	});
+	Outbox {
+		tx,
+		max_frame_size: framing.max_frame_size,
+	}
+}
+
+/// Options for [`Caller`].
+#[non_exhaustive]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct CallerOptions {
+	/// The framing options, which must be the same as the ones used by the other end. Requests and
+	/// responses are limited to the maximum frame size minus 9 bytes.
+	///
+	/// The default value is [`FramingOptions::new()`].
+	pub framing: FramingOptions,
+	/// The timeout applied to calls made with [`.call()`](Caller::call), or `None` if they should
+	/// wait for the response indefinitely.
+	///
+	/// The default value is `None`.
+	pub timeout: Option<Duration>,
+}
+impl CallerOptions {
+	/// Starts with the default parameters. Identical to `Default::default()`.
+	pub const fn new() -> Self {
+		Self {
+			framing: FramingOptions::new(),
+			timeout: None,
+		}
+	}
+	/// Sets the framing options.
+	///
+	/// See the [associated field](#structfield.framing) for more.
+	#[must_use = "this is not an in-place operation"]
+	#[inline]
+	pub fn framing(mut self, framing: FramingOptions) -> Self {
+		self.framing = framing;
+		self
+	}
+	/// Sets the default timeout of calls.
+	///
+	/// See the [associated field](#structfield.timeout) for more.
+	#[must_use = "this is not an in-place operation"]
+	#[inline]
+	pub fn timeout(mut self, timeout: Option<Duration>) -> Self {
+		self.timeout = timeout;
+		self
+	}
+}
+impl Default for CallerOptions {
+	#[inline]
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+type CallResult = io::Result<Vec<u8>>;
+
+#[derive(Default)]
+struct Pending {
+	calls: HashMap<u64, oneshot::Sender<CallResult>>,
+	/// Set once the connection is closed, after which no more calls are accepted.
+	closed: bool,
+}
+
+struct CallerShared {
+	outbox: Outbox,
+	pending: Arc<Mutex<Pending>>,
+	next_id: AtomicU64,
+	timeout: Option<Duration>,
+	reader: AbortHandle,
+}
+impl Drop for CallerShared {
+	fn drop(&mut self) {
+		self.reader.abort();
+	}
+}
+
+/// Client which makes concurrent calls over a Tokio local socket stream.
+///
+/// See the [module-level documentation](self) for more.
+///
+/// Responses are routed to calls by a background task. The caller can be cloned cheaply to make
+/// calls from multiple tasks; the connection is closed once all clones are dropped, and calls
+/// which are still waiting for a response at the time the connection is closed by the other end
+/// fail with [`ConnectionAborted`](io::ErrorKind::ConnectionAborted).
+#[derive(Clone)]
+pub struct Caller(Arc<CallerShared>);
+impl Caller {
+	/// Creates a caller with the default [options](CallerOptions).
+	///
+	/// # Panics
+	/// Must be called from within a Tokio runtime, since tasks for sending and receiving frames are
+	/// spawned on it.
+	#[inline]
+	pub fn new(stream: Stream) -> Self {
+		Self::with_options(stream, CallerOptions::new())
+	}
+	/// Creates a caller with the given options.
+	///
+	/// # Panics
+	/// Must be called from within a Tokio runtime, since tasks for sending and receiving frames are
+	/// spawned on it.
+	pub fn with_options(stream: Stream, options: CallerOptions) -> Self {
+		let (rh, sh) = stream.split();
+		let outbox = spawn_writer(sh, options.framing);
+		let pending = Arc::new(Mutex::new(Pending::default()));
+		let reader = tokio::spawn(caller_read_loop(
+			Framed::with_options(rh, options.framing),
+			Arc::clone(&pending),
+		));
+		Self(Arc::new(CallerShared {
+			outbox,
+			pending,
+			next_id: AtomicU64::new(0),
+			timeout: options.timeout,
+			reader: reader.abort_handle(),
+		}))
+	}
+
+	/// Sends a request and waits for its response, for no longer than the
+	/// [default timeout](CallerOptions::timeout) if there is one.
+	///
+	/// # Errors
+	/// In addition to errors from the connection, fails with:
+	/// -	[`TimedOut`](io::ErrorKind::TimedOut) if the timeout elapses before the response
+	///   	arrives;
+	/// -	[`InvalidInput`](io::ErrorKind::InvalidInput) if the request exceeds the maximum frame
+	///   	size;
+	/// -	[`Other`](io::ErrorKind::Other) if the other end responds with an error, such as when
+	///   	the handler panics.
+	pub async fn call(&self, request: &[u8]) -> CallResult {
+		match self.0.timeout {
+			Some(timeout) => self.call_timeout(request, timeout).await,
+			None => self.call_inner(request).await,
+		}
+	}
+	/// Sends a request and waits for its response for no longer than the given timeout, ignoring
+	/// the default one.
+	///
+	/// See [`.call()`](Self::call) for the possible errors.
+	pub async fn call_timeout(&self, request: &[u8], timeout: Duration) -> CallResult {
+		tokio::time::timeout(timeout, self.call_inner(request))
+			.await
+			.map_err(|_| io::Error::new(io::ErrorKind::TimedOut, "call timed out"))?
+	}
+	async fn call_inner(&self, request: &[u8]) -> CallResult {
+		let id = self.0.next_id.fetch_add(1, Relaxed);
+		let response = {
+			let mut pending = self.0.pending.lock().map_err(poison_error)?;
+			if pending.closed {
+				return Err(connection_closed());
+			}
+			let (tx, rx) = oneshot::channel();
+			pending.calls.insert(id, tx);
+			rx
+		};
+		let _guard = CancelOnDrop { caller: self, id };
+		self.0.outbox.send(REQUEST, id, request)?;
+		response.await.map_err(|_| connection_closed())?
+	}
+}
+impl Debug for Caller {
+	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+		f.debug_struct("Caller")
+			.field("timeout", &self.0.timeout)
+			.finish_non_exhaustive()
+	}
+}
+
+/// Routes received responses to pending calls.
+async fn caller_read_loop(mut framed: Framed<RecvHalf>, pending: Arc<Mutex<Pending>>) {
+	while let Ok(Some(frame)) = framed.recv_frame().await {
+		let Some((kind, id, payload)) = decode(frame) else {
+			break;
+		};
+		let result = match kind {
+			RESPONSE => Ok(payload.to_vec()),
+			ERROR => Err(io::Error::other(String::from_utf8_lossy(payload))),
+			_ => break,
+		};
+		let call = pending.lock().expect(LOCK_POISON).calls.remove(&id);
+		if let Some(call) = call {
+			let _ = call.send(result);
+		}
+	}
+	let mut pending = pending.lock().expect(LOCK_POISON);
+	pending.closed = true;
+	pending.calls.clear();
+}
+
+/// Cancels the call with the given ID if it is still pending.
+struct CancelOnDrop<'a> {
+	caller: &'a Caller,
+	id: u64,
+}
+impl Drop for CancelOnDrop<'_> {
+	fn drop(&mut self) {
+		let call = self
+			.caller
+			.0
+			.pending
+			.lock()
+			.expect(LOCK_POISON)
+			.calls
+			.remove(&self.id);
+		if call.is_some() {
+			let _ = self.caller.0.outbox.send(CANCEL, self.id, &[]);
+		}
+	}
+}
+
+/// Serves calls received over the given stream with the default [framing
+/// options](FramingOptions) until the other end closes or resets the connection.
+///
+/// See [`serve_with_options()`] for more.
+#[inline]
+pub async fn serve<H, Fut>(stream: Stream, handler: H) -> io::Result<()>
+where
+	H: Fn(Vec<u8>) -> Fut + Send + Sync + 'static,
+	Fut: Future<Output = Vec<u8>> + Send + 'static,
+{
+	serve_with_options(stream, FramingOptions::new(), handler).await
+}
+
+/// Serves calls received over the given stream until the other end closes or resets the
+/// connection.
+///
+/// Each request is handled by calling `handler` and running the returned future in its own task,
+/// with no limit on the number of requests being handled at once. Responses are sent as soon as
+/// they are ready. Cancelled requests have their task aborted and receive no response. Requests
+/// whose handler panics or returns a response exceeding the maximum frame size receive an error
+/// response. Requests that are still being handled once the connection is closed are cancelled.
+///
+/// # Errors
+/// Fails if receiving from the stream fails or if a received frame is malformed, in which case the
+/// error kind is [`InvalidData`](io::ErrorKind::InvalidData).
+pub async fn serve_with_options<H, Fut>(
+	stream: Stream,
+	framing: FramingOptions,
+	handler: H,
+) -> io::Result<()>
+where
+	H: Fn(Vec<u8>) -> Fut + Send + Sync + 'static,
+	Fut: Future<Output = Vec<u8>> + Send + 'static,
+{
+	let (rh, sh) = stream.split();
+	let outbox = spawn_writer(sh, framing);
+	let in_flight = Arc::new(Mutex::new(HashMap::<u64, AbortHandle>::new()));
+	let mut framed = Framed::with_options(rh, framing);
+	let rslt = loop {
+		let frame = match framed.recv_frame().await {
+			Ok(Some(frame)) => frame,
+			Ok(None) => break Ok(()),
+			// Callers that disconnect with responses left unread reset the connection.
+			Err(e) if e.kind() == io::ErrorKind::ConnectionReset => break Ok(()),
+			Err(e) => break Err(e),
+		};
+		let (id, request) = match decode(frame) {
+			Some((REQUEST, id, request)) => (id, request.to_vec()),
+			Some((CANCEL, id, _)) => {
+				if let Some(task) = in_flight.lock().map_err(poison_error)?.get(&id) {
+					task.abort();
+				}
+				continue;
+			}
+			_ => {
+				break Err(io::Error::new(
+					io::ErrorKind::InvalidData,
+					"malformed call frame",
+				))
+			}
+		};
+		let task = tokio::spawn(handler(request));
+		in_flight
+			.lock()
+			.map_err(poison_error)?
+			.insert(id, task.abort_handle());
+		let (in_flight, outbox) = (Arc::clone(&in_flight), outbox.clone());
+		tokio::spawn(async move {
+			let rslt = task.await;
+			in_flight.lock().expect(LOCK_POISON).remove(&id);
+			let _ = match rslt {
+				Ok(response) => outbox
+					.send(RESPONSE, id, &response)
+					.or_else(|_| outbox.send(ERROR, id, b"response exceeds maximum frame size")),
+				Err(e) if e.is_cancelled() => Ok(()),
+				Err(..) => outbox.send(ERROR, id, b"request handler panicked"),
+			};
+		});
+	};
+	for task in in_flight.lock().map_err(poison_error)?.values() {
+		task.abort();
+	}
+	rslt
+}
@@ -0,0 +1,410 @@
+//! A server runner for Tokio local socket listeners with graceful shutdown.
+//!
+//! [`serve()`] accepts connections from a [`Listener`] and handles each of them in its own task
+//! until the shutdown future it's given completes. It then stops accepting, gives the connections
+//! that are still being handled up to the [drain timeout](ServerOptions::drain_timeout) to finish
+//! and aborts the ones that don't.
+//!
+//! Handlers receive a [`Connection`], which fails reads and writes that wait for longer than the
+//! configured [handshake](ServerOptions::handshake_timeout) and [idle](ServerOptions::idle_timeout)
+//! timeouts, so that clients which connect and then never send anything don't hold on to a task
+//! and a connection slot forever.
+//!
+//! # Examples
+//! ```no_run
+//! # #[tokio::main]
+//! # async fn main() -> std::io::Result<()> {
+//! use interprocess::local_socket::{
+//! 	tokio::{
+//! 		server::{serve, Connection, ServerOptions},
+//! 		Listener,
+//! 	},
+//! 	ToNsName,
+//! };
+//! use std::time::Duration;
+//! use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+//!
+//! let listener = Listener::bind("example-server.sock".to_ns_name()?)?;
+//! let handler = |conn: Connection| async move {
+//! 	let mut conn = BufReader::new(conn);
+//! 	let mut line = String::new();
+//! 	if conn.read_line(&mut line).await.is_ok() {
+//! 		let _ = conn.get_mut().write_all(line.as_bytes()).await;
+//! 	}
+//! };
+//! let opts = ServerOptions::new()
+//! 	.handshake_timeout(Some(Duration::from_secs(5)))
+//! 	.drain_timeout(Duration::from_secs(10));
+//!
+//! // The server is shut down by sending on, or dropping, `stop`.
+//! let (stop, stopped) = tokio::sync::oneshot::channel::<()>();
+//! let shutdown = async move { let _ = stopped.await; };
+//! let server = tokio::spawn(serve(listener, handler, shutdown, opts));
+//!
+//! // ...
+//! drop(stop);
+//! server.await??;
+//! # Ok(()) }
+//! ```
+
+use super::{Listener, Stream};
+use crate::{
+	local_socket::server::{is_transient, next_backoff},
+	LOCK_POISON,
+};
+use std::{
+	collections::HashMap,
+	fmt::{self, Debug, Formatter},
+	future::{poll_fn, Future},
+	io,
+	num::NonZeroUsize,
+	pin::{pin, Pin},
+	sync::{Arc, Mutex},
+	task::{Context, Poll},
+	time::Duration,
+};
+use tokio::{
+	io::{AsyncRead, AsyncWrite, ReadBuf},
+	sync::{Notify, OwnedSemaphorePermit, Semaphore},
+	task::AbortHandle,
+	time::{self, Instant, Sleep},
+};
+
+/// Configuration of [`serve()`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub struct ServerOptions {
+	/// The maximum number of connections handled at once, or `None` for no limit. No more
+	/// connections are accepted while this many are being handled.
+	///
+	/// The default value is `None`.
+	pub max_connections: Option<NonZeroUsize>,
+	/// How long connections that are still being handled when the server is shut down are given to
+	/// finish before their tasks are aborted.
+	///
+	/// The default value is 30 seconds.
+	pub drain_timeout: Duration,
+	/// How long a [`Connection`] waits for the client to send its first byte, or `None` to use the
+	/// idle timeout for that as well.
+	///
+	/// The default value is `None`.
+	pub handshake_timeout: Option<Duration>,
+	/// How long a read from or write to a [`Connection`] may wait for the client after the last
+	/// completed one, or `None` to wait indefinitely.
+	///
+	/// The default value is `None`.
+	pub idle_timeout: Option<Duration>,
+}
+impl ServerOptions {
+	/// Creates an options table with default values.
+	#[inline]
+	pub const fn new() -> Self {
+		Self {
+			max_connections: None,
+			drain_timeout: Duration::from_secs(30),
+			handshake_timeout: None,
+			idle_timeout: None,
+		}
+	}
+	/// Sets the maximum number of connections handled at once. Zero is treated as `None`.
+	///
+	/// See the [associated field](#structfield.max_connections) for more.
+	#[must_use = "this is not an in-place operation"]
+	#[inline]
+	pub fn max_connections(mut self, max_connections: usize) -> Self {
+		self.max_connections = NonZeroUsize::new(max_connections);
+		self
+	}
+	/// Sets the drain timeout.
+	///
+	/// See the [associated field](#structfield.drain_timeout) for more.
+	#[must_use = "this is not an in-place operation"]
+	#[inline]
+	pub fn drain_timeout(mut self, drain_timeout: Duration) -> Self {
+		self.drain_timeout = drain_timeout;
+		self
+	}
+	/// Sets the handshake timeout.
+	///
+	/// See the [associated field](#structfield.handshake_timeout) for more.
+	#[must_use = "this is not an in-place operation"]
+	#[inline]
+	pub fn handshake_timeout(mut self, handshake_timeout: Option<Duration>) -> Self {
+		self.handshake_timeout = handshake_timeout;
+		self
+	}
+	/// Sets the idle timeout.
+	///
+	/// See the [associated field](#structfield.idle_timeout) for more.
+	#[must_use = "this is not an in-place operation"]
+	#[inline]
+	pub fn idle_timeout(mut self, idle_timeout: Option<Duration>) -> Self {
+		self.idle_timeout = idle_timeout;
+		self
+	}
+}
+impl Default for ServerOptions {
+	#[inline]
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+/// A connection accepted by [`serve()`], which enforces the handshake and idle timeouts.
+///
+/// A read or write which waits for the client past the current deadline fails with an error of
+/// kind [`TimedOut`](io::ErrorKind::TimedOut). The deadline starts out as the handshake timeout
+/// after the connection was accepted and stays put until the first byte is read from the client,
+/// even if the handler writes to it in the meantime, such as to greet it. From then on, the
+/// deadline is moved to the idle timeout after the last completed read or write. The timeouts can be escaped from by taking the stream out with
+/// [`.into_inner()`](Self::into_inner), such as once the client has identified itself.
+pub struct Connection {
+	stream: Stream,
+	idle_timeout: Option<Duration>,
+	deadline: Option<Pin<Box<Sleep>>>,
+	/// Whether the handshake deadline is in effect, which only a read of at least one byte ends.
+	handshake: bool,
+}
+impl Connection {
+	fn new(stream: Stream, opts: &ServerOptions) -> Self {
+		let first = opts.handshake_timeout.or(opts.idle_timeout);
+		Self {
+			stream,
+			idle_timeout: opts.idle_timeout,
+			deadline: first.map(|t| Box::pin(time::sleep(t))),
+			handshake: opts.handshake_timeout.is_some(),
+		}
+	}
+	/// Returns a reference to the underlying stream.
+	#[inline]
+	pub fn get_ref(&self) -> &Stream {
+		&self.stream
+	}
+	/// Returns the underlying stream, which is no longer subject to the timeouts.
+	#[inline]
+	pub fn into_inner(self) -> Stream {
+		self.stream
+	}
+
+	/// Moves the deadline forward after a completed operation, or fails a pending one whose
+	/// deadline has passed. `received` is whether the operation read at least one byte.
+	fn check<T>(
+		&mut self,
+		cx: &mut Context<'_>,
+		poll: Poll<io::Result<T>>,
+		received: bool,
+	) -> Poll<io::Result<T>> {
+		if poll.is_ready() && self.handshake && !received {
+			// The client hasn't sent anything yet.
+			return poll;
+		}
+		if poll.is_ready() {
+			self.handshake = false;
+			// A timeout too long to be represented is the same as none at all.
+			match self
+				.idle_timeout
+				.and_then(|t| Instant::now().checked_add(t))
+			{
+				Some(at) => match &mut self.deadline {
+					Some(deadline) => deadline.as_mut().reset(at),
+					None => self.deadline = Some(Box::pin(time::sleep_until(at))),
+				},
+				None => self.deadline = None,
+			}
+			return poll;
+		}
+		let expired = match &mut self.deadline {
+			Some(deadline) => deadline.as_mut().poll(cx).is_ready(),
+			None => false,
+		};
+		if expired {
+			Poll::Ready(Err(io::Error::new(
+				io::ErrorKind::TimedOut,
+				"client took too long to respond",
+			)))
+		} else {
+			Poll::Pending
+		}
+	}
+}
+impl AsyncRead for Connection {
+	fn poll_read(
+		mut self: Pin<&mut Self>,
+		cx: &mut Context<'_>,
+		buf: &mut ReadBuf<'_>,
+	) -> Poll<io::Result<()>> {
+		let filled = buf.filled().len();
+		let poll = Pin::new(&mut self.stream).poll_read(cx, buf);
+		let received = matches!(poll, Poll::Ready(Ok(()))) && buf.filled().len() > filled;
+		self.check(cx, poll, received)
+	}
+}
+impl AsyncWrite for Connection {
+	fn poll_write(
+		mut self: Pin<&mut Self>,
+		cx: &mut Context<'_>,
+		buf: &[u8],
+	) -> Poll<io::Result<usize>> {
+		let poll = Pin::new(&mut self.stream).poll_write(cx, buf);
+		self.check(cx, poll, false)
+	}
+	fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+		Pin::new(&mut self.stream).poll_flush(cx)
+	}
+	fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+		Pin::new(&mut self.stream).poll_shutdown(cx)
+	}
+}
+impl Debug for Connection {
+	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+		f.debug_struct("Connection")
+			.field("stream", &self.stream)
+			.field("idle_timeout", &self.idle_timeout)
+			.field("deadline", &self.deadline.as_ref().map(|d| d.deadline()))
+			.field("handshake", &self.handshake)
+			.finish()
+	}
+}
+
+/// The tasks of the connections which are being handled.
+#[derive(Default)]
+struct Tracker {
+	tasks: Mutex<HashMap<u64, AbortHandle>>,
+	/// Notified when the last task finishes.
+	idle: Notify,
+}
+
+/// Removes a connection task from the tracker when it finishes or is aborted, and releases its
+/// connection slot.
+struct TaskGuard {
+	tracker: Arc<Tracker>,
+	id: u64,
+	_permit: OwnedSemaphorePermit,
+}
+impl Drop for TaskGuard {
+	fn drop(&mut self) {
+		let mut tasks = self.tracker.tasks.lock().expect(LOCK_POISON);
+		tasks.remove(&self.id);
+		if tasks.is_empty() {
+			self.tracker.idle.notify_waiters();
+		}
+	}
+}
+
+/// Polls `fut` until it completes, returning `None` instead if `shutdown` completes first.
+async fn unless_shutdown<T>(
+	shutdown: &mut Pin<&mut impl Future<Output = ()>>,
+	fut: impl Future<Output = T>,
+) -> Option<T> {
+	let mut fut = pin!(fut);
+	poll_fn(|cx| {
+		if shutdown.as_mut().poll(cx).is_ready() {
+			return Poll::Ready(None);
+		}
+		fut.as_mut().poll(cx).map(Some)
+	})
+	.await
+}
+
+/// Accepts connections from `listener` and handles each of them by spawning the future returned by
+/// `handler` as a task, until `shutdown` completes or accepting a connection fails.
+///
+/// Once that happens, the connections that are still being handled are given up to the [drain
+/// timeout](ServerOptions::drain_timeout) to finish, after which their tasks are aborted. The
+/// returned future only completes once all of the tasks are gone, aborted ones included, so that
+/// nothing held by a handler outlives it. Any future that completes on shutdown can be used, such
+/// as a signal handler, the receiving end of a channel or a cancellation token.
+///
+/// Failures to accept a connection which are expected to clear up on their own, such as running
+/// out of file descriptors or the client hanging up before it is accepted, are retried after a
+/// wait which grows from 10 milliseconds up to 1 second as long as they keep happening. Any other
+/// failure stops the server.
+///
+/// A panicking handler only ends its own connection.
+///
+/// # Errors
+/// Returns the error which made accepting a connection fail, if that is why the server stopped.
+pub async fn serve<H, Fut>(
+	listener: Listener,
+	handler: H,
+	shutdown: impl Future<Output = ()>,
+	options: ServerOptions,
+) -> io::Result<()>
+where
+	H: Fn(Connection) -> Fut,
+	Fut: Future<Output = ()> + Send + 'static,
+{
+	let mut shutdown = pin!(shutdown);
+	let slots = options
+		.max_connections
+		.map_or(Semaphore::MAX_PERMITS, NonZeroUsize::get);
+	let slots = Arc::new(Semaphore::new(slots));
+	let tracker = Arc::new(Tracker::default());
+	let mut rslt = Ok(());
+	let mut backoff = Duration::ZERO;
+	for id in 0_u64.. {
+		let Some(permit) = unless_shutdown(&mut shutdown, Arc::clone(&slots).acquire_owned()).await
+		else {
+			break;
+		};
+		// The semaphore is never closed.
+		let Ok(permit) = permit else { break };
+		let conn = loop {
+			match unless_shutdown(&mut shutdown, listener.accept()).await {
+				Some(Ok(conn)) => break Some(conn),
+				Some(Err(e)) if is_transient(&e) => {
+					let wait = time::sleep(next_backoff(&mut backoff));
+					if unless_shutdown(&mut shutdown, wait).await.is_none() {
+						break None;
+					}
+				}
+				Some(Err(e)) => {
+					rslt = Err(e);
+					break None;
+				}
+				None => break None,
+			}
+		};
+		let Some(conn) = conn else { break };
+		backoff = Duration::ZERO;
+		let conn = Connection::new(conn, &options);
+		let guard = TaskGuard {
+			tracker: Arc::clone(&tracker),
+			id,
+			_permit: permit,
+		};
+		let fut = handler(conn);
+		// Held across the spawn so that the task cannot remove itself before it's inserted.
+		let mut tasks = tracker.tasks.lock().expect(LOCK_POISON);
+		let task = tokio::spawn(async move {
+			let _guard = guard;
+			fut.await;
+		});
+		tasks.insert(id, task.abort_handle());
+	}
+	drop(listener);
+	drain(&tracker, options.drain_timeout).await;
+	rslt
+}
+
+/// Waits for the tracked tasks to finish for up to `timeout`, then aborts the rest and waits for
+/// them to be dropped.
+async fn drain(tracker: &Tracker, timeout: Duration) {
+	if time::timeout(timeout, finished(tracker)).await.is_err() {
+		for task in tracker.tasks.lock().expect(LOCK_POISON).values() {
+			task.abort();
+		}
+		finished(tracker).await;
+	}
+}
+
+/// Waits until there are no tracked tasks left.
+async fn finished(tracker: &Tracker) {
+	loop {
+		let idle = pin!(tracker.idle.notified());
+		if tracker.tasks.lock().expect(LOCK_POISON).is_empty() {
+			return;
+		}
+		idle.await;
+	}
+}
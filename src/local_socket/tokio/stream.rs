@@ -66,6 +66,14 @@ impl Stream {
 	pub async fn connect(name: Name<'_>) -> io::Result<Self> {
 		StreamImpl::connect(name).await.map(Self::from)
 	}
+	/// Creates a pair of streams connected to each other without binding a listener to a name.
+	///
+	/// See the [synchronous version](crate::local_socket::traits::Stream::pair) for more.
+	#[inline]
+	pub async fn pair() -> io::Result<(Self, Self)> {
+		let (s1, s2) = StreamImpl::pair().await?;
+		Ok((Self(s1), Self(s2)))
+	}
 
 	/// Splits a stream into a receive half and a send half, which can be used to receive data from
 	/// and send data to the stream concurrently from independently spawned tasks, entailing a
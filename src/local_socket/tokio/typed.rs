@@ -0,0 +1,233 @@
+//! Tokio-based local socket streams which send and receive serializable values.
+//!
+//! This is the Tokio counterpart of the [sync typed stream module](crate::local_socket::typed),
+//! with which it shares the [serialization formats](Format) and the wire format.
+//!
+//! # Examples
+//! ```no_run
+//! # #[tokio::main]
+//! # async fn main() -> std::io::Result<()> {
+//! use interprocess::local_socket::tokio::{typed::TypedStream, Stream};
+//!
+//! let (a, b) = Stream::pair().await?;
+//! let mut client = TypedStream::<String, usize>::new(a);
+//! let mut server = TypedStream::<usize, String>::new(b);
+//! client.send(&"Hello world!".to_owned()).await?;
+//! if let Some(text) = server.recv().await? {
+//! 	server.send(&text.len()).await?;
+//! }
+//! assert_eq!(client.recv().await?, Some(12));
+//! # Ok(()) }
+//! ```
+
+use super::{
+	framing::{Framed, FramingOptions},
+	RecvHalf, SendHalf, Stream,
+};
+use crate::error::{ReuniteError, ReuniteResult};
+#[cfg(feature = "json")]
+pub use crate::local_socket::typed::Json;
+#[cfg(feature = "postcard")]
+pub use crate::local_socket::typed::Postcard;
+pub use crate::local_socket::typed::{Bincode, Format};
+use serde::{de::DeserializeOwned, Serialize};
+use std::{
+	fmt::{self, Debug, Formatter},
+	io,
+	marker::PhantomData,
+};
+use tokio::io::{AsyncRead, AsyncWrite};
+
+async fn send<T: Serialize + ?Sized>(
+	framed: &mut Framed<impl AsyncWrite + Unpin>,
+	format: &impl Format,
+	sbuf: &mut Vec<u8>,
+	value: &T,
+) -> io::Result<()> {
+	sbuf.clear();
+	format.serialize(value, sbuf)?;
+	framed.send_frame(sbuf).await
+}
+async fn recv<T: DeserializeOwned>(
+	framed: &mut Framed<impl AsyncRead + Unpin>,
+	format: &impl Format,
+) -> io::Result<Option<T>> {
+	match framed.recv_frame().await? {
+		Some(frame) => format.deserialize(frame).map(Some),
+		None => Ok(None),
+	}
+}
+
+/// Tokio-based local socket stream which sends values of type `Tx` and receives values of type
+/// `Rx`, serialized in format `F`.
+///
+/// See the [sync version](crate::local_socket::typed::TypedStream) for more.
+pub struct TypedStream<Tx, Rx, F = Bincode> {
+	framed: Framed<Stream>,
+	format: F,
+	sbuf: Vec<u8>,
+	_phantom: PhantomData<fn(Tx) -> Rx>,
+}
+impl<Tx, Rx, F: Format + Default> TypedStream<Tx, Rx, F> {
+	/// Wraps the given stream with the default format and [framing options](FramingOptions).
+	#[inline]
+	pub fn new(stream: Stream) -> Self {
+		Self::with_format(stream, F::default(), FramingOptions::new())
+	}
+}
+impl<Tx, Rx, F: Format> TypedStream<Tx, Rx, F> {
+	/// Wraps the given stream with the given format and framing options.
+	#[inline]
+	pub fn with_format(stream: Stream, format: F, options: FramingOptions) -> Self {
+		Self::from_framed(Framed::with_options(stream, options), format)
+	}
+	/// Wraps an already framed stream, keeping any data it has buffered.
+	#[inline]
+	pub fn from_framed(framed: Framed<Stream>, format: F) -> Self {
+		Self {
+			framed,
+			format,
+			sbuf: Vec::new(),
+			_phantom: PhantomData,
+		}
+	}
+	/// Returns the serialization format used by the stream.
+	#[inline]
+	pub fn format(&self) -> &F {
+		&self.format
+	}
+	/// Borrows the wrapped stream.
+	#[inline]
+	pub fn get_ref(&self) -> &Stream {
+		self.framed.get_ref()
+	}
+	/// Unwraps the framed stream, keeping any data it has buffered.
+	#[inline]
+	pub fn into_framed(self) -> Framed<Stream> {
+		self.framed
+	}
+	/// Unwraps the stream, discarding any buffered received data.
+	#[inline]
+	pub fn into_inner(self) -> Stream {
+		self.framed.into_inner()
+	}
+	/// Splits the stream into a receive half and a send half.
+	pub fn split(self) -> (TypedRecvHalf<Rx, F>, TypedSendHalf<Tx, F>)
+	where
+		F: Clone,
+	{
+		let (rh, sh) = self.framed.split();
+		let rh = TypedRecvHalf {
+			framed: rh,
+			format: self.format.clone(),
+			_phantom: PhantomData,
+		};
+		let sh = TypedSendHalf {
+			framed: sh,
+			format: self.format,
+			sbuf: self.sbuf,
+			_phantom: PhantomData,
+		};
+		(rh, sh)
+	}
+	/// Attempts to reunite a receive half with a send half to yield the original stream back,
+	/// returning both halves as an error if they belong to different streams.
+	#[allow(clippy::result_large_err)]
+	pub fn reunite(
+		rh: TypedRecvHalf<Rx, F>,
+		sh: TypedSendHalf<Tx, F>,
+	) -> ReuniteResult<Self, TypedRecvHalf<Rx, F>, TypedSendHalf<Tx, F>> {
+		let TypedSendHalf {
+			framed: shf,
+			sbuf,
+			format: sformat,
+			..
+		} = sh;
+		match Framed::reunite(rh.framed, shf) {
+			Ok(framed) => Ok(Self {
+				framed,
+				format: rh.format,
+				sbuf,
+				_phantom: PhantomData,
+			}),
+			Err(ReuniteError { rh: rhf, sh: shf }) => Err(ReuniteError {
+				rh: TypedRecvHalf { framed: rhf, ..rh },
+				sh: TypedSendHalf {
+					framed: shf,
+					format: sformat,
+					sbuf,
+					_phantom: PhantomData,
+				},
+			}),
+		}
+	}
+}
+impl<Tx: Serialize, Rx, F: Format> TypedStream<Tx, Rx, F> {
+	/// Serializes and sends a value.
+	#[inline]
+	pub async fn send(&mut self, value: &Tx) -> io::Result<()> {
+		send(&mut self.framed, &self.format, &mut self.sbuf, value).await
+	}
+}
+impl<Tx, Rx: DeserializeOwned, F: Format> TypedStream<Tx, Rx, F> {
+	/// Receives and deserializes a value, returning `None` if the other end has closed the
+	/// connection.
+	#[inline]
+	pub async fn recv(&mut self) -> io::Result<Option<Rx>> {
+		recv(&mut self.framed, &self.format).await
+	}
+}
+impl<Tx, Rx, F: Debug> Debug for TypedStream<Tx, Rx, F> {
+	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+		f.debug_struct("TypedStream")
+			.field("framed", &self.framed)
+			.field("format", &self.format)
+			.finish_non_exhaustive()
+	}
+}
+
+/// Receive half of a [`TypedStream`], obtained by splitting it.
+pub struct TypedRecvHalf<Rx, F = Bincode> {
+	framed: Framed<RecvHalf>,
+	format: F,
+	_phantom: PhantomData<fn() -> Rx>,
+}
+impl<Rx: DeserializeOwned, F: Format> TypedRecvHalf<Rx, F> {
+	/// Receives and deserializes a value, returning `None` if the other end has closed the
+	/// connection.
+	#[inline]
+	pub async fn recv(&mut self) -> io::Result<Option<Rx>> {
+		recv(&mut self.framed, &self.format).await
+	}
+}
+impl<Rx, F: Debug> Debug for TypedRecvHalf<Rx, F> {
+	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+		f.debug_struct("TypedRecvHalf")
+			.field("framed", &self.framed)
+			.field("format", &self.format)
+			.finish_non_exhaustive()
+	}
+}
+
+/// Send half of a [`TypedStream`], obtained by splitting it.
+pub struct TypedSendHalf<Tx, F = Bincode> {
+	framed: Framed<SendHalf>,
+	format: F,
+	sbuf: Vec<u8>,
+	_phantom: PhantomData<fn(Tx)>,
+}
+impl<Tx: Serialize, F: Format> TypedSendHalf<Tx, F> {
+	/// Serializes and sends a value.
+	#[inline]
+	pub async fn send(&mut self, value: &Tx) -> io::Result<()> {
+		send(&mut self.framed, &self.format, &mut self.sbuf, value).await
+	}
+}
+impl<Tx, F: Debug> Debug for TypedSendHalf<Tx, F> {
+	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+		f.debug_struct("TypedSendHalf")
+			.field("framed", &self.framed)
+			.field("format", &self.format)
+			.finish_non_exhaustive()
+	}
+}
@@ -0,0 +1,311 @@
+//! Local socket streams which send and receive serializable values.
+//!
+//! [`TypedStream`] wraps a [`Stream`] in a [length-prefixed framing layer](super::framing) and
+//! serializes each sent value into a frame, deserializing received frames back into values on the
+//! other end. The format in which values are serialized is chosen with the [`Format`] trait,
+//! which has the following implementations out of the box:
+//! -	[`Bincode`], the default one, which is always available with the `serde` feature;
+//! -	[`Json`], with the `json` feature;
+//! -	[`Postcard`], with the `postcard` feature.
+//!
+//! The type parameters `Tx` and `Rx` are the types of sent and received values respectively, which
+//! means that the two ends of a connection use the same types in opposite order. The [maximum
+//! frame size](super::framing::FramingOptions::max_frame_size) limits the size of serialized
+//! values.
+//!
+//! A Tokio version is available in the [`tokio::typed`](super::tokio::typed) module when the
+//! `tokio` feature is enabled.
+//!
+//! # Examples
+//! ```no_run
+//! use interprocess::local_socket::{prelude::*, typed::TypedStream, Stream};
+//!
+//! let (a, b) = Stream::pair()?;
+//! let mut client = TypedStream::<String, usize>::new(a);
+//! let mut server = TypedStream::<usize, String>::new(b);
+//! client.send(&"Hello world!".to_owned())?;
+//! if let Some(text) = server.recv()? {
+//! 	server.send(&text.len())?;
+//! }
+//! assert_eq!(client.recv()?, Some(12));
+//! # std::io::Result::<()>::Ok(())
+//! ```
+
+use super::{
+	framing::{Framed, FramingOptions},
+	RecvHalf, SendHalf, Stream,
+};
+use crate::error::{ReuniteError, ReuniteResult};
+use bincode::Options as _;
+use serde::{de::DeserializeOwned, Serialize};
+use std::{
+	fmt::{self, Debug, Formatter},
+	io::{self, prelude::*},
+	marker::PhantomData,
+};
+
+/// A serialization format for [typed streams](TypedStream).
+///
+/// Both ends of a connection must use the same format.
+pub trait Format {
+	/// Serializes `value`, appending the result to `buf`.
+	fn serialize<T: Serialize + ?Sized>(&self, value: &T, buf: &mut Vec<u8>) -> io::Result<()>;
+	/// Deserializes a value from the entirety of `buf`.
+	fn deserialize<T: DeserializeOwned>(&self, buf: &[u8]) -> io::Result<T>;
+}
+
+/// The [bincode](https://docs.rs/bincode/1) serialization format with its default configuration.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Bincode;
+impl Format for Bincode {
+	fn serialize<T: Serialize + ?Sized>(&self, value: &T, buf: &mut Vec<u8>) -> io::Result<()> {
+		bincode::serialize_into(buf, value).map_err(|e| bincode_to_io(*e))
+	}
+	fn deserialize<T: DeserializeOwned>(&self, buf: &[u8]) -> io::Result<T> {
+		// Same as bincode::deserialize(), but without allowing trailing bytes.
+		bincode::options()
+			.with_fixint_encoding()
+			.reject_trailing_bytes()
+			.deserialize(buf)
+			.map_err(|e| bincode_to_io(*e))
+	}
+}
+fn bincode_to_io(e: bincode::ErrorKind) -> io::Error {
+	match e {
+		bincode::ErrorKind::Io(e) => e,
+		e => io::Error::new(io::ErrorKind::InvalidData, e),
+	}
+}
+
+/// The JSON serialization format, as implemented by [`serde_json`](https://docs.rs/serde_json).
+#[cfg(feature = "json")]
+#[cfg_attr(feature = "doc_cfg", doc(cfg(feature = "json")))]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Json;
+#[cfg(feature = "json")]
+impl Format for Json {
+	fn serialize<T: Serialize + ?Sized>(&self, value: &T, buf: &mut Vec<u8>) -> io::Result<()> {
+		serde_json::to_writer(buf, value).map_err(io::Error::from)
+	}
+	fn deserialize<T: DeserializeOwned>(&self, buf: &[u8]) -> io::Result<T> {
+		serde_json::from_slice(buf).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+	}
+}
+
+/// The [postcard](https://docs.rs/postcard/1) serialization format.
+#[cfg(feature = "postcard")]
+#[cfg_attr(feature = "doc_cfg", doc(cfg(feature = "postcard")))]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Postcard;
+#[cfg(feature = "postcard")]
+impl Format for Postcard {
+	fn serialize<T: Serialize + ?Sized>(&self, value: &T, buf: &mut Vec<u8>) -> io::Result<()> {
+		*buf = postcard::to_extend(value, std::mem::take(buf))
+			.map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+		Ok(())
+	}
+	fn deserialize<T: DeserializeOwned>(&self, buf: &[u8]) -> io::Result<T> {
+		match postcard::take_from_bytes(buf) {
+			Ok((value, [])) => Ok(value),
+			Ok(..) => Err(io::Error::new(
+				io::ErrorKind::InvalidData,
+				"trailing bytes after serialized value",
+			)),
+			Err(e) => Err(io::Error::new(io::ErrorKind::InvalidData, e)),
+		}
+	}
+}
+
+fn send<T: Serialize + ?Sized>(
+	framed: &mut Framed<impl Write>,
+	format: &impl Format,
+	sbuf: &mut Vec<u8>,
+	value: &T,
+) -> io::Result<()> {
+	sbuf.clear();
+	format.serialize(value, sbuf)?;
+	framed.send_frame(sbuf)
+}
+fn recv<T: DeserializeOwned>(
+	framed: &mut Framed<impl Read>,
+	format: &impl Format,
+) -> io::Result<Option<T>> {
+	match framed.recv_frame()? {
+		Some(frame) => format.deserialize(frame).map(Some),
+		None => Ok(None),
+	}
+}
+
+/// Local socket stream which sends values of type `Tx` and receives values of type `Rx`,
+/// serialized in format `F`.
+///
+/// See the [module-level documentation](self) for more.
+pub struct TypedStream<Tx, Rx, F = Bincode> {
+	framed: Framed<Stream>,
+	format: F,
+	sbuf: Vec<u8>,
+	_phantom: PhantomData<fn(Tx) -> Rx>,
+}
+impl<Tx, Rx, F: Format + Default> TypedStream<Tx, Rx, F> {
+	/// Wraps the given stream with the default format and [framing options](FramingOptions).
+	#[inline]
+	pub fn new(stream: Stream) -> Self {
+		Self::with_format(stream, F::default(), FramingOptions::new())
+	}
+}
+impl<Tx, Rx, F: Format> TypedStream<Tx, Rx, F> {
+	/// Wraps the given stream with the given format and framing options.
+	#[inline]
+	pub fn with_format(stream: Stream, format: F, options: FramingOptions) -> Self {
+		Self::from_framed(Framed::with_options(stream, options), format)
+	}
+	/// Wraps an already framed stream, keeping any data it has buffered.
+	#[inline]
+	pub fn from_framed(framed: Framed<Stream>, format: F) -> Self {
+		Self {
+			framed,
+			format,
+			sbuf: Vec::new(),
+			_phantom: PhantomData,
+		}
+	}
+	/// Returns the serialization format used by the stream.
+	#[inline]
+	pub fn format(&self) -> &F {
+		&self.format
+	}
+	/// Borrows the wrapped stream.
+	#[inline]
+	pub fn get_ref(&self) -> &Stream {
+		self.framed.get_ref()
+	}
+	/// Unwraps the framed stream, keeping any data it has buffered.
+	#[inline]
+	pub fn into_framed(self) -> Framed<Stream> {
+		self.framed
+	}
+	/// Unwraps the stream, discarding any buffered received data.
+	#[inline]
+	pub fn into_inner(self) -> Stream {
+		self.framed.into_inner()
+	}
+	/// Splits the stream into a receive half and a send half.
+	pub fn split(self) -> (TypedRecvHalf<Rx, F>, TypedSendHalf<Tx, F>)
+	where
+		F: Clone,
+	{
+		let (rh, sh) = self.framed.split();
+		let rh = TypedRecvHalf {
+			framed: rh,
+			format: self.format.clone(),
+			_phantom: PhantomData,
+		};
+		let sh = TypedSendHalf {
+			framed: sh,
+			format: self.format,
+			sbuf: self.sbuf,
+			_phantom: PhantomData,
+		};
+		(rh, sh)
+	}
+	/// Attempts to reunite a receive half with a send half to yield the original stream back,
+	/// returning both halves as an error if they belong to different streams.
+	#[allow(clippy::result_large_err)]
+	pub fn reunite(
+		rh: TypedRecvHalf<Rx, F>,
+		sh: TypedSendHalf<Tx, F>,
+	) -> ReuniteResult<Self, TypedRecvHalf<Rx, F>, TypedSendHalf<Tx, F>> {
+		let TypedSendHalf {
+			framed: shf,
+			sbuf,
+			format: sformat,
+			..
+		} = sh;
+		match Framed::reunite(rh.framed, shf) {
+			Ok(framed) => Ok(Self {
+				framed,
+				format: rh.format,
+				sbuf,
+				_phantom: PhantomData,
+			}),
+			Err(ReuniteError { rh: rhf, sh: shf }) => Err(ReuniteError {
+				rh: TypedRecvHalf { framed: rhf, ..rh },
+				sh: TypedSendHalf {
+					framed: shf,
+					format: sformat,
+					sbuf,
+					_phantom: PhantomData,
+				},
+			}),
+		}
+	}
+}
+impl<Tx: Serialize, Rx, F: Format> TypedStream<Tx, Rx, F> {
+	/// Serializes and sends a value.
+	#[inline]
+	pub fn send(&mut self, value: &Tx) -> io::Result<()> {
+		send(&mut self.framed, &self.format, &mut self.sbuf, value)
+	}
+}
+impl<Tx, Rx: DeserializeOwned, F: Format> TypedStream<Tx, Rx, F> {
+	/// Receives and deserializes a value, returning `None` if the other end has closed the
+	/// connection.
+	#[inline]
+	pub fn recv(&mut self) -> io::Result<Option<Rx>> {
+		recv(&mut self.framed, &self.format)
+	}
+}
+impl<Tx, Rx, F: Debug> Debug for TypedStream<Tx, Rx, F> {
+	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+		f.debug_struct("TypedStream")
+			.field("framed", &self.framed)
+			.field("format", &self.format)
+			.finish_non_exhaustive()
+	}
+}
+
+/// Receive half of a [`TypedStream`], obtained by splitting it.
+pub struct TypedRecvHalf<Rx, F = Bincode> {
+	framed: Framed<RecvHalf>,
+	format: F,
+	_phantom: PhantomData<fn() -> Rx>,
+}
+impl<Rx: DeserializeOwned, F: Format> TypedRecvHalf<Rx, F> {
+	/// Receives and deserializes a value, returning `None` if the other end has closed the
+	/// connection.
+	#[inline]
+	pub fn recv(&mut self) -> io::Result<Option<Rx>> {
+		recv(&mut self.framed, &self.format)
+	}
+}
+impl<Rx, F: Debug> Debug for TypedRecvHalf<Rx, F> {
+	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+		f.debug_struct("TypedRecvHalf")
+			.field("framed", &self.framed)
+			.field("format", &self.format)
+			.finish_non_exhaustive()
+	}
+}
+
+/// Send half of a [`TypedStream`], obtained by splitting it.
+pub struct TypedSendHalf<Tx, F = Bincode> {
+	framed: Framed<SendHalf>,
+	format: F,
+	sbuf: Vec<u8>,
+	_phantom: PhantomData<fn(Tx)>,
+}
+impl<Tx: Serialize, F: Format> TypedSendHalf<Tx, F> {
+	/// Serializes and sends a value.
+	#[inline]
+	pub fn send(&mut self, value: &Tx) -> io::Result<()> {
+		send(&mut self.framed, &self.format, &mut self.sbuf, value)
+	}
+}
+impl<Tx, F: Debug> Debug for TypedSendHalf<Tx, F> {
+	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+		f.debug_struct("TypedSendHalf")
+			.field("framed", &self.framed)
+			.field("format", &self.format)
+			.finish_non_exhaustive()
+	}
+}
@@ -8,15 +8,46 @@
 //! need to be spawned by another).
 //!
 //! FIFO files are available on all supported systems.
+//!
+//! ## Inheritance
+//! The [`Inheritable`] trait hands local sockets, unnamed pipes and FIFO file ends over to child
+//! processes spawned with [`Command`](std::process::Command). Building on it, the
+//! [`child_channel`] module, available with the `serde` feature, provides typed message channels
+//! between a parent process and its children.
+//!
+//! ## Service managers
+//! The [`socket_activation`] module adopts local socket listeners passed to a service by a service
+//! manager such as systemd, and the [`sd_notify`] module sends readiness and watchdog notifications
+//! back to it.
+//!
+//! ## Listener handover
+//! The [`handover`] module passes a live listener from one generation of a server to the next, so
+//! that upgrades don't make clients fail to connect.
+//!
+//! ## Pre-fork servers
+//! The [`prefork`] module runs a server as a supervisor process and a number of worker processes
+//! it forks, all accepting connections on the same listener.
 
 pub(crate) mod imports;
 
 mod c_wrappers;
 mod fdops;
+mod inherit;
 // Exported into child modules specifically, not this file.
 use fdops::*;
 
+pub use inherit::*;
+
+pub(crate) use c_wrappers::peer_credentials;
+
+#[cfg(feature = "serde")]
+#[cfg_attr(feature = "doc_cfg", doc(cfg(feature = "serde")))]
+pub mod child_channel;
 pub mod fifo_file;
+pub mod handover;
+pub mod prefork;
+pub mod sd_notify;
+pub mod socket_activation;
 pub mod uds_local_socket;
 
 pub(crate) mod unnamed_pipe;
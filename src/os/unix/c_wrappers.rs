@@ -1,5 +1,5 @@
 use super::unixprelude::*;
-use std::{io, net::Shutdown};
+use std::{io, net::Shutdown, ptr, time::Duration};
 
 pub(super) unsafe fn fcntl_int(fd: BorrowedFd<'_>, cmd: c_int, val: c_int) -> io::Result<c_int> {
 	let val = unsafe { libc::fcntl(fd.as_raw_fd(), cmd, val) };
@@ -24,7 +24,19 @@ pub(super) fn duplicate_fd(fd: BorrowedFd<'_>) -> io::Result<OwnedFd> {
 	}
 }
 
-#[cfg(not(target_os = "linux"))]
+pub(super) fn set_nonblocking(fd: BorrowedFd<'_>, nonblocking: bool) -> io::Result<()> {
+	let old_flags = unsafe { fcntl_int(fd, libc::F_GETFL, 0)? };
+	let new_flags = if nonblocking {
+		old_flags | libc::O_NONBLOCK
+	} else {
+		old_flags & !libc::O_NONBLOCK
+	};
+	if new_flags != old_flags {
+		unsafe { fcntl_int(fd, libc::F_SETFL, new_flags)? };
+	}
+	Ok(())
+}
+
 fn get_fdflags(fd: BorrowedFd<'_>) -> io::Result<i32> {
 	let (val, success) = unsafe {
 		let ret = libc::fcntl(fd.as_raw_fd(), libc::F_GETFD, 0);
@@ -32,17 +44,144 @@ fn get_fdflags(fd: BorrowedFd<'_>) -> io::Result<i32> {
 	};
 	ok_or_errno!(success => val)
 }
-#[cfg(not(target_os = "linux"))]
 fn set_fdflags(fd: BorrowedFd<'_>, flags: i32) -> io::Result<()> {
 	let success = unsafe { libc::fcntl(fd.as_raw_fd(), libc::F_SETFD, flags) != -1 };
 	ok_or_errno!(success => ())
 }
-#[cfg(not(target_os = "linux"))]
-fn set_cloexec(fd: BorrowedFd<'_>) -> io::Result<()> {
+pub(super) fn set_cloexec(fd: BorrowedFd<'_>) -> io::Result<()> {
 	set_fdflags(fd, get_fdflags(fd)? | libc::FD_CLOEXEC)?;
 	Ok(())
 }
 
+/// Retrieves a socket option whose value is an integer.
+pub(super) fn getsockopt_int(fd: BorrowedFd<'_>, level: c_int, option: c_int) -> io::Result<c_int> {
+	let mut val: c_int = 0;
+	let mut len = std::mem::size_of::<c_int>() as libc::socklen_t;
+	// SAFETY: the pointers are to live locals, and the length is that of the value.
+	let success = unsafe {
+		libc::getsockopt(
+			fd.as_raw_fd(),
+			level,
+			option,
+			(&mut val as *mut c_int).cast(),
+			&mut len,
+		) != -1
+	};
+	ok_or_errno!(success => val)
+}
+
+/// Sends a message on a connected socket along with file descriptors, passed as `SCM_RIGHTS`
+/// ancillary data.
+pub(super) fn send_with_fds(
+	fd: BorrowedFd<'_>,
+	buf: &[u8],
+	fds: &[BorrowedFd<'_>],
+) -> io::Result<usize> {
+	let raw_fds = fds.iter().map(AsRawFd::as_raw_fd).collect::<Vec<_>>();
+	let fds_len = raw_fds
+		.len()
+		.checked_mul(std::mem::size_of::<c_int>())
+		.and_then(|len| u32::try_from(len).ok())
+		.ok_or_else(|| io::Error::from(io::ErrorKind::InvalidInput))?;
+	// SAFETY: just a size calculation.
+	let space = unsafe { libc::CMSG_SPACE(fds_len) } as usize;
+	// Made of `cmsghdr`s so as to be aligned for them.
+	let cmsg_count = space.div_ceil(std::mem::size_of::<libc::cmsghdr>());
+	// SAFETY: `cmsghdr` is plain old data.
+	let mut cmsg_buf = vec![unsafe { std::mem::zeroed::<libc::cmsghdr>() }; cmsg_count];
+
+	let mut iov = libc::iovec {
+		iov_base: buf.as_ptr().cast_mut().cast(),
+		iov_len: buf.len(),
+	};
+	// SAFETY: `msghdr` is plain old data, and null pointers with zero lengths are valid in it.
+	let mut msg = unsafe { std::mem::zeroed::<libc::msghdr>() };
+	msg.msg_iov = &mut iov;
+	msg.msg_iovlen = 1;
+	if !raw_fds.is_empty() {
+		msg.msg_control = cmsg_buf.as_mut_ptr().cast();
+		msg.msg_controllen = space as _;
+		// SAFETY: the control buffer is big enough for one header with the file descriptors, as
+		// calculated by CMSG_SPACE(), and aligned for the header.
+		unsafe {
+			let cmsg = libc::CMSG_FIRSTHDR(&msg);
+			(*cmsg).cmsg_level = libc::SOL_SOCKET;
+			(*cmsg).cmsg_type = libc::SCM_RIGHTS;
+			(*cmsg).cmsg_len = libc::CMSG_LEN(fds_len) as _;
+			ptr::copy_nonoverlapping(
+				raw_fds.as_ptr(),
+				libc::CMSG_DATA(cmsg).cast::<c_int>(),
+				raw_fds.len(),
+			);
+		}
+	}
+	// SAFETY: everything the message points to lives until the call returns.
+	let sent = unsafe { libc::sendmsg(fd.as_raw_fd(), &msg, 0) };
+	ok_or_errno!(sent != -1 => sent as usize)
+}
+
+/// Receives a message along with up to `max_fds` file descriptors passed as `SCM_RIGHTS` ancillary
+/// data, which are made close-on-exec.
+pub(super) fn recv_with_fds(
+	fd: BorrowedFd<'_>,
+	buf: &mut [u8],
+	max_fds: usize,
+) -> io::Result<(usize, Vec<OwnedFd>)> {
+	let fds_len = max_fds
+		.checked_mul(std::mem::size_of::<c_int>())
+		.and_then(|len| u32::try_from(len).ok())
+		.ok_or_else(|| io::Error::from(io::ErrorKind::InvalidInput))?;
+	// SAFETY: just a size calculation.
+	let space = unsafe { libc::CMSG_SPACE(fds_len) } as usize;
+	let cmsg_count = space.div_ceil(std::mem::size_of::<libc::cmsghdr>());
+	// SAFETY: `cmsghdr` is plain old data.
+	let mut cmsg_buf = vec![unsafe { std::mem::zeroed::<libc::cmsghdr>() }; cmsg_count];
+
+	let mut iov = libc::iovec {
+		iov_base: buf.as_mut_ptr().cast(),
+		iov_len: buf.len(),
+	};
+	// SAFETY: as in `send_with_fds()`.
+	let mut msg = unsafe { std::mem::zeroed::<libc::msghdr>() };
+	msg.msg_iov = &mut iov;
+	msg.msg_iovlen = 1;
+	msg.msg_control = cmsg_buf.as_mut_ptr().cast();
+	msg.msg_controllen = space as _;
+	#[cfg(any(target_os = "linux", target_os = "android", target_os = "freebsd"))]
+	let flags = libc::MSG_CMSG_CLOEXEC;
+	#[cfg(not(any(target_os = "linux", target_os = "android", target_os = "freebsd")))]
+	let flags = 0;
+	// SAFETY: everything the message points to lives until the call returns.
+	let received = unsafe { libc::recvmsg(fd.as_raw_fd(), &mut msg, flags) };
+	let received = ok_or_errno!(received != -1 => received as usize)?;
+
+	let mut fds = Vec::new();
+	// SAFETY: the kernel has filled in the control buffer, and the header pointers are only
+	// dereferenced while non-null, as returned by CMSG_FIRSTHDR() and CMSG_NXTHDR().
+	unsafe {
+		let mut cmsg = libc::CMSG_FIRSTHDR(&msg);
+		while !cmsg.is_null() {
+			if (*cmsg).cmsg_level == libc::SOL_SOCKET && (*cmsg).cmsg_type == libc::SCM_RIGHTS {
+				let data_len =
+					((*cmsg).cmsg_len as usize).saturating_sub(libc::CMSG_LEN(0) as usize);
+				let data = libc::CMSG_DATA(cmsg).cast::<c_int>();
+				for i in 0..data_len
+					.checked_div(std::mem::size_of::<c_int>())
+					.unwrap_or(0)
+				{
+					fds.push(OwnedFd::from_raw_fd(data.add(i).read_unaligned()));
+				}
+			}
+			cmsg = libc::CMSG_NXTHDR(&msg, cmsg);
+		}
+	}
+	// Only needed where MSG_CMSG_CLOEXEC isn't available, but harmless elsewhere.
+	for fd in &fds {
+		set_cloexec(fd.as_fd())?;
+	}
+	Ok((received, fds))
+}
+
 #[cfg(feature = "tokio")]
 pub(super) fn shutdown(fd: BorrowedFd<'_>, how: Shutdown) -> io::Result<()> {
 	let how = match how {
@@ -53,3 +192,146 @@ pub(super) fn shutdown(fd: BorrowedFd<'_>, how: Shutdown) -> io::Result<()> {
 	let success = unsafe { libc::shutdown(fd.as_raw_fd(), how) != -1 };
 	ok_or_errno!(success => ())
 }
+
+/// Waits until at least one of the given file descriptors is readable or the timeout, in
+/// milliseconds, expires, with a negative timeout meaning no timeout. Returns whether any of them
+/// is readable. Being interrupted by a signal counts as a spurious wakeup rather than an error.
+pub(super) fn poll_readable(fds: &[BorrowedFd<'_>], timeout_ms: c_int) -> io::Result<bool> {
+	let mut pollfds = fds
+		.iter()
+		.map(|fd| libc::pollfd {
+			fd: fd.as_raw_fd(),
+			events: libc::POLLIN,
+			revents: 0,
+		})
+		.collect::<Vec<_>>();
+	let nfds = libc::nfds_t::try_from(pollfds.len())
+		.map_err(|_| io::Error::from(io::ErrorKind::InvalidInput))?;
+	// SAFETY: the pointer and the length come from the same live vector.
+	let ready = unsafe { libc::poll(pollfds.as_mut_ptr(), nfds, timeout_ms) };
+	match ok_or_errno!(ready != -1 => ready > 0) {
+		Err(e) if e.kind() == io::ErrorKind::Interrupted => Ok(false),
+		els => els,
+	}
+}
+
+/// Converts a timeout to milliseconds for `poll()`, rounding up so as not to wake up just before
+/// the deadline.
+pub(super) fn poll_timeout(timeout: Duration) -> c_int {
+	let ms = timeout.as_nanos().div_ceil(1_000_000);
+	c_int::try_from(ms).unwrap_or(c_int::MAX)
+}
+
+/// Accepts a connection on a listening socket and closes it right away, returning whether there
+/// was one to accept. Only meant for shedding load when no file descriptors are left, which is why
+/// errors are not reported.
+pub(super) fn accept_and_close(fd: BorrowedFd<'_>) -> bool {
+	// SAFETY: null address pointers are allowed and make the peer address not be retrieved.
+	let new_fd = unsafe { libc::accept(fd.as_raw_fd(), ptr::null_mut(), ptr::null_mut()) };
+	if new_fd == -1 {
+		return false;
+	}
+	// SAFETY: the descriptor was just created and isn't owned by anything else.
+	drop(unsafe { OwnedFd::from_raw_fd(new_fd) });
+	true
+}
+
+/// Forks the process, returning the ID of the child process in the parent and `None` in the child.
+///
+/// # Safety
+/// The child process only has a copy of the calling thread, so it must not wait for anything held
+/// by other threads at the time of the call, such as locks.
+pub(super) unsafe fn fork() -> io::Result<Option<pid_t>> {
+	let pid = unsafe { libc::fork() };
+	ok_or_errno!(pid != -1 => (pid != 0).then_some(pid))
+}
+
+/// Reaps the child process if it has exited, returning its wait status, or waits for it to exit if
+/// `block` is set. Being interrupted by a signal is retried.
+pub(super) fn waitpid(pid: pid_t, block: bool) -> io::Result<Option<c_int>> {
+	let options = if block { 0 } else { libc::WNOHANG };
+	let mut status = 0;
+	loop {
+		// SAFETY: the pointer is to a live local.
+		let ret = unsafe { libc::waitpid(pid, &mut status, options) };
+		match ok_or_errno!(ret != -1 => (ret != 0).then_some(status)) {
+			Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+			els => return els,
+		}
+	}
+}
+
+/// Sends a signal to a process.
+pub(super) fn kill(pid: pid_t, signal: c_int) -> io::Result<()> {
+	let success = unsafe { libc::kill(pid, signal) != -1 };
+	ok_or_errno!(success => ())
+}
+
+/// Credentials of the process on the other end of a Unix domain socket connection, as of when it
+/// connected.
+#[derive(Copy, Clone, Debug)]
+pub(crate) struct PeerCredentials {
+	/// Not available on every platform.
+	pub pid: Option<pid_t>,
+	pub euid: uid_t,
+	pub egid: gid_t,
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub(crate) fn peer_credentials(fd: BorrowedFd<'_>) -> io::Result<PeerCredentials> {
+	let mut cred = libc::ucred {
+		pid: 0,
+		uid: 0,
+		gid: 0,
+	};
+	let mut len = std::mem::size_of::<libc::ucred>() as libc::socklen_t;
+	// SAFETY: the pointers are to live locals, and the length is that of the value.
+	let success = unsafe {
+		libc::getsockopt(
+			fd.as_raw_fd(),
+			libc::SOL_SOCKET,
+			libc::SO_PEERCRED,
+			(&mut cred as *mut libc::ucred).cast(),
+			&mut len,
+		) != -1
+	};
+	ok_or_errno!(success => PeerCredentials {
+		pid: Some(cred.pid),
+		euid: cred.uid,
+		egid: cred.gid,
+	})
+}
+#[cfg(any(
+	target_os = "macos",
+	target_os = "ios",
+	target_os = "freebsd",
+	target_os = "dragonfly",
+	target_os = "openbsd",
+	target_os = "netbsd",
+))]
+pub(crate) fn peer_credentials(fd: BorrowedFd<'_>) -> io::Result<PeerCredentials> {
+	let (mut euid, mut egid) = (0, 0);
+	// SAFETY: the pointers are to live locals.
+	let success = unsafe { libc::getpeereid(fd.as_raw_fd(), &mut euid, &mut egid) != -1 };
+	ok_or_errno!(success => PeerCredentials {
+		pid: None,
+		euid,
+		egid,
+	})
+}
+#[cfg(not(any(
+	target_os = "linux",
+	target_os = "android",
+	target_os = "macos",
+	target_os = "ios",
+	target_os = "freebsd",
+	target_os = "dragonfly",
+	target_os = "openbsd",
+	target_os = "netbsd",
+)))]
+pub(crate) fn peer_credentials(_: BorrowedFd<'_>) -> io::Result<PeerCredentials> {
+	Err(io::Error::new(
+		io::ErrorKind::Unsupported,
+		"peer credentials are not supported on this platform",
+	))
+}
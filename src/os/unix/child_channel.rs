@@ -0,0 +1,132 @@
+//! Typed bidirectional channels to child processes.
+//!
+//! [`spawn_with_channel()`] spawns a child process with one end of a [local socket stream
+//! pair](local_socket::traits::Stream::pair) [inherited](Inheritable) by it, and returns a
+//! [`Channel`] wrapping the other end. The child process then calls
+//! [`Channel::from_parent()`] to obtain the matching end of the channel.
+//!
+//! Channels are [typed streams](local_socket::typed) with the default format and framing options,
+//! which means that messages are serialized with [bincode](https://docs.rs/bincode/1) and prefixed
+//! with their length, and that messages larger than 16 MiB are rejected. The type parameters of
+//! the channel are the types of outgoing and incoming messages, so the parent process will usually
+//! use `Channel<Request, Response>` while the child process uses `Channel<Response, Request>`.
+//!
+//! Channels are only available on Unix, since they rely on the child process inheriting a file
+//! descriptor through [`Inheritable`], which has no Windows counterpart in this crate yet. On
+//! Windows, the same can be achieved by binding a [local socket listener](local_socket::Listener)
+//! to a randomly generated name, passing the name to the child process in an environment variable
+//! and wrapping the connection into a [typed stream](local_socket::typed) on both sides.
+//!
+//! # Examples
+//! ```no_run
+//! use interprocess::os::unix::child_channel::{spawn_with_channel, Channel};
+//! use std::{env, process::Command};
+//!
+//! if env::args().nth(1).as_deref() == Some("worker") {
+//! 	// Child process
+//! 	let mut channel = unsafe { Channel::<u64, String>::from_parent()? };
+//! 	while let Some(text) = channel.recv()? {
+//! 		channel.send(&(text.len() as u64))?;
+//! 	}
+//! } else {
+//! 	// Parent process
+//! 	let mut command = Command::new(env::current_exe()?);
+//! 	command.arg("worker");
+//! 	let (mut child, mut channel) = spawn_with_channel::<String, u64>(command)?;
+//! 	channel.send(&"Hello from the parent!".to_owned())?;
+//! 	assert_eq!(channel.recv()?, Some(22));
+//! 	drop(channel);
+//! 	child.wait()?;
+//! }
+//! # std::io::Result::<()>::Ok(())
+//! ```
+
+use super::Inheritable;
+use crate::local_socket::{self, traits::Stream as _, typed::TypedStream, Stream};
+use serde::{de::DeserializeOwned, Serialize};
+use std::{
+	fmt::{self, Debug, Formatter},
+	io,
+	process::{Child, Command},
+};
+
+/// The environment variable through which the file descriptor of the child's end of the channel
+/// is passed.
+const CHANNEL_ENV_VAR: &str = "INTERPROCESS_CHANNEL_FD";
+
+/// Spawns the child process described by `command` with a [`Channel`] to it.
+///
+/// The child process must use [`Channel::from_parent()`] to open its end of the channel. The
+/// command is consumed because it holds on to a copy of the child's end of the channel, which has
+/// to be closed for the parent process to notice the child closing the channel or exiting.
+pub fn spawn_with_channel<Tx: Serialize, Rx: DeserializeOwned>(
+	mut command: Command,
+) -> io::Result<(Child, Channel<Tx, Rx>)> {
+	let (ours, theirs) = Stream::pair()?;
+	theirs.inherit_env(&mut command, CHANNEL_ENV_VAR)?;
+	let child = command.spawn()?;
+	Ok((child, Channel::from_stream(ours)))
+}
+
+/// A typed bidirectional channel to a parent or child process, created by [`spawn_with_channel()`]
+/// or [`Channel::from_parent()`].
+///
+/// `Tx` is the type of messages sent over the channel, and `Rx` is the type of received ones.
+pub struct Channel<Tx, Rx>(TypedStream<Tx, Rx>);
+impl<Tx: Serialize, Rx: DeserializeOwned> Channel<Tx, Rx> {
+	/// Opens the child's end of the channel created by [`spawn_with_channel()`] in the parent
+	/// process.
+	///
+	/// # Safety
+	/// The current process must have been spawned by [`spawn_with_channel()`], and this function
+	/// must not be called more than once in it.
+	pub unsafe fn from_parent() -> io::Result<Self> {
+		let stream = unsafe { Stream::from_inherited_env(CHANNEL_ENV_VAR)? };
+		Ok(Self::from_stream(stream))
+	}
+	/// Wraps an existing local socket stream into a channel.
+	///
+	/// The other end of the stream must also be wrapped into a channel with the type parameters
+	/// swapped around, or into a [`TypedStream`] with the default format and framing options.
+	#[inline]
+	pub fn from_stream(stream: Stream) -> Self {
+		Self(TypedStream::new(stream))
+	}
+	/// Sends a message over the channel.
+	#[inline]
+	pub fn send(&mut self, msg: &Tx) -> io::Result<()> {
+		self.0.send(msg)
+	}
+	/// Receives a message from the channel, returning `None` if the other end has been closed.
+	#[inline]
+	pub fn recv(&mut self) -> io::Result<Option<Rx>> {
+		self.0.recv()
+	}
+	/// Returns the typed stream underlying the channel.
+	#[inline]
+	pub fn into_typed(self) -> TypedStream<Tx, Rx> {
+		self.0
+	}
+	/// Returns the local socket stream underlying the channel.
+	#[inline]
+	pub fn into_inner(self) -> Stream {
+		self.0.into_inner()
+	}
+}
+impl<Tx, Rx> Debug for Channel<Tx, Rx> {
+	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+		f.debug_tuple("Channel").field(&self.0).finish()
+	}
+}
+impl<Tx, Rx> From<Channel<Tx, Rx>> for TypedStream<Tx, Rx> {
+	#[inline]
+	fn from(channel: Channel<Tx, Rx>) -> Self {
+		channel.0
+	}
+}
+impl<Tx, Rx> From<Channel<Tx, Rx>> for local_socket::Stream {
+	#[inline]
+	fn from(channel: Channel<Tx, Rx>) -> Self {
+		channel.0.into_inner()
+	}
+}
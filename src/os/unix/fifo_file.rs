@@ -15,10 +15,48 @@
 //! known path which works like a pipe and nothing else.
 //!
 //! ## Usage
-//! The [`create_fifo()`] function serves for a FIFO file creation. Opening FIFO files works via the
-//! standard [`File`](std::fs::File)s, opened either only for sending or only for receiving.
-//! Deletion works the same way as with any regular file, via
-//! [`remove_file()`](std::fs::remove_file).
+//! The [`Fifo`] type owns a FIFO file: it creates the file (optionally in a fresh private
+//! directory), opens [`Recver`]s and [`Sender`]s on it and deletes it when dropped, much like
+//! [name reclamation](crate::local_socket::Listener#name-reclamation) works for local socket
+//! listeners.
+//!
+//! The lower-level [`create_fifo()`] function only creates the FIFO file. FIFO files which are not
+//! owned by a [`Fifo`] can be opened using the free functions in this module, such as
+//! [`open_recver()`] and [`open_sender()`]. Deletion works the same way as with any regular file,
+//! via [`remove_file()`](std::fs::remove_file).
+//!
+//! [`DuplexFifo`] and [`DuplexStream`] combine two FIFO files into a bidirectional channel, taking
+//! care of opening them in an order which doesn't deadlock.
+//!
+//! With the `tokio` feature enabled, the [`tokio`](self::tokio) submodule provides asynchronous
+//! FIFO file ends.
+//!
+//! ## Opening
+//! Opening a FIFO file is a rendezvous: opening it for receiving blocks until another process opens
+//! it for sending, and vice versa. A process which opens two FIFO files in a different order than
+//! its peer, or which opens a FIFO file nobody else ever opens, will thus hang indefinitely. This
+//! module offers three strategies for dealing with that:
+//! -	**Blocking**, as performed by [`open_recver()`] and [`open_sender()`], which waits for the
+//!   	other side for however long it takes.
+//! -	**Nonblocking**, as performed by [`open_recver_nonblocking()`] and
+//!   	[`open_sender_nonblocking()`]. A receiver opened this way does not wait for a sender, and will
+//!   	see an end-of-file condition until one connects. Senders cannot be opened without a receiver,
+//!   	so opening one this way fails with [`WouldBlock`](io::ErrorKind::WouldBlock) instead.
+//! -	**With a timeout**, as performed by [`open_recver_timeout()`] and [`open_sender_timeout()`],
+//!   	which wait like the blocking variants but give up with [`TimedOut`](io::ErrorKind::TimedOut)
+//!   	once the timeout expires. Senders are opened by retrying a nonblocking open until a receiver
+//!   	appears.
+
+mod duplex;
+mod ends;
+mod fifo;
+mod open;
+
+#[cfg(feature = "tokio")]
+#[cfg_attr(feature = "doc_cfg", doc(cfg(feature = "tokio")))]
+pub mod tokio;
+
+pub use {duplex::*, ends::*, fifo::*, open::*};
 
 use super::unixprelude::*;
 use std::{ffi::CString, io, path::Path};
@@ -37,7 +75,11 @@ pub fn create_fifo<P: AsRef<Path>>(path: P, mode: mode_t) -> io::Result<()> {
 	_create_fifo(path.as_ref(), mode)
 }
 fn _create_fifo(path: &Path, mode: mode_t) -> io::Result<()> {
-	let path = CString::new(path.as_os_str().as_bytes())?;
+	let path = path_to_cstring(path)?;
 	let success = unsafe { libc::mkfifo(path.as_bytes_with_nul().as_ptr() as *const _, mode) == 0 };
 	ok_or_errno!(success => ())
 }
+
+fn path_to_cstring(path: &Path) -> io::Result<CString> {
+	Ok(CString::new(path.as_os_str().as_bytes())?)
+}
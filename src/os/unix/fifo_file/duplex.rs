@@ -0,0 +1,226 @@
+use super::{
+	super::unixprelude::*, fifo::make_private_dir, open_recver, open_recver_nonblocking,
+	open_recver_timeout, open_sender, open_sender_timeout, Fifo, Recver, Sender,
+};
+use std::{
+	ffi::OsString,
+	fs,
+	io::{self, prelude::*, IoSlice, IoSliceMut},
+	path::{Path, PathBuf},
+	time::{Duration, Instant},
+};
+
+/// A pair of FIFO files at `<base>.in` and `<base>.out` forming a bidirectional channel, deleted
+/// from the filesystem when dropped.
+///
+/// This is the server side of a duplex FIFO channel: [`accept()`](Self::accept) receives from
+/// `<base>.in` and sends to `<base>.out`. The other side is typically [`DuplexStream::connect()`],
+/// but can also be a program which only knows how to handle files, such as a shell script:
+/// ```sh
+/// exec 3>/tmp/channel.in 4</tmp/channel.out
+/// echo ping >&3
+/// read -r reply <&4
+/// ```
+///
+/// Since a single FIFO file cannot tell its senders apart, there can only be one client at a time.
+/// Once a client disconnects, the next one can be accepted.
+///
+/// # Examples
+/// ```no_run
+/// use interprocess::os::unix::fifo_file::DuplexFifo;
+/// use std::io::{prelude::*, BufReader};
+///
+/// let channel = DuplexFifo::create("/tmp/channel", 0o600)?;
+/// loop {
+/// 	let mut conn = BufReader::new(channel.accept()?);
+/// 	let mut line = String::new();
+/// 	while conn.read_line(&mut line)? != 0 {
+/// 		conn.get_mut().write_all(line.to_uppercase().as_bytes())?;
+/// 		line.clear();
+/// 	}
+/// }
+/// # std::io::Result::<()>::Ok(())
+/// ```
+#[derive(Debug)]
+pub struct DuplexFifo {
+	// The order of the fields is significant: the private directory is owned by `out`, which must
+	// therefore be dropped last.
+	in_fifo: Fifo,
+	out_fifo: Fifo,
+}
+impl DuplexFifo {
+	/// Creates the FIFO files `<base>.in` and `<base>.out` with the specified permissions.
+	///
+	/// See [`create_fifo()`](super::create_fifo) for the meaning of `mode`.
+	pub fn create(base: impl AsRef<Path>, mode: mode_t) -> io::Result<Self> {
+		let (in_path, out_path) = duplex_paths(base.as_ref());
+		let in_fifo = Fifo::create(in_path, mode)?;
+		let out_fifo = Fifo::create(out_path, mode)?;
+		Ok(Self { in_fifo, out_fifo })
+	}
+	/// Creates a new directory only accessible to the current user inside the system's temporary
+	/// directory, and creates the FIFO files `<base_name>.in` and `<base_name>.out` in it.
+	///
+	/// See [`Fifo::create_in_private_dir()`].
+	pub fn create_in_private_dir(base_name: impl AsRef<Path>, mode: mode_t) -> io::Result<Self> {
+		let base_name = base_name.as_ref();
+		if base_name.components().count() != 1 || base_name.file_name().is_none() {
+			return Err(io::Error::new(
+				io::ErrorKind::InvalidInput,
+				"base name must be a single non-empty path component",
+			));
+		}
+		let private_dir = make_private_dir()?;
+		let mut slf = match Self::create(private_dir.join(base_name), mode) {
+			Ok(slf) => slf,
+			Err(e) => {
+				let _ = fs::remove_dir(&private_dir);
+				return Err(e);
+			}
+		};
+		slf.out_fifo.private_dir = Some(private_dir);
+		Ok(slf)
+	}
+
+	/// Returns the path to the FIFO file which the server receives from and clients send to.
+	#[inline]
+	pub fn in_path(&self) -> &Path {
+		self.in_fifo.path()
+	}
+	/// Returns the path to the FIFO file which the server sends to and clients receive from.
+	#[inline]
+	pub fn out_path(&self) -> &Path {
+		self.out_fifo.path()
+	}
+	/// Disables name reclamation, leaving the FIFO files (and their private directory, if any) on
+	/// the filesystem after the `DuplexFifo` is dropped.
+	pub fn do_not_reclaim_name_on_drop(&mut self) {
+		self.in_fifo.do_not_reclaim_name_on_drop();
+		self.out_fifo.do_not_reclaim_name_on_drop();
+	}
+
+	/// Waits for a client to open both FIFO files and returns the resulting duplex stream.
+	///
+	/// See [`DuplexStream`] for a description of the handshake.
+	pub fn accept(&self) -> io::Result<DuplexStream> {
+		handshake(self.in_path(), self.out_path(), None)
+	}
+	/// Like [`accept()`](Self::accept), but gives up with a [`TimedOut`](io::ErrorKind::TimedOut)
+	/// error if no client completes the handshake before the timeout expires.
+	pub fn accept_timeout(&self, timeout: Duration) -> io::Result<DuplexStream> {
+		handshake(
+			self.in_path(),
+			self.out_path(),
+			Instant::now().checked_add(timeout),
+		)
+	}
+}
+
+/// Bidirectional channel made out of two FIFO files, obtained either from [`DuplexFifo`] or by
+/// connecting to an existing one.
+///
+/// # Handshake
+/// Opening a FIFO file blocks until the other end is opened by someone else, which makes the order
+/// in which the two processes open the two FIFO files significant: if both sides first open their
+/// receiving ends, neither ever gets around to opening its sending end. To avoid this, both
+/// [`DuplexFifo::accept()`] and [`DuplexStream::connect()`] perform the handshake as follows:
+/// 1.	Open the receiving FIFO file in a nonblocking fashion, which doesn't wait for the peer but
+///    	makes it possible for the peer's sending end to be opened.
+/// 2.	Open the sending FIFO file, waiting for the peer to open its receiving end.
+/// 3.	Open the receiving FIFO file again, this time waiting for the peer to open its sending end,
+///    	and close the end opened in step 1.
+///
+/// This rendezvouses with a peer which opens the FIFO files in any order, including one which
+/// opens both of them in a blocking fashion.
+#[derive(Debug)]
+pub struct DuplexStream {
+	recver: Recver,
+	sender: Sender,
+}
+impl DuplexStream {
+	/// Connects to the duplex FIFO channel with the given base path, receiving from `<base>.out` and
+	/// sending to `<base>.in`.
+	pub fn connect(base: impl AsRef<Path>) -> io::Result<Self> {
+		let (in_path, out_path) = duplex_paths(base.as_ref());
+		handshake(&out_path, &in_path, None)
+	}
+	/// Like [`connect()`](Self::connect), but gives up with a [`TimedOut`](io::ErrorKind::TimedOut)
+	/// error if the server does not complete the handshake before the timeout expires.
+	pub fn connect_timeout(base: impl AsRef<Path>, timeout: Duration) -> io::Result<Self> {
+		let deadline = Instant::now().checked_add(timeout);
+		let (in_path, out_path) = duplex_paths(base.as_ref());
+		handshake(&out_path, &in_path, deadline)
+	}
+
+	/// Splits the stream into its receiving and sending ends.
+	#[inline]
+	pub fn split(self) -> (Recver, Sender) {
+		(self.recver, self.sender)
+	}
+	/// Assembles a duplex stream from a receiving and a sending end, which need not be related in
+	/// any way.
+	#[inline]
+	pub fn from_parts(recver: Recver, sender: Sender) -> Self {
+		Self { recver, sender }
+	}
+}
+impl Read for &DuplexStream {
+	#[inline]
+	fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+		(&self.recver).read(buf)
+	}
+	#[inline]
+	fn read_vectored(&mut self, bufs: &mut [IoSliceMut<'_>]) -> io::Result<usize> {
+		(&self.recver).read_vectored(bufs)
+	}
+}
+impl Write for &DuplexStream {
+	#[inline]
+	fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+		(&self.sender).write(buf)
+	}
+	#[inline]
+	fn write_vectored(&mut self, bufs: &[IoSlice<'_>]) -> io::Result<usize> {
+		(&self.sender).write_vectored(bufs)
+	}
+	#[inline]
+	fn flush(&mut self) -> io::Result<()> {
+		(&self.sender).flush()
+	}
+}
+multimacro! {
+	DuplexStream,
+	derive_sync_mut_read,
+	derive_sync_mut_write,
+}
+
+fn handshake(
+	recv_path: &Path,
+	send_path: &Path,
+	deadline: Option<Instant>,
+) -> io::Result<DuplexStream> {
+	let placeholder = open_recver_nonblocking(recv_path)?;
+	let sender = match deadline {
+		Some(deadline) => open_sender_timeout(send_path, remaining(deadline))?,
+		None => open_sender(send_path)?,
+	};
+	let recver = match deadline {
+		Some(deadline) => open_recver_timeout(recv_path, remaining(deadline))?,
+		None => open_recver(recv_path)?,
+	};
+	drop(placeholder);
+	Ok(DuplexStream { recver, sender })
+}
+
+fn duplex_paths(base: &Path) -> (PathBuf, PathBuf) {
+	let suffixed = |suffix: &str| {
+		let mut path = OsString::from(base);
+		path.push(suffix);
+		PathBuf::from(path)
+	};
+	(suffixed(".in"), suffixed(".out"))
+}
+
+fn remaining(deadline: Instant) -> Duration {
+	deadline.saturating_duration_since(Instant::now())
+}
@@ -0,0 +1,68 @@
+use super::super::{c_wrappers, unixprelude::*, FdOps};
+use std::io::{self, prelude::*, IoSlice};
+
+/// Receiving end of a FIFO file, obtained by opening it for receiving.
+///
+/// The core functionality is exposed in a [`Read`] interface. Reading from a FIFO file returns an
+/// end-of-file condition once all of its senders have disconnected (or, if it was opened in a
+/// nonblocking fashion, before any of them have connected).
+pub struct Recver(pub(super) FdOps);
+impl Recver {
+	/// Enables or disables the nonblocking mode for the receiver. By default, it is disabled.
+	///
+	/// In nonblocking mode, receiving from a FIFO file which has a sender but no data available
+	/// returns a [`WouldBlock`](io::ErrorKind::WouldBlock) error instead of waiting for data.
+	pub fn set_nonblocking(&self, nonblocking: bool) -> io::Result<()> {
+		c_wrappers::set_nonblocking(self.as_fd(), nonblocking)
+	}
+}
+multimacro! {
+	Recver,
+	forward_rbv(FdOps, &),
+	forward_sync_ref_read,
+	forward_try_clone,
+	forward_handle,
+	forward_debug,
+	derive_sync_mut_read,
+	derive_raw,
+}
+
+/// Sending end of a FIFO file, obtained by opening it for sending.
+///
+/// The core functionality is exposed in a [`Write`] interface. Sending to a FIFO file which no
+/// longer has any receivers fails with a [`BrokenPipe`](io::ErrorKind::BrokenPipe) error.
+pub struct Sender(pub(super) FdOps);
+impl Sender {
+	/// Enables or disables the nonblocking mode for the sender. By default, it is disabled.
+	///
+	/// In nonblocking mode, sending to a FIFO file whose buffer is full returns a
+	/// [`WouldBlock`](io::ErrorKind::WouldBlock) error instead of waiting for the receiver to catch
+	/// up.
+	pub fn set_nonblocking(&self, nonblocking: bool) -> io::Result<()> {
+		c_wrappers::set_nonblocking(self.as_fd(), nonblocking)
+	}
+}
+impl Write for &Sender {
+	#[inline]
+	fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+		self.refwd().write(buf)
+	}
+	#[inline]
+	fn write_vectored(&mut self, bufs: &[IoSlice<'_>]) -> io::Result<usize> {
+		self.refwd().write_vectored(bufs)
+	}
+	/// Does nothing, since FIFO files are not buffered in userspace and cannot be synced.
+	#[inline]
+	fn flush(&mut self) -> io::Result<()> {
+		Ok(())
+	}
+}
+multimacro! {
+	Sender,
+	forward_rbv(FdOps, &),
+	forward_try_clone,
+	forward_handle,
+	forward_debug,
+	derive_sync_mut_write,
+	derive_raw,
+}
@@ -0,0 +1,152 @@
+use super::{super::unixprelude::*, create_fifo, path_to_cstring, Recver, Sender};
+use std::{
+	env,
+	ffi::{CString, OsStr, OsString},
+	fs, io,
+	path::{Path, PathBuf},
+	time::Duration,
+};
+
+/// An owned FIFO file, deleted from the filesystem when dropped.
+///
+/// `Fifo` creates the FIFO file it owns, and performs the FIFO equivalent of
+/// [name reclamation](crate::local_socket::Listener#name-reclamation) when dropped, deleting it
+/// from the filesystem. If it was created in a private directory, that directory is deleted as
+/// well. This can be opted out of via
+/// [`.do_not_reclaim_name_on_drop()`](Self::do_not_reclaim_name_on_drop).
+///
+/// The `open_*` methods open the FIFO file for receiving or sending, and work exactly like the
+/// same-named free functions in the [parent module](super) – see its documentation for the
+/// differences between them.
+///
+/// # Examples
+/// ```no_run
+/// use interprocess::os::unix::fifo_file::Fifo;
+/// use std::io::{prelude::*, BufReader};
+///
+/// // Creates something like /tmp/interprocess-fifo-Uq1BTm/control.
+/// let fifo = Fifo::create_in_private_dir("control", 0o600)?;
+/// println!("Send commands to {}", fifo.path().display());
+///
+/// // Don't wait for the first sender, and see EOF until one arrives instead.
+/// let mut recver = BufReader::new(fifo.open_recver_nonblocking()?);
+/// let mut line = String::new();
+/// recver.read_line(&mut line)?;
+/// # std::io::Result::<()>::Ok(())
+/// ```
+#[derive(Debug)]
+pub struct Fifo {
+	path: PathBuf,
+	pub(super) private_dir: Option<PathBuf>,
+	reclaim: bool,
+}
+impl Fifo {
+	/// Creates a FIFO file at the specified path with the specified permissions.
+	///
+	/// See [`create_fifo()`] for the meaning of `mode`.
+	pub fn create(path: impl AsRef<Path>, mode: mode_t) -> io::Result<Self> {
+		let path = path.as_ref();
+		create_fifo(path, mode)?;
+		Ok(Self {
+			path: path.to_owned(),
+			private_dir: None,
+			reclaim: true,
+		})
+	}
+	/// Creates a new directory only accessible to the current user inside the system's temporary
+	/// directory, and creates a FIFO file with the given file name and permissions in it.
+	///
+	/// This avoids collisions with other FIFO files and prevents other users from tampering with
+	/// the FIFO file regardless of the `umask`.
+	pub fn create_in_private_dir(file_name: impl AsRef<OsStr>, mode: mode_t) -> io::Result<Self> {
+		let file_name = file_name.as_ref();
+		if file_name.is_empty() || file_name.as_bytes().contains(&b'/') {
+			return Err(io::Error::new(
+				io::ErrorKind::InvalidInput,
+				"FIFO file name must be a single non-empty path component",
+			));
+		}
+		let private_dir = make_private_dir()?;
+		let path = private_dir.join(file_name);
+		if let Err(e) = create_fifo(&path, mode) {
+			let _ = fs::remove_dir(&private_dir);
+			return Err(e);
+		}
+		Ok(Self {
+			path,
+			private_dir: Some(private_dir),
+			reclaim: true,
+		})
+	}
+
+	/// Returns the path to the FIFO file.
+	#[inline]
+	pub fn path(&self) -> &Path {
+		&self.path
+	}
+	/// Disables name reclamation, leaving the FIFO file (and its private directory, if any) on the
+	/// filesystem after the `Fifo` is dropped.
+	#[inline]
+	pub fn do_not_reclaim_name_on_drop(&mut self) {
+		self.reclaim = false;
+	}
+
+	/// Opens the FIFO file for receiving, blocking until a sender connects. See
+	/// [`open_recver()`](super::open_recver).
+	#[inline]
+	pub fn open_recver(&self) -> io::Result<Recver> {
+		super::open_recver(&self.path)
+	}
+	/// Opens the FIFO file for receiving without waiting for a sender. See
+	/// [`open_recver_nonblocking()`](super::open_recver_nonblocking).
+	#[inline]
+	pub fn open_recver_nonblocking(&self) -> io::Result<Recver> {
+		super::open_recver_nonblocking(&self.path)
+	}
+	/// Opens the FIFO file for receiving, waiting for a sender for no longer than the given
+	/// timeout. See [`open_recver_timeout()`](super::open_recver_timeout).
+	#[inline]
+	pub fn open_recver_timeout(&self, timeout: Duration) -> io::Result<Recver> {
+		super::open_recver_timeout(&self.path, timeout)
+	}
+	/// Opens the FIFO file for sending, blocking until a receiver connects. See
+	/// [`open_sender()`](super::open_sender).
+	#[inline]
+	pub fn open_sender(&self) -> io::Result<Sender> {
+		super::open_sender(&self.path)
+	}
+	/// Opens the FIFO file for sending if a receiver is already connected. See
+	/// [`open_sender_nonblocking()`](super::open_sender_nonblocking).
+	#[inline]
+	pub fn open_sender_nonblocking(&self) -> io::Result<Sender> {
+		super::open_sender_nonblocking(&self.path)
+	}
+	/// Opens the FIFO file for sending, waiting for a receiver for no longer than the given
+	/// timeout. See [`open_sender_timeout()`](super::open_sender_timeout).
+	#[inline]
+	pub fn open_sender_timeout(&self, timeout: Duration) -> io::Result<Sender> {
+		super::open_sender_timeout(&self.path, timeout)
+	}
+}
+impl Drop for Fifo {
+	fn drop(&mut self) {
+		if !self.reclaim {
+			return;
+		}
+		let _ = fs::remove_file(&self.path);
+		if let Some(private_dir) = &self.private_dir {
+			let _ = fs::remove_dir(private_dir);
+		}
+	}
+}
+
+pub(super) fn make_private_dir() -> io::Result<PathBuf> {
+	let template = env::temp_dir().join("interprocess-fifo-XXXXXX");
+	let mut template = path_to_cstring(&template)?.into_bytes_with_nul();
+	// mkdtemp creates the directory with 0o700 permissions.
+	let success = unsafe { !libc::mkdtemp(template.as_mut_ptr().cast()).is_null() };
+	ok_or_errno!(success => ())?;
+	let path = CString::from_vec_with_nul(template)
+		.map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+	Ok(OsString::from_vec(path.into_bytes()).into())
+}
@@ -0,0 +1,152 @@
+use super::{
+	super::{c_wrappers, unixprelude::*, FdOps},
+	path_to_cstring, Recver, Sender,
+};
+use std::{
+	ffi::CStr,
+	io,
+	path::Path,
+	sync::mpsc::{self, RecvTimeoutError},
+	thread,
+	time::{Duration, Instant},
+};
+
+pub(super) const RETRY_INTERVAL_MIN: Duration = Duration::from_millis(1);
+pub(super) const RETRY_INTERVAL_MAX: Duration = Duration::from_millis(50);
+
+/// Opens the FIFO file at the given path for receiving, blocking until it is opened for sending by
+/// someone else.
+///
+/// See the [module-level documentation](super#opening) for ways to avoid blocking indefinitely.
+pub fn open_recver(path: impl AsRef<Path>) -> io::Result<Recver> {
+	let path = path_to_cstring(path.as_ref())?;
+	open_fd(&path, libc::O_RDONLY).map(recver_from_fd)
+}
+
+/// Opens the FIFO file at the given path for receiving without waiting for a sender to connect.
+///
+/// Receiving from the resulting receiver will return an end-of-file condition until a sender
+/// connects. The receiver itself is in blocking mode, i.e. the nonblocking mode only applies to
+/// the act of opening the FIFO file.
+pub fn open_recver_nonblocking(path: impl AsRef<Path>) -> io::Result<Recver> {
+	let path = path_to_cstring(path.as_ref())?;
+	let fd = open_fd(&path, libc::O_RDONLY | libc::O_NONBLOCK)?;
+	c_wrappers::set_nonblocking(fd.as_fd(), false)?;
+	Ok(recver_from_fd(fd))
+}
+
+/// Opens the FIFO file at the given path for receiving, blocking until it is opened for sending by
+/// someone else or until the timeout expires, in which case a [`TimedOut`](io::ErrorKind::TimedOut)
+/// error is returned.
+///
+/// Since opening a FIFO file for receiving cannot be interrupted, this function performs the
+/// blocking open in a helper thread. If the timeout expires, the helper thread is unblocked by
+/// briefly opening the FIFO file for sending. As such, a sender which connects at the very moment
+/// of the timeout expiring may find itself connected to a receiver which has already been closed.
+pub fn open_recver_timeout(path: impl AsRef<Path>, timeout: Duration) -> io::Result<Recver> {
+	let path = path_to_cstring(path.as_ref())?;
+	let (result_sender, result_recver) = mpsc::channel();
+	let thread_path = path.clone();
+	thread::Builder::new()
+		.name("FIFO opener".to_owned())
+		.spawn(move || {
+			let _ = result_sender.send(open_fd(&thread_path, libc::O_RDONLY));
+		})?;
+	match result_recver.recv_timeout(timeout) {
+		Ok(rslt) => return rslt.map(recver_from_fd),
+		Err(RecvTimeoutError::Disconnected) => return Err(opener_thread_died()),
+		Err(RecvTimeoutError::Timeout) => {}
+	}
+	// Keep opening the FIFO file for sending until the helper thread notices. The first attempt
+	// almost always succeeds, since the helper thread counts as a receiver as soon as it starts
+	// waiting inside `open()`.
+	loop {
+		let _unblocker = open_fd(&path, libc::O_WRONLY | libc::O_NONBLOCK);
+		match result_recver.recv_timeout(RETRY_INTERVAL_MAX) {
+			Err(RecvTimeoutError::Timeout) => continue,
+			Ok(..) | Err(RecvTimeoutError::Disconnected) => break,
+		}
+	}
+	Err(io::Error::from(io::ErrorKind::TimedOut))
+}
+
+/// Opens the FIFO file at the given path for sending, blocking until it is opened for receiving by
+/// someone else.
+///
+/// See the [module-level documentation](super#opening) for ways to avoid blocking indefinitely.
+pub fn open_sender(path: impl AsRef<Path>) -> io::Result<Sender> {
+	let path = path_to_cstring(path.as_ref())?;
+	open_fd(&path, libc::O_WRONLY).map(sender_from_fd)
+}
+
+/// Opens the FIFO file at the given path for sending if it is already opened for receiving by
+/// someone else, failing with a [`WouldBlock`](io::ErrorKind::WouldBlock) error otherwise.
+///
+/// The sender itself is in blocking mode, i.e. the nonblocking mode only applies to the act of
+/// opening the FIFO file.
+pub fn open_sender_nonblocking(path: impl AsRef<Path>) -> io::Result<Sender> {
+	let path = path_to_cstring(path.as_ref())?;
+	try_open_sender(&path)
+}
+
+/// Opens the FIFO file at the given path for sending, waiting until it is opened for receiving by
+/// someone else or until the timeout expires, in which case a [`TimedOut`](io::ErrorKind::TimedOut)
+/// error is returned.
+///
+/// Waiting is implemented by retrying [`open_sender_nonblocking()`] with exponential backoff
+/// between attempts, up to 50 milliseconds.
+pub fn open_sender_timeout(path: impl AsRef<Path>, timeout: Duration) -> io::Result<Sender> {
+	let path = path_to_cstring(path.as_ref())?;
+	let deadline = Instant::now().checked_add(timeout);
+	let mut interval = RETRY_INTERVAL_MIN;
+	loop {
+		match try_open_sender(&path) {
+			Err(e) if e.kind() == io::ErrorKind::WouldBlock => {}
+			els => return els,
+		}
+		let remaining = match deadline {
+			Some(deadline) => deadline.saturating_duration_since(Instant::now()),
+			None => interval,
+		};
+		if remaining.is_zero() {
+			return Err(io::Error::from(io::ErrorKind::TimedOut));
+		}
+		thread::sleep(interval.min(remaining));
+		interval = interval.saturating_mul(2).min(RETRY_INTERVAL_MAX);
+	}
+}
+
+pub(super) fn try_open_sender(path: &CStr) -> io::Result<Sender> {
+	let fd = match open_fd(path, libc::O_WRONLY | libc::O_NONBLOCK) {
+		Err(e) if e.raw_os_error() == Some(libc::ENXIO) => {
+			return Err(io::Error::from(io::ErrorKind::WouldBlock))
+		}
+		els => els?,
+	};
+	c_wrappers::set_nonblocking(fd.as_fd(), false)?;
+	Ok(sender_from_fd(fd))
+}
+
+pub(super) fn open_fd(path: &CStr, flags: c_int) -> io::Result<OwnedFd> {
+	loop {
+		let fd = unsafe { libc::open(path.as_ptr(), flags | libc::O_CLOEXEC) };
+		match ok_or_errno!(fd != -1 => fd) {
+			Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+			// SAFETY: we just created the file descriptor, meaning that it's guaranteed not to be
+			// in use elsewhere.
+			els => return els.map(|fd| unsafe { OwnedFd::from_raw_fd(fd) }),
+		}
+	}
+}
+
+fn recver_from_fd(fd: OwnedFd) -> Recver {
+	Recver(FdOps(fd))
+}
+fn sender_from_fd(fd: OwnedFd) -> Sender {
+	Sender(FdOps(fd))
+}
+
+#[cold]
+fn opener_thread_died() -> io::Error {
+	io::Error::other("FIFO opener thread exited unexpectedly")
+}
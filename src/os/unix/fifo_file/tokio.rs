@@ -0,0 +1,211 @@
+//! Tokio-based FIFO file ends.
+//!
+//! Both ends are opened in nonblocking mode and registered with the Tokio reactor, so neither
+//! opening nor using them ever blocks the runtime.
+//!
+//! # Persistent receivers
+//! A FIFO file receiver sees an end-of-file condition once all of its senders have disconnected,
+//! which is a nuisance when the senders are short-lived (a shell script running
+//! `echo reload > /run/mydaemon/control`, for instance) and the receiver is supposed to outlive
+//! all of them. [`Recver::open_persistent()`] deals with that by keeping a dummy sender open
+//! alongside the receiver, which never sends anything but prevents the end-of-file condition from
+//! ever occurring. Receiving from such a receiver simply waits until the next sender connects
+//! and sends something.
+//!
+//! # Examples
+//! ```no_run
+//! # #[tokio::main(flavor = "current_thread")]
+//! # async fn main() -> std::io::Result<()> {
+//! use interprocess::os::unix::fifo_file::{tokio::Recver, Fifo};
+//! use tokio::io::{AsyncBufReadExt, BufReader};
+//!
+//! let fifo = Fifo::create("/run/mydaemon/control", 0o600)?;
+//! let mut lines = BufReader::new(Recver::open_persistent(fifo.path())?).lines();
+//! while let Some(line) = lines.next_line().await? {
+//! 	println!("Received command: {line}");
+//! }
+//! # Ok(()) }
+//! ```
+
+use super::{
+	super::{unixprelude::*, FdOps},
+	open::{open_fd, try_open_sender, RETRY_INTERVAL_MAX, RETRY_INTERVAL_MIN},
+	path_to_cstring,
+};
+use std::{
+	fmt::{self, Debug, Formatter},
+	fs::File,
+	io::{self, ErrorKind::WouldBlock},
+	path::Path,
+	pin::Pin,
+	task::{ready, Context, Poll},
+};
+use tokio::{
+	io::{AsyncRead, AsyncWrite, ReadBuf},
+	net::unix::pipe,
+	time::sleep,
+};
+
+/// Tokio-based receiving end of a FIFO file.
+///
+/// Unless opened with [`open_persistent()`](Self::open_persistent), receiving returns an
+/// end-of-file condition until a sender connects and after all senders have disconnected.
+pub struct Recver {
+	pipe: pipe::Receiver,
+	keepalive: Option<OwnedFd>,
+}
+impl Recver {
+	/// Opens the FIFO file at the given path for receiving without waiting for a sender to connect.
+	pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+		let path = path_to_cstring(path.as_ref())?;
+		let fd = open_fd(&path, libc::O_RDONLY | libc::O_NONBLOCK)?;
+		Self::try_from(super::Recver(FdOps(fd)))
+	}
+	/// Opens the FIFO file at the given path for receiving, keeping a dummy sender open for as long
+	/// as the receiver exists. See the [module-level documentation](self#persistent-receivers).
+	pub fn open_persistent(path: impl AsRef<Path>) -> io::Result<Self> {
+		let path = path_to_cstring(path.as_ref())?;
+		let fd = open_fd(&path, libc::O_RDONLY | libc::O_NONBLOCK)?;
+		// Cannot fail with ENXIO, since the FIFO file now has a receiver – us.
+		let keepalive = open_fd(&path, libc::O_WRONLY | libc::O_NONBLOCK)?;
+		let mut slf = Self::try_from(super::Recver(FdOps(fd)))?;
+		slf.keepalive = Some(keepalive);
+		Ok(slf)
+	}
+	/// Returns `true` if the receiver keeps a dummy sender open, i.e. if it was opened with
+	/// [`open_persistent()`](Self::open_persistent).
+	#[inline]
+	pub fn is_persistent(&self) -> bool {
+		self.keepalive.is_some()
+	}
+}
+impl TryFrom<super::Recver> for Recver {
+	type Error = io::Error;
+	/// Registers a synchronous receiver with the Tokio reactor, switching it to nonblocking mode.
+	fn try_from(sync: super::Recver) -> io::Result<Self> {
+		Ok(Self {
+			pipe: pipe::Receiver::from_file(File::from(OwnedFd::from(sync)))?,
+			keepalive: None,
+		})
+	}
+}
+impl AsyncRead for &Recver {
+	fn poll_read(
+		self: Pin<&mut Self>,
+		cx: &mut Context<'_>,
+		buf: &mut ReadBuf<'_>,
+	) -> Poll<io::Result<()>> {
+		loop {
+			match self.pipe.try_read_buf(buf) {
+				Err(e) if e.kind() == WouldBlock => ready!(self.pipe.poll_read_ready(cx)?),
+				els => return Poll::Ready(els.map(|_| ())),
+			}
+		}
+	}
+}
+impl Debug for Recver {
+	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+		f.debug_struct("Recver")
+			.field("fd", &self.pipe.as_raw_fd())
+			.field("keepalive", &self.keepalive)
+			.finish()
+	}
+}
+impl AsFd for Recver {
+	#[inline]
+	fn as_fd(&self) -> BorrowedFd<'_> {
+		self.pipe.as_fd()
+	}
+}
+derive_tokio_mut_read!(Recver);
+derive_asraw!(Recver, unix);
+
+/// Tokio-based sending end of a FIFO file.
+///
+/// Sending to a FIFO file which no longer has any receivers fails with a
+/// [`BrokenPipe`](io::ErrorKind::BrokenPipe) error.
+pub struct Sender(pipe::Sender);
+impl Sender {
+	/// Opens the FIFO file at the given path for sending, waiting until it is opened for receiving
+	/// by someone else.
+	///
+	/// Waiting is implemented by retrying [`open_nonblocking()`](Self::open_nonblocking) with
+	/// exponential backoff between attempts, up to 50 milliseconds. To put a limit on how long to
+	/// wait, use [`tokio::time::timeout()`].
+	pub async fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+		let path = path_to_cstring(path.as_ref())?;
+		let mut interval = RETRY_INTERVAL_MIN;
+		loop {
+			match try_open_sender(&path) {
+				Err(e) if e.kind() == WouldBlock => {}
+				els => return Self::try_from(els?),
+			}
+			sleep(interval).await;
+			interval = interval.saturating_mul(2).min(RETRY_INTERVAL_MAX);
+		}
+	}
+	/// Opens the FIFO file at the given path for sending if it is already opened for receiving by
+	/// someone else, failing with a [`WouldBlock`](io::ErrorKind::WouldBlock) error otherwise.
+	pub fn open_nonblocking(path: impl AsRef<Path>) -> io::Result<Self> {
+		let path = path_to_cstring(path.as_ref())?;
+		Self::try_from(try_open_sender(&path)?)
+	}
+}
+impl TryFrom<super::Sender> for Sender {
+	type Error = io::Error;
+	/// Registers a synchronous sender with the Tokio reactor, switching it to nonblocking mode.
+	fn try_from(sync: super::Sender) -> io::Result<Self> {
+		Ok(Self(pipe::Sender::from_file(File::from(OwnedFd::from(
+			sync,
+		)))?))
+	}
+}
+impl AsyncWrite for &Sender {
+	fn poll_write(
+		self: Pin<&mut Self>,
+		cx: &mut Context<'_>,
+		buf: &[u8],
+	) -> Poll<io::Result<usize>> {
+		loop {
+			match self.0.try_write(buf) {
+				Err(e) if e.kind() == WouldBlock => ready!(self.0.poll_write_ready(cx)?),
+				els => return Poll::Ready(els),
+			}
+		}
+	}
+	fn poll_write_vectored(
+		self: Pin<&mut Self>,
+		cx: &mut Context<'_>,
+		bufs: &[io::IoSlice<'_>],
+	) -> Poll<io::Result<usize>> {
+		loop {
+			match self.0.try_write_vectored(bufs) {
+				Err(e) if e.kind() == WouldBlock => ready!(self.0.poll_write_ready(cx)?),
+				els => return Poll::Ready(els),
+			}
+		}
+	}
+	#[inline]
+	fn is_write_vectored(&self) -> bool {
+		true
+	}
+	/// Does nothing, since FIFO files are not buffered in userspace.
+	#[inline]
+	fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+		Poll::Ready(Ok(()))
+	}
+	/// Does nothing, since FIFO files cannot be shut down without closing them. Drop the sender to
+	/// signal end-of-file to the receiver.
+	#[inline]
+	fn poll_shutdown(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+		Poll::Ready(Ok(()))
+	}
+}
+multimacro! {
+	Sender,
+	forward_rbv(pipe::Sender, &),
+	forward_debug,
+	forward_as_handle(unix),
+	derive_tokio_mut_write,
+	derive_asraw(unix),
+}
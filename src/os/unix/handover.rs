@@ -0,0 +1,252 @@
+//! Handover of live local socket listeners from one process to another, such as from the old
+//! version of a server to the new one during an upgrade, without ever closing the listening socket.
+//!
+//! Both processes can accept connections while they share the socket, so the old process can stop
+//! accepting whenever it sees fit after the handover: clients that connect in the meantime wait in
+//! the backlog of the socket instead of failing to connect. [Name
+//! reclamation](crate::local_socket::Listener#name-reclamation) moves along with the socket – the
+//! old process no longer unlinks the socket file when its listener is dropped, and the new process
+//! does instead if the old one would have.
+//!
+//! The listener can be handed over in two ways:
+//! -	[`send_listener()`] and [`recv_listener()`] pass it over a connected local socket, which works
+//!   	between unrelated processes, such as a new version started by a service manager which finds
+//!   	the old one through a control socket;
+//! -	[`inherit_listener()`] and [`adopt_inherited_listener()`] pass it to a child process, such as
+//!   	one the old process re-executes itself as.
+//!
+//! Whether the socket is in nonblocking mode is shared by both processes, and the handover leaves
+//! it as the old process had it, so that a listener the old process keeps using, such as with
+//! Tokio, isn't disturbed. The listener received by the new process starts out in blocking mode
+//! regardless: if the socket is in nonblocking mode, accepting waits for a connection to arrive
+//! instead of failing with [`WouldBlock`](io::ErrorKind::WouldBlock). Switching either listener to
+//! either mode later on does change the mode for both processes.
+//!
+//! # Examples
+//! ```no_run
+//! use interprocess::{
+//! 	local_socket::{prelude::*, Listener, Stream, ToFsName},
+//! 	os::unix::handover,
+//! };
+//!
+//! // Old process, when the new one connects to its control socket:
+//! # fn old(mut listener: Listener, control: Listener) -> std::io::Result<()> {
+//! let conn = control.accept()?;
+//! handover::send_listener(&mut listener, &conn)?;
+//! // Finish handling the connections that have already been accepted, then exit.
+//! # Ok(()) }
+//!
+//! // New process:
+//! let conn = Stream::connect("/run/example/control.sock".to_fs_name()?)?;
+//! let listener = handover::recv_listener(&conn)?;
+//! for conn in listener.incoming() {
+//! 	// ...
+//! 	# drop(conn);
+//! }
+//! # std::io::Result::<()>::Ok(())
+//! ```
+
+use super::{c_wrappers, socket_activation::check_listener, unixprelude::*, Inheritable};
+use crate::{
+	local_socket::{Listener, Name, Stream, ToFsName},
+	os::unix::uds_local_socket::{FileId, Listener as UdsListener},
+};
+use std::{
+	env,
+	ffi::{OsStr, OsString},
+	io::{self, prelude::*},
+	os::unix::ffi::{OsStrExt, OsStringExt},
+	path::PathBuf,
+	process::Command,
+};
+
+/// Sent in the first byte of the message if the receiving process is to take over name
+/// reclamation, in which case a length-prefixed name follows.
+const RECLAIM: u8 = 1;
+/// Same as `RECLAIM`, but with the device and inode numbers of the socket file following the name.
+const RECLAIM_FILE: u8 = 2;
+const NO_RECLAIM: u8 = 0;
+/// A flag byte and a 32-bit little-endian length.
+const HEADER_LEN: usize = 5;
+/// Far above what any socket address can hold.
+const MAX_NAME_LEN: u32 = 4096;
+
+/// A name to reclaim and the socket file it's expected to refer to.
+type Reclaim = (Name<'static>, Option<FileId>);
+
+/// Sends the listener to the process on the other end of `conn`, which is to receive it with
+/// [`recv_listener()`].
+///
+/// The listener remains usable in this process, but no longer performs name reclamation once this
+/// function succeeds.
+///
+/// # Errors
+/// Fails if sending fails, in which case name reclamation is left as it was.
+pub fn send_listener(listener: &mut Listener, conn: &Stream) -> io::Result<()> {
+	let listener = uds_listener(listener);
+	let reclaim = listener.take_reclaim();
+	let rslt = send(listener.as_fd(), reclaim.as_ref(), conn);
+	if rslt.is_err() {
+		listener.set_reclaim(reclaim);
+	}
+	rslt
+}
+fn send(fd: BorrowedFd<'_>, reclaim: Option<&Reclaim>, conn: &Stream) -> io::Result<()> {
+	let mut msg = Vec::with_capacity(HEADER_LEN);
+	match reclaim {
+		Some((name, file)) => {
+			let raw = name.raw().as_bytes();
+			let len = u32::try_from(raw.len())
+				.ok()
+				.filter(|len| *len <= MAX_NAME_LEN)
+				.ok_or_else(|| invalid_data("name is too long"))?;
+			msg.push(if file.is_some() {
+				RECLAIM_FILE
+			} else {
+				RECLAIM
+			});
+			msg.extend_from_slice(&len.to_le_bytes());
+			msg.extend_from_slice(raw);
+			if let Some((dev, ino)) = file {
+				msg.extend_from_slice(&dev.to_le_bytes());
+				msg.extend_from_slice(&ino.to_le_bytes());
+			}
+		}
+		None => msg.extend_from_slice(&[NO_RECLAIM, 0, 0, 0, 0]),
+	}
+	let sent = c_wrappers::send_with_fds(conn.as_fd(), &msg, &[fd])?;
+	let mut conn = conn;
+	conn.write_all(msg.get(sent..).unwrap_or_default())
+}
+
+/// Receives a listener sent with [`send_listener()`] by the process on the other end of `conn`,
+/// taking over name reclamation if the sending process was performing it.
+///
+/// # Errors
+/// Fails if receiving fails, if the message is malformed or if the file descriptor received isn't
+/// a listening Unix domain stream socket.
+pub fn recv_listener(conn: &Stream) -> io::Result<Listener> {
+	let mut header = [0; HEADER_LEN];
+	let (received, mut fds) = c_wrappers::recv_with_fds(conn.as_fd(), &mut header, 1)?;
+	if received == 0 {
+		return Err(io::ErrorKind::UnexpectedEof.into());
+	}
+	let fd = fds
+		.pop()
+		.ok_or_else(|| invalid_data("no listener was received"))?;
+	let mut conn = conn;
+	conn.read_exact(header.get_mut(received..).unwrap_or_default())?;
+
+	let [flag, len @ ..] = header;
+	let len = u32::from_le_bytes(len);
+	let reclaim = match flag {
+		RECLAIM | RECLAIM_FILE if len <= MAX_NAME_LEN => {
+			let mut raw = vec![0; len as usize];
+			conn.read_exact(&mut raw)?;
+			let name = PathBuf::from(OsString::from_vec(raw)).to_fs_name()?;
+			let file = if flag == RECLAIM_FILE {
+				let mut dev = [0; 8];
+				let mut ino = [0; 8];
+				conn.read_exact(&mut dev)?;
+				conn.read_exact(&mut ino)?;
+				Some((u64::from_le_bytes(dev), u64::from_le_bytes(ino)))
+			} else {
+				None
+			};
+			Some((name, file))
+		}
+		NO_RECLAIM => None,
+		_ => return Err(invalid_data("malformed listener handover message")),
+	};
+	adopt(fd, reclaim)
+}
+
+/// Makes the listener available to the child process spawned by `command`, as
+/// [`Inheritable::inherit_env()`] does, and hands name reclamation over to it.
+///
+/// The child process is to take the listener with [`adopt_inherited_listener()`]. The name to
+/// reclaim, if any, is stored in another environment variable, whose name is that of `env_var`
+/// followed by `_RECLAIM`, and the device and inode numbers of its socket file in one whose name
+/// ends with `_RECLAIM_FILE`.
+///
+/// The listener remains usable in this process, but no longer performs name reclamation once this
+/// function succeeds, even if the child process is never spawned.
+///
+/// # Errors
+/// Same as [`Inheritable::inherit_env()`].
+pub fn inherit_listener(
+	listener: &mut Listener,
+	command: &mut Command,
+	env_var: impl AsRef<OsStr>,
+) -> io::Result<()> {
+	let env_var = env_var.as_ref();
+	listener.inherit_env(command, env_var)?;
+	let (name, file) = uds_listener(listener).take_reclaim().unzip();
+	match name {
+		Some(name) => command.env(var_with_suffix(env_var, "_RECLAIM"), name.raw()),
+		None => command.env_remove(var_with_suffix(env_var, "_RECLAIM")),
+	};
+	match file.flatten() {
+		Some((dev, ino)) => command.env(
+			var_with_suffix(env_var, "_RECLAIM_FILE"),
+			format!("{dev}:{ino}"),
+		),
+		None => command.env_remove(var_with_suffix(env_var, "_RECLAIM_FILE")),
+	};
+	Ok(())
+}
+
+/// Takes ownership of a listener inherited from the parent process with [`inherit_listener()`],
+/// taking over name reclamation if the parent process was performing it.
+///
+/// # Errors
+/// Fails if the environment variable is malformed, if the file descriptor wasn't inherited or if
+/// it isn't a listening Unix domain stream socket.
+///
+/// # Safety
+/// See [`Inheritable::from_inherited()`].
+pub unsafe fn adopt_inherited_listener(env_var: impl AsRef<OsStr>) -> io::Result<Listener> {
+	let env_var = env_var.as_ref();
+	// SAFETY: ensured by the caller.
+	let fd = OwnedFd::from(unsafe { Listener::from_inherited_env(env_var) }?);
+	let Some(name) = env::var_os(var_with_suffix(env_var, "_RECLAIM")) else {
+		return adopt(fd, None);
+	};
+	let name = PathBuf::from(name).to_fs_name()?;
+	let file = env::var_os(var_with_suffix(env_var, "_RECLAIM_FILE"))
+		.map(|file| parse_file_id(&file))
+		.transpose()?;
+	adopt(fd, Some((name, file)))
+}
+
+fn parse_file_id(file: &OsStr) -> io::Result<FileId> {
+	file.to_str()
+		.and_then(|file| file.split_once(':'))
+		.and_then(|(dev, ino)| Some((dev.parse().ok()?, ino.parse().ok()?)))
+		.ok_or_else(|| invalid_data("malformed socket file identity"))
+}
+
+fn adopt(fd: OwnedFd, reclaim: Option<Reclaim>) -> io::Result<Listener> {
+	let (listener, _) = check_listener(fd)?;
+	// The mode of the socket is shared with the sending process and is left alone, since the
+	// listener deals with it being in nonblocking mode by itself.
+	let mut listener = UdsListener::from(OwnedFd::from(listener));
+	listener.set_reclaim(reclaim);
+	Ok(Listener::UdSocket(listener))
+}
+
+fn uds_listener(listener: &mut Listener) -> &mut UdsListener {
+	match listener {
+		Listener::UdSocket(l) => l,
+	}
+}
+
+fn var_with_suffix(env_var: &OsStr, suffix: &str) -> OsString {
+	let mut var = env_var.to_owned();
+	var.push(suffix);
+	var
+}
+
+fn invalid_data(msg: &str) -> io::Error {
+	io::Error::new(io::ErrorKind::InvalidData, msg)
+}
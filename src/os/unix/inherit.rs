@@ -0,0 +1,194 @@
+use super::{c_wrappers, fifo_file, unixprelude::*};
+use crate::{local_socket, unnamed_pipe, Sealed};
+use std::{env, ffi::OsStr, io, os::unix::process::CommandExt, process::Command};
+
+/// The lowest file descriptor number used for staging file descriptors to be inherited.
+///
+/// Staging the file descriptors this high up prevents `dup2()` calls in the child from clobbering
+/// file descriptors staged for other objects, as long as the target file descriptor numbers are
+/// below this value.
+const STAGING_FD_MIN: c_int = 64;
+
+/// Objects which can be inherited by child processes.
+///
+/// Passing an object to a child process on Unix means making sure that its file descriptor is not
+/// closed by `exec` (i.e. that it doesn't have the `FD_CLOEXEC` flag), telling the child which
+/// file descriptor number it has and reconstructing an object of the right type from that file
+/// descriptor in the child. This trait does all three:
+/// -	[`.inherit_at()`](Self::inherit_at) makes the object available to a child process spawned
+///   	by a [`Command`] at a given file descriptor number, without affecting the file descriptor in
+///   	the parent process or leaking it into any other child processes;
+/// -	[`.inherit_env()`](Self::inherit_env) does the same, but picks the file descriptor number
+///   	automatically and stores it in an environment variable of the child process;
+/// -	[`from_inherited()`](Self::from_inherited) and
+///   	[`from_inherited_env()`](Self::from_inherited_env) take ownership of the inherited file
+///   	descriptor in the child process.
+///
+/// Preparing an object for inheritance duplicates its file descriptor and moves the duplicate into
+/// the `Command` until the `Command` is dropped, so the original object remains usable in the
+/// parent process. Note that dropping a [local socket listener](local_socket::Listener) in the
+/// parent process still performs [name reclamation](local_socket::Listener#name-reclamation), which
+/// is to be disabled if the child process is to keep using the listener.
+///
+/// # Examples
+/// ```no_run
+/// use interprocess::{local_socket::{prelude::*, Stream}, os::unix::Inheritable};
+/// use std::{io::prelude::*, process::Command};
+///
+/// // Parent process
+/// let (ours, theirs) = Stream::pair()?;
+/// let mut command = Command::new("/usr/libexec/helper");
+/// theirs.inherit_env(&mut command, "HELPER_CONN_FD")?;
+/// let child = command.spawn()?;
+/// drop((command, theirs));
+///
+/// // Child process
+/// let mut conn = unsafe { Stream::from_inherited_env("HELPER_CONN_FD")? };
+/// conn.write_all(b"Hello from the child!\n")?;
+/// # std::io::Result::<()>::Ok(())
+/// ```
+#[allow(private_bounds)]
+pub trait Inheritable: AsFd + Sized + Sealed {
+	/// Makes the object available to the child process spawned by `command` as the file descriptor
+	/// `target_fd`, replacing whichever file descriptor the child would otherwise have had there.
+	///
+	/// Target file descriptors from 0 to 2 replace the standard input, output and error of the
+	/// child process respectively. Targets of 64 and above may clobber file descriptors of other
+	/// objects being inherited.
+	fn inherit_at(&self, command: &mut Command, target_fd: RawFd) -> io::Result<()> {
+		if target_fd < 0 {
+			return Err(io::Error::new(
+				io::ErrorKind::InvalidInput,
+				"target file descriptor number must not be negative",
+			));
+		}
+		inherit(self.as_fd(), command, Some(target_fd)).map(|_| ())
+	}
+	/// Makes the object available to the child process spawned by `command` and sets the
+	/// environment variable `env_var` of the child process to the number of its file descriptor,
+	/// which is also returned.
+	///
+	/// The child process can then use [`from_inherited_env()`](Self::from_inherited_env) to
+	/// reconstruct the object.
+	fn inherit_env(&self, command: &mut Command, env_var: impl AsRef<OsStr>) -> io::Result<RawFd> {
+		let fd = inherit(self.as_fd(), command, None)?;
+		command.env(env_var, fd.to_string());
+		Ok(fd)
+	}
+
+	/// Takes ownership of a file descriptor inherited from the parent process, checking that it is
+	/// open and setting the `FD_CLOEXEC` flag on it so that it doesn't leak further.
+	///
+	/// # Safety
+	/// The file descriptor must have been inherited from an object of the same type, and must not
+	/// be owned by anything else in the process.
+	unsafe fn from_inherited(fd: RawFd) -> io::Result<Self>;
+	/// Takes ownership of a file descriptor inherited from the parent process, whose number is
+	/// stored in the environment variable `env_var` as done by
+	/// [`.inherit_env()`](Self::inherit_env).
+	///
+	/// # Safety
+	/// See [`from_inherited()`](Self::from_inherited).
+	unsafe fn from_inherited_env(env_var: impl AsRef<OsStr>) -> io::Result<Self> {
+		let fd = fd_from_env(env_var.as_ref())?;
+		unsafe { Self::from_inherited(fd) }
+	}
+}
+
+/// Stages a duplicate of `fd` for inheritance by the child process, returning the number it will
+/// have in the child.
+fn inherit(
+	fd: BorrowedFd<'_>,
+	command: &mut Command,
+	target_fd: Option<RawFd>,
+) -> io::Result<RawFd> {
+	let staged = stage_fd(fd)?;
+	let target_fd = target_fd.unwrap_or_else(|| staged.as_raw_fd());
+	// SAFETY: fcntl() and dup2() are async-signal-safe, and so is constructing an io::Error from
+	// errno.
+	unsafe {
+		command.pre_exec(move || {
+			// The closure owns the staged file descriptor, keeping it open until the command is
+			// dropped.
+			let staged = staged.as_raw_fd();
+			let success = if staged == target_fd {
+				let flags = libc::fcntl(staged, libc::F_GETFD, 0);
+				flags != -1 && libc::fcntl(staged, libc::F_SETFD, flags & !libc::FD_CLOEXEC) != -1
+			} else {
+				// The duplicate created by dup2() never has FD_CLOEXEC set.
+				libc::dup2(staged, target_fd) != -1
+			};
+			ok_or_errno!(success => ())
+		})
+	};
+	Ok(target_fd)
+}
+
+fn stage_fd(fd: BorrowedFd<'_>) -> io::Result<OwnedFd> {
+	// F_DUPFD_CLOEXEC sets the flag atomically, so that a concurrent fork() in another thread
+	// cannot leak the staged file descriptor into an unrelated child.
+	match unsafe { c_wrappers::fcntl_int(fd, libc::F_DUPFD_CLOEXEC, STAGING_FD_MIN) } {
+		// SAFETY: we just created the file descriptor, meaning that it's guaranteed not to be in
+		// use elsewhere.
+		Ok(staged) => Ok(unsafe { OwnedFd::from_raw_fd(staged) }),
+		// The resource limit on file descriptors is lower than STAGING_FD_MIN.
+		Err(e) if e.raw_os_error() == Some(libc::EINVAL) => c_wrappers::duplicate_fd(fd),
+		Err(e) => Err(e),
+	}
+}
+
+fn fd_from_env(env_var: &OsStr) -> io::Result<RawFd> {
+	let Some(val) = env::var_os(env_var) else {
+		return Err(io::Error::new(
+			io::ErrorKind::NotFound,
+			format!("environment variable {env_var:?} is not set"),
+		));
+	};
+	val.to_str().and_then(|v| v.parse().ok()).ok_or_else(|| {
+		io::Error::new(
+			io::ErrorKind::InvalidData,
+			format!("environment variable {env_var:?} is not a file descriptor number"),
+		)
+	})
+}
+
+/// Checks and adopts an inherited file descriptor.
+///
+/// # Safety
+/// See `Inheritable::from_inherited()`.
+pub(super) unsafe fn adopt_inherited(fd: RawFd) -> io::Result<OwnedFd> {
+	let open = fd >= 0 && unsafe { libc::fcntl(fd, libc::F_GETFD, 0) } != -1;
+	if !open {
+		return Err(io::Error::new(
+			io::ErrorKind::NotFound,
+			format!("file descriptor {fd} was not inherited"),
+		));
+	}
+	let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+	c_wrappers::set_cloexec(fd.as_fd())?;
+	Ok(fd)
+}
+
+macro_rules! impl_inheritable {
+	($($ty:ty),+ $(,)?) => {$(
+		impl Inheritable for $ty {
+			#[inline]
+			unsafe fn from_inherited(fd: RawFd) -> io::Result<Self> {
+				unsafe { adopt_inherited(fd) }.map(Self::from)
+			}
+		}
+	)+};
+}
+impl_inheritable! {
+	local_socket::Stream,
+	local_socket::Listener,
+	unnamed_pipe::Recver,
+	unnamed_pipe::Sender,
+	fifo_file::Recver,
+	fifo_file::Sender,
+}
+
+impl Sealed for unnamed_pipe::Recver {}
+impl Sealed for unnamed_pipe::Sender {}
+impl Sealed for fifo_file::Recver {}
+impl Sealed for fifo_file::Sender {}
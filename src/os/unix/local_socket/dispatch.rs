@@ -9,9 +9,18 @@ pub fn bind(name: Name<'_>) -> io::Result<Listener> {
 
 #[inline]
 pub fn bind_without_name_reclamation(name: Name<'_>) -> io::Result<Listener> {
-	uds_impl::Listener::bind(name).map(Listener::from)
+	uds_impl::Listener::bind_without_name_reclamation(name).map(Listener::from)
+}
+
+#[inline]
+pub fn bind_replacing(name: Name<'_>) -> io::Result<Listener> {
+	uds_impl::Listener::bind_replacing(name).map(Listener::from)
 }
 
 pub fn connect(name: Name<'_>) -> io::Result<Stream> {
 	uds_impl::Stream::connect(name).map(Stream::from)
 }
+
+pub fn pair() -> io::Result<(Stream, Stream)> {
+	uds_impl::Stream::pair().map(|(s1, s2)| (s1.into(), s2.into()))
+}
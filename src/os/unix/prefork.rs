@@ -0,0 +1,407 @@
+//! Pre-fork servers, in which a supervisor process shares one local socket listener with a number
+//! of worker processes that it forks, each of which accepts connections on it.
+//!
+//! Running every worker in a process of its own isolates them from each other – a worker that
+//! crashes or leaks memory doesn't take the others down with it – while clients keep connecting to
+//! the one name the listener is bound to. The kernel hands every incoming connection to one of the
+//! workers that are waiting in `.accept()`.
+//!
+//! A [`Supervisor`] forks the workers, replaces the ones that exit and, once
+//! [shut down](Supervisor::shutdown_handle), terminates the remaining ones. Workers exit without
+//! dropping their copy of the listener, even if the worker function panics, which leaves [name
+//! reclamation](crate::local_socket::Listener#name-reclamation) to the supervisor process alone.
+//!
+//! # Examples
+//! ```no_run
+//! use interprocess::{
+//! 	local_socket::{prelude::*, Listener, ToFsName},
+//! 	os::unix::prefork::{PreforkOptions, Supervisor},
+//! };
+//! use std::{io::prelude::*, thread};
+//!
+//! let listener = Listener::bind("/run/example.sock".to_fs_name()?)?;
+//! let options = PreforkOptions::new().workers(thread::available_parallelism()?.get());
+//! let supervisor = Supervisor::new(&listener)?;
+//! // SAFETY: the program has no other threads.
+//! unsafe {
+//! 	supervisor.run(&options, |listener, _index| {
+//! 		for conn in listener.incoming() {
+//! 			let mut conn = conn?;
+//! 			conn.write_all(b"Hello from a worker!\n")?;
+//! 		}
+//! 		Ok(())
+//! 	})?;
+//! }
+//! // Dropping the listener here, in the supervisor, deletes the socket file.
+//! # std::io::Result::<()>::Ok(())
+//! ```
+
+use super::{c_wrappers, unixprelude::*};
+use crate::local_socket::{Listener, ShutdownHandle, ShutdownState};
+use std::{
+	fmt::{self, Debug, Formatter},
+	io::{self, prelude::*},
+	os::unix::process::ExitStatusExt,
+	panic::{self, AssertUnwindSafe},
+	process::ExitStatus,
+	sync::Arc,
+	thread,
+	time::{Duration, Instant},
+};
+
+type Callback = Arc<dyn Fn(&WorkerExit) + Send + Sync>;
+type ForkFailureCallback = Arc<dyn Fn(&ForkFailure<'_>) + Send + Sync>;
+
+/// How often the supervisor checks whether any of the workers have exited.
+const CHECK_INTERVAL: Duration = Duration::from_millis(100);
+/// How often the supervisor checks whether the workers have exited while shutting down.
+const TERMINATE_CHECK_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Options for [`Supervisor::run()`].
+#[derive(Clone)]
+#[non_exhaustive]
+pub struct PreforkOptions {
+	/// The number of worker processes to keep running. The number of CPUs, as reported by
+	/// [`available_parallelism()`](std::thread::available_parallelism), is a good fit for servers
+	/// which are bound by CPU time.
+	///
+	/// The default value is 1.
+	pub workers: usize,
+	/// How long to wait before replacing a worker that has exited, so that a worker which keeps
+	/// failing right after starting doesn't make the supervisor fork in a tight loop.
+	///
+	/// The default value is 100 milliseconds.
+	pub respawn_delay: Duration,
+	/// How long the workers are given to exit after being sent `SIGTERM` on shutdown, after which
+	/// the ones that are still running are sent `SIGKILL`.
+	///
+	/// The default value is 30 seconds.
+	pub grace_period: Duration,
+	/// Function to call in the supervisor process every time a worker exits, including when the
+	/// supervisor terminates it while shutting down.
+	///
+	/// The default value is `None`.
+	pub on_worker_exit: Option<Callback>,
+	/// Function to call in the supervisor process every time forking a worker fails because the
+	/// system is temporarily out of resources (`EAGAIN` or `ENOMEM`), in which case forking it is
+	/// tried again after the [respawn delay](#structfield.respawn_delay).
+	///
+	/// The default value is `None`.
+	pub on_fork_failure: Option<ForkFailureCallback>,
+}
+impl PreforkOptions {
+	/// Creates options with default values.
+	#[inline]
+	pub const fn new() -> Self {
+		Self {
+			workers: 1,
+			respawn_delay: Duration::from_millis(100),
+			grace_period: Duration::from_secs(30),
+			on_worker_exit: None,
+			on_fork_failure: None,
+		}
+	}
+	/// Sets the number of worker processes.
+	///
+	/// See the [associated field](#structfield.workers) for more.
+	#[must_use = "this is not an in-place operation"]
+	#[inline]
+	pub fn workers(mut self, workers: usize) -> Self {
+		self.workers = workers;
+		self
+	}
+	/// Sets the wait before replacing a worker.
+	///
+	/// See the [associated field](#structfield.respawn_delay) for more.
+	#[must_use = "this is not an in-place operation"]
+	#[inline]
+	pub fn respawn_delay(mut self, respawn_delay: Duration) -> Self {
+		self.respawn_delay = respawn_delay;
+		self
+	}
+	/// Sets how long the workers are given to exit on shutdown.
+	///
+	/// See the [associated field](#structfield.grace_period) for more.
+	#[must_use = "this is not an in-place operation"]
+	#[inline]
+	pub fn grace_period(mut self, grace_period: Duration) -> Self {
+		self.grace_period = grace_period;
+		self
+	}
+	/// Sets the function to call when a worker exits.
+	///
+	/// See the [associated field](#structfield.on_worker_exit) for more.
+	#[must_use = "this is not an in-place operation"]
+	#[inline]
+	pub fn on_worker_exit(
+		mut self,
+		on_worker_exit: impl Fn(&WorkerExit) + Send + Sync + 'static,
+	) -> Self {
+		self.on_worker_exit = Some(Arc::new(on_worker_exit));
+		self
+	}
+	/// Sets the function to call when forking a worker fails.
+	///
+	/// See the [associated field](#structfield.on_fork_failure) for more.
+	#[must_use = "this is not an in-place operation"]
+	#[inline]
+	pub fn on_fork_failure(
+		mut self,
+		on_fork_failure: impl Fn(&ForkFailure<'_>) + Send + Sync + 'static,
+	) -> Self {
+		self.on_fork_failure = Some(Arc::new(on_fork_failure));
+		self
+	}
+}
+impl Default for PreforkOptions {
+	#[inline]
+	fn default() -> Self {
+		Self::new()
+	}
+}
+impl Debug for PreforkOptions {
+	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+		f.debug_struct("PreforkOptions")
+			.field("workers", &self.workers)
+			.field("respawn_delay", &self.respawn_delay)
+			.field("grace_period", &self.grace_period)
+			.field("on_worker_exit", &self.on_worker_exit.is_some())
+			.field("on_fork_failure", &self.on_fork_failure.is_some())
+			.finish()
+	}
+}
+
+/// Information about a worker process that has exited, passed to the
+/// [callback](PreforkOptions::on_worker_exit) of a [`Supervisor`].
+#[derive(Debug)]
+#[non_exhaustive]
+pub struct WorkerExit {
+	/// The index of the worker, from 0 up to the number of workers, which its replacement gets as
+	/// well.
+	pub index: usize,
+	/// The process ID of the worker.
+	pub pid: u32,
+	/// How the worker exited. The exit code is 0 if the worker function returned `Ok`, 1 if it
+	/// returned `Err` and 101 if it panicked.
+	pub status: ExitStatus,
+}
+
+/// Information about a failure to fork a worker process, passed to the
+/// [callback](PreforkOptions::on_fork_failure) of a [`Supervisor`].
+#[derive(Debug)]
+#[non_exhaustive]
+pub struct ForkFailure<'a> {
+	/// The index of the worker that was to be forked.
+	pub index: usize,
+	/// The error forking failed with.
+	pub error: &'a io::Error,
+}
+
+/// The supervisor process of a pre-fork server, which forks workers that accept connections on a
+/// shared listener.
+///
+/// See the [module-level documentation](self) for more.
+pub struct Supervisor<'l> {
+	listener: &'l Listener,
+	shutdown: Arc<ShutdownState>,
+}
+impl<'l> Supervisor<'l> {
+	/// Creates a supervisor for the given listener.
+	///
+	/// # Errors
+	/// Fails if the socket pair used to wake the supervisor up on shutdown cannot be created.
+	pub fn new(listener: &'l Listener) -> io::Result<Self> {
+		Ok(Self {
+			listener,
+			shutdown: Arc::new(ShutdownState::new()?),
+		})
+	}
+
+	/// Returns a handle which makes [`.run()`](Self::run) terminate the workers and return, such as
+	/// from a thread which handles signals. Shutting down before `.run()` is called makes it return
+	/// right away.
+	#[inline]
+	pub fn shutdown_handle(&self) -> ShutdownHandle {
+		ShutdownHandle(Arc::clone(&self.shutdown))
+	}
+
+	/// Forks the workers, each of which calls `worker` with the listener and its index, then keeps
+	/// replacing the ones that exit until the supervisor is shut down.
+	///
+	/// Workers exit as soon as the worker function returns or panics, without running any
+	/// destructors or returning from this function. On shutdown, the remaining workers are sent
+	/// `SIGTERM`, which they may handle to finish serving the connections they have accepted, and
+	/// are waited for until the [grace period](PreforkOptions::grace_period) runs out, after which
+	/// the ones that are left are killed with `SIGKILL`.
+	///
+	/// Every worker is passed a listener of its own for the shared socket, which starts out without
+	/// a [shutdown handle](crate::local_socket::traits::Listener::shutdown_handle) even if the
+	/// supervisor's listener has one. A worker can thus stop accepting connections on `SIGTERM` by
+	/// shutting down its listener, which affects neither the other workers nor the supervisor.
+	///
+	/// # Errors
+	/// Fails if checking on a worker or waiting for the shutdown fails, or if forking a worker
+	/// fails for any other reason than the system being temporarily out of resources. The workers
+	/// that are running at the time are terminated as on shutdown.
+	///
+	/// # Safety
+	/// A worker process only has a copy of the thread which calls this function, so no other thread
+	/// may be holding anything the worker function ends up waiting for, such as a lock, at the time
+	/// a worker is forked. That includes the lock of the memory allocator, which is why the
+	/// simplest way to uphold this is to not have any other threads running, or have only ones
+	/// which are blocked in system calls, such as a thread waiting for signals.
+	pub unsafe fn run(
+		&self,
+		options: &PreforkOptions,
+		mut worker: impl FnMut(&Listener, usize) -> io::Result<()>,
+	) -> io::Result<()> {
+		let mut slots = vec![Slot::Vacant(Instant::now()); options.workers];
+		// SAFETY: ensured by the caller.
+		let rslt = unsafe { self.supervise(options, &mut slots, &mut worker) };
+		self.terminate(options, &mut slots);
+		rslt
+	}
+
+	unsafe fn supervise(
+		&self,
+		options: &PreforkOptions,
+		slots: &mut [Slot],
+		worker: &mut dyn FnMut(&Listener, usize) -> io::Result<()>,
+	) -> io::Result<()> {
+		while !self.shutdown.is_shut_down() {
+			let now = Instant::now();
+			let mut wait = CHECK_INTERVAL;
+			for (index, slot) in slots.iter_mut().enumerate() {
+				if let Slot::Running(pid) = *slot {
+					if let Some(status) = c_wrappers::waitpid(pid, false)? {
+						report(options, index, pid, status);
+						let respawn_at = now.checked_add(options.respawn_delay);
+						*slot = Slot::Vacant(respawn_at.unwrap_or(now));
+					}
+				}
+				if let Slot::Vacant(respawn_at) = *slot {
+					if respawn_at <= now {
+						// SAFETY: ensured by the caller.
+						match unsafe { self.spawn(index, worker) } {
+							Ok(pid) => *slot = Slot::Running(pid),
+							Err(e) if is_temporary(&e) => {
+								if let Some(on_fork_failure) = &options.on_fork_failure {
+									on_fork_failure(&ForkFailure { index, error: &e });
+								}
+								let retry_at = now.checked_add(options.respawn_delay);
+								*slot = Slot::Vacant(retry_at.unwrap_or(now));
+								wait = wait.min(options.respawn_delay);
+							}
+							Err(e) => return Err(e),
+						}
+					} else {
+						wait = wait.min(respawn_at.saturating_duration_since(now));
+					}
+				}
+			}
+			c_wrappers::poll_readable(&[self.shutdown.wake_fd()], c_wrappers::poll_timeout(wait))?;
+		}
+		Ok(())
+	}
+
+	unsafe fn spawn(
+		&self,
+		index: usize,
+		worker: &mut dyn FnMut(&Listener, usize) -> io::Result<()>,
+	) -> io::Result<pid_t> {
+		// Output left in the buffer would otherwise be written out by the worker as well.
+		let _ = io::stdout().flush();
+		// SAFETY: ensured by the caller.
+		if let Some(pid) = unsafe { c_wrappers::fork() }? {
+			return Ok(pid);
+		}
+		let rslt = panic::catch_unwind(AssertUnwindSafe(|| {
+			// The copy inherited from the supervisor shares its shutdown state, if it has one.
+			let Listener::UdSocket(listener) = self.listener;
+			let listener = Listener::UdSocket(listener.detached_copy()?);
+			worker(&listener, index)
+		}));
+		let code = match rslt {
+			Ok(Ok(())) => 0,
+			Ok(Err(..)) => 1,
+			Err(..) => 101,
+		};
+		let _ = io::stdout().flush();
+		// SAFETY: exiting without running the exit handlers registered by the supervisor, which
+		// are not meant to run in the worker.
+		unsafe { libc::_exit(code) }
+	}
+
+	fn terminate(&self, options: &PreforkOptions, slots: &mut [Slot]) {
+		for slot in slots.iter() {
+			if let Slot::Running(pid) = *slot {
+				let _ = c_wrappers::kill(pid, libc::SIGTERM);
+			}
+		}
+		let start = Instant::now();
+		while reap(options, slots, false) {
+			let left = options.grace_period.saturating_sub(start.elapsed());
+			if left.is_zero() {
+				break;
+			}
+			thread::sleep(TERMINATE_CHECK_INTERVAL.min(left));
+		}
+		for slot in slots.iter() {
+			if let Slot::Running(pid) = *slot {
+				let _ = c_wrappers::kill(pid, libc::SIGKILL);
+			}
+		}
+		reap(options, slots, true);
+	}
+}
+
+/// Waits for the workers that have exited, or for all of them if `block` is `true`, returning
+/// whether any are still running.
+fn reap(options: &PreforkOptions, slots: &mut [Slot], block: bool) -> bool {
+	let mut running = false;
+	for (index, slot) in slots.iter_mut().enumerate() {
+		if let Slot::Running(pid) = *slot {
+			match c_wrappers::waitpid(pid, block) {
+				Ok(Some(status)) => report(options, index, pid, status),
+				Ok(None) => {
+					running = true;
+					continue;
+				}
+				// Not a child process to wait for anymore.
+				Err(..) => {}
+			}
+			*slot = Slot::Vacant(Instant::now());
+		}
+	}
+	running
+}
+
+/// Returns whether forking failed because the system is temporarily out of resources.
+fn is_temporary(error: &io::Error) -> bool {
+	matches!(error.raw_os_error(), Some(libc::EAGAIN | libc::ENOMEM))
+}
+
+fn report(options: &PreforkOptions, index: usize, pid: pid_t, status: c_int) {
+	if let Some(on_worker_exit) = &options.on_worker_exit {
+		on_worker_exit(&WorkerExit {
+			index,
+			pid: pid.unsigned_abs(),
+			status: ExitStatus::from_raw(status),
+		});
+	}
+}
+impl Debug for Supervisor<'_> {
+	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+		f.debug_struct("Supervisor")
+			.field("listener", self.listener)
+			.field("shut_down", &self.shutdown.is_shut_down())
+			.finish()
+	}
+}
+
+#[derive(Copy, Clone, Debug)]
+enum Slot {
+	Running(pid_t),
+	/// The worker is to be forked once the time comes.
+	Vacant(Instant),
+}
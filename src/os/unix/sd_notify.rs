@@ -0,0 +1,237 @@
+//! Notifications to a service manager, as done by systemd's `sd_notify()`.
+//!
+//! A service started by systemd with `Type=notify` or with a watchdog finds the address of a
+//! datagram socket in the `NOTIFY_SOCKET` environment variable, and sends newline-separated
+//! assignments such as `READY=1` to it to tell the service manager about its state. The address is
+//! either a filesystem path or, if it starts with `@`, a name in the abstract namespace of Linux.
+//!
+//! [`notify()`] sends a notification to the socket named by the environment variable, doing
+//! nothing if it isn't set, and [`Notifier`] does the same for any socket. The
+//! [`States`](State) that can be sent are listed in the `sd_notify(3)` manual page.
+//!
+//! # Examples
+//! ```no_run
+//! use interprocess::os::unix::sd_notify::{self, State};
+//!
+//! // Once the service has started up:
+//! sd_notify::notify(&[State::Ready, State::Status("Accepting connections")])?;
+//!
+//! // Every half of the watchdog interval, if the watchdog is enabled:
+//! if sd_notify::watchdog_interval().is_some() {
+//! 	sd_notify::notify(&[State::Watchdog])?;
+//! }
+//!
+//! // When shutting down:
+//! sd_notify::notify(&[State::Stopping])?;
+//! # std::io::Result::<()>::Ok(())
+//! ```
+
+use super::{c_wrappers, unixprelude::*};
+#[cfg(any(target_os = "linux", target_os = "android"))]
+use std::os::linux::net::SocketAddrExt;
+use std::{
+	env,
+	ffi::OsStr,
+	fmt::{self, Display, Formatter, Write as _},
+	io,
+	os::unix::{
+		ffi::OsStrExt,
+		net::{SocketAddr, UnixDatagram},
+	},
+	path::Path,
+	process,
+	time::Duration,
+};
+
+/// A state assignment sent to the service manager.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum State<'a> {
+	/// `READY=1`: the service has finished starting up.
+	Ready,
+	/// `RELOADING=1`: the service is reloading its configuration, and is to send `Ready` once it's
+	/// done.
+	Reloading,
+	/// `STOPPING=1`: the service is shutting down.
+	Stopping,
+	/// `STATUS=…`: a single line of text describing the state of the service.
+	Status(&'a str),
+	/// `ERRNO=…`: the error the service failed with.
+	Errno(i32),
+	/// `MAINPID=…`: the process ID of the main process of the service.
+	MainPid(u32),
+	/// `WATCHDOG=1`: resets the watchdog timer.
+	Watchdog,
+	/// `WATCHDOG=trigger`: tells the service manager that the service is unresponsive, as if the
+	/// watchdog timer had run out.
+	WatchdogTrigger,
+	/// `WATCHDOG_USEC=…`: changes the watchdog timeout, rounded down to microseconds.
+	WatchdogTimeout(Duration),
+	/// `FDSTORE=1`: the file descriptors sent along with the notification are to be kept by the
+	/// service manager. See [`Notifier::notify_with_fds()`].
+	FdStore,
+	/// `FDSTOREREMOVE=1`: the file descriptors with the name given by `FdName` are to be closed and
+	/// removed from the file descriptor store.
+	FdStoreRemove,
+	/// `FDNAME=…`: the name of the file descriptors being stored or removed.
+	FdName(&'a str),
+	/// Any other assignment, sent as is.
+	Custom(&'a str),
+}
+impl State<'_> {
+	/// Returns whether the assignment can be sent without spilling into another line.
+	fn is_valid(&self) -> bool {
+		match self {
+			Self::Status(s) | Self::FdName(s) | Self::Custom(s) => !s.contains('\n'),
+			_ => true,
+		}
+	}
+}
+impl Display for State<'_> {
+	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::Ready => f.write_str("READY=1"),
+			Self::Reloading => f.write_str("RELOADING=1"),
+			Self::Stopping => f.write_str("STOPPING=1"),
+			Self::Status(status) => write!(f, "STATUS={status}"),
+			Self::Errno(errno) => write!(f, "ERRNO={errno}"),
+			Self::MainPid(pid) => write!(f, "MAINPID={pid}"),
+			Self::Watchdog => f.write_str("WATCHDOG=1"),
+			Self::WatchdogTrigger => f.write_str("WATCHDOG=trigger"),
+			Self::WatchdogTimeout(timeout) => write!(f, "WATCHDOG_USEC={}", timeout.as_micros()),
+			Self::FdStore => f.write_str("FDSTORE=1"),
+			Self::FdStoreRemove => f.write_str("FDSTOREREMOVE=1"),
+			Self::FdName(name) => write!(f, "FDNAME={name}"),
+			Self::Custom(assignment) => f.write_str(assignment),
+		}
+	}
+}
+
+/// A datagram socket connected to a service manager's notification socket.
+#[derive(Debug)]
+pub struct Notifier {
+	socket: UnixDatagram,
+}
+impl Notifier {
+	/// Connects to the socket named by the `NOTIFY_SOCKET` environment variable, returning `None`
+	/// if it's unset or empty.
+	///
+	/// # Errors
+	/// See [`connect()`](Self::connect).
+	pub fn from_env() -> io::Result<Option<Self>> {
+		match env::var_os("NOTIFY_SOCKET") {
+			Some(addr) if !addr.is_empty() => Self::connect(addr).map(Some),
+			_ => Ok(None),
+		}
+	}
+	/// Connects to the socket at the given address, which is either a filesystem path or, if it
+	/// starts with `@`, a name in the abstract namespace.
+	///
+	/// # Errors
+	/// Fails with [`Unsupported`](io::ErrorKind::Unsupported) if the address is of any other kind
+	/// (such as `vsock:` addresses) or is abstract on a platform other than Linux. Also fails if the
+	/// socket cannot be connected to.
+	pub fn connect(addr: impl AsRef<OsStr>) -> io::Result<Self> {
+		let addr = addr.as_ref().as_bytes();
+		let addr = match addr.first() {
+			Some(b'/') => SocketAddr::from_pathname(Path::new(OsStr::from_bytes(addr)))?,
+			#[cfg(any(target_os = "linux", target_os = "android"))]
+			Some(b'@') => SocketAddr::from_abstract_name(addr.get(1..).unwrap_or_default())?,
+			_ => {
+				return Err(io::Error::new(
+					io::ErrorKind::Unsupported,
+					"unsupported notification socket address",
+				))
+			}
+		};
+		let socket = UnixDatagram::unbound()?;
+		socket.connect_addr(&addr)?;
+		Ok(Self { socket })
+	}
+
+	/// Sends the given state assignments in one notification.
+	///
+	/// # Errors
+	/// Fails with [`InvalidInput`](io::ErrorKind::InvalidInput) if one of the strings contains a
+	/// newline, or if sending fails.
+	pub fn notify(&self, states: &[State<'_>]) -> io::Result<()> {
+		self.notify_with_fds(states, &[])
+	}
+	/// Sends the given state assignments in one notification, along with the given file
+	/// descriptors, which the service manager duplicates.
+	///
+	/// Together with [`FdStore`](State::FdStore), this stores the file descriptors in the file
+	/// descriptor store of the service, from which the service manager passes them back to the
+	/// service the next time it's started, like [socket activation](super::socket_activation)
+	/// does.
+	///
+	/// # Errors
+	/// Same as [`.notify()`](Self::notify).
+	pub fn notify_with_fds(&self, states: &[State<'_>], fds: &[BorrowedFd<'_>]) -> io::Result<()> {
+		let msg = format_states(states)?;
+		let sent = c_wrappers::send_with_fds(self.socket.as_fd(), msg.as_bytes(), fds)?;
+		if sent != msg.len() {
+			return Err(io::Error::new(
+				io::ErrorKind::WriteZero,
+				"notification was truncated",
+			));
+		}
+		Ok(())
+	}
+}
+impl AsFd for Notifier {
+	#[inline]
+	fn as_fd(&self) -> BorrowedFd<'_> {
+		self.socket.as_fd()
+	}
+}
+
+fn format_states(states: &[State<'_>]) -> io::Result<String> {
+	let mut msg = String::new();
+	for state in states {
+		if !state.is_valid() {
+			return Err(io::Error::new(
+				io::ErrorKind::InvalidInput,
+				"notification strings must not contain newlines",
+			));
+		}
+		// Writing to a string doesn't fail.
+		let _ = writeln!(msg, "{state}");
+	}
+	Ok(msg)
+}
+
+/// Sends the given state assignments to the service manager, if the program was started by one
+/// which expects them, returning whether it was.
+///
+/// # Errors
+/// See [`Notifier::from_env()`] and [`Notifier::notify()`].
+pub fn notify(states: &[State<'_>]) -> io::Result<bool> {
+	notify_with_fds(states, &[])
+}
+/// Like [`notify()`], but also sends the given file descriptors. See
+/// [`Notifier::notify_with_fds()`].
+///
+/// # Errors
+/// See [`notify()`].
+pub fn notify_with_fds(states: &[State<'_>], fds: &[BorrowedFd<'_>]) -> io::Result<bool> {
+	match Notifier::from_env()? {
+		Some(notifier) => notifier.notify_with_fds(states, fds).map(|()| true),
+		None => Ok(false),
+	}
+}
+
+/// Returns the interval at which the service manager expects [`Watchdog`](State::Watchdog)
+/// notifications, or `None` if the watchdog isn't enabled for this process.
+///
+/// The interval is read from `WATCHDOG_USEC`, and is only returned if `WATCHDOG_PID` is either
+/// unset or the ID of this process. Notifications are best sent about twice as often as that.
+pub fn watchdog_interval() -> Option<Duration> {
+	if let Some(pid) = env::var_os("WATCHDOG_PID") {
+		if pid.to_str()?.parse::<u32>().ok()? != process::id() {
+			return None;
+		}
+	}
+	let usec = env::var_os("WATCHDOG_USEC")?.to_str()?.parse().ok()?;
+	(usec != 0).then(|| Duration::from_micros(usec))
+}
@@ -0,0 +1,211 @@
+//! Adoption of local socket listeners passed by a service manager, as done by systemd's socket
+//! activation.
+//!
+//! A socket-activated service is started with its listening sockets already open, at file
+//! descriptors 3 and onwards, and finds out about them through the following environment
+//! variables:
+//! -	`LISTEN_PID`, the process ID of the service, which tells the service that the variables are
+//!   	meant for it and weren't inherited from a parent process;
+//! -	`LISTEN_FDS`, the number of file descriptors passed;
+//! -	`LISTEN_FDNAMES`, optionally, the names of the file descriptors, separated by colons, which
+//!   	are set with `FileDescriptorName=` in the socket unit and default to the name of the unit.
+//!
+//! [`listeners()`] reads those, checks that every file descriptor is a listening Unix domain stream
+//! socket and wraps it into a [`Listener`], along with the [`Name`] it's bound to.
+//!
+//! # Examples
+//! ```no_run
+//! use interprocess::{local_socket::prelude::*, os::unix::socket_activation};
+//! use std::io;
+//!
+//! // SAFETY: nothing else in the program takes ownership of the file descriptors.
+//! let activated = unsafe { socket_activation::listeners()? };
+//! let Some(activated) = activated.into_iter().find(|a| a.fd_name() == Some("control")) else {
+//! 	return Err(io::Error::new(io::ErrorKind::NotFound, "not socket-activated"));
+//! };
+//! let listener = activated.into_listener();
+//! for conn in listener.incoming() {
+//! 	let conn = conn?;
+//! 	// ...
+//! 	# drop(conn);
+//! }
+//! # io::Result::<()>::Ok(())
+//! ```
+
+use super::{c_wrappers, inherit::adopt_inherited, unixprelude::*};
+#[cfg(feature = "tokio")]
+use crate::local_socket::tokio::Listener as TokioListener;
+use crate::local_socket::{Listener, Name, ToFsName};
+use std::{
+	env,
+	ffi::OsString,
+	io,
+	os::unix::net::{SocketAddr, UnixListener},
+	process,
+};
+#[cfg(any(target_os = "linux", target_os = "android"))]
+use {
+	crate::local_socket::ToNsName,
+	std::os::{linux::net::SocketAddrExt, unix::ffi::OsStringExt},
+};
+
+/// The file descriptor number of the first socket passed by the service manager.
+const LISTEN_FDS_START: RawFd = 3;
+
+/// A listening socket passed by the service manager, as returned by [`listeners()`].
+#[derive(Debug)]
+pub struct ActivatedListener {
+	listener: Listener,
+	name: Option<Name<'static>>,
+	fd_name: Option<String>,
+}
+impl ActivatedListener {
+	/// Returns a reference to the listener.
+	#[inline]
+	pub fn listener(&self) -> &Listener {
+		&self.listener
+	}
+	/// Returns the name the socket is bound to, or `None` if it's an unnamed socket.
+	#[inline]
+	pub fn name(&self) -> Option<Name<'_>> {
+		self.name.as_ref().map(Name::borrow)
+	}
+	/// Returns the name given to the file descriptor in `LISTEN_FDNAMES`, if the service manager
+	/// passed one.
+	#[inline]
+	pub fn fd_name(&self) -> Option<&str> {
+		self.fd_name.as_deref()
+	}
+	/// Unwraps the listener.
+	///
+	/// The listener doesn't perform [name reclamation](Listener#name-reclamation), since the
+	/// socket file belongs to the service manager.
+	#[inline]
+	pub fn into_listener(self) -> Listener {
+		self.listener
+	}
+	/// Converts the listener to a [Tokio one](TokioListener).
+	///
+	/// # Errors
+	/// Fails if the listener cannot be registered with the Tokio runtime, which also happens if
+	/// this is called outside of one.
+	#[cfg(feature = "tokio")]
+	#[cfg_attr(feature = "doc_cfg", doc(cfg(feature = "tokio")))]
+	pub fn into_tokio_listener(self) -> io::Result<TokioListener> {
+		TokioListener::try_from(OwnedFd::from(self.listener))
+	}
+}
+
+/// Takes ownership of the listening sockets passed by the service manager, in the order in which
+/// they were passed.
+///
+/// An empty list is returned if `LISTEN_PID` is unset or set to the ID of another process, which
+/// means that the program wasn't socket-activated. The environment variables are left as they are.
+///
+/// # Errors
+/// Fails if the environment variables are malformed, if a file descriptor isn't open or isn't a
+/// listening Unix domain stream socket, or if the name of a socket cannot be retrieved. None of the
+/// file descriptors that were taken ownership of stay open in that case.
+///
+/// # Safety
+/// The file descriptors described by the environment variables must not be owned by anything else
+/// in the process, which also means that this function must not be called more than once.
+pub unsafe fn listeners() -> io::Result<Vec<ActivatedListener>> {
+	let Some(pid) = env::var_os("LISTEN_PID") else {
+		return Ok(Vec::new());
+	};
+	if parse_var("LISTEN_PID", pid)? != process::id() {
+		return Ok(Vec::new());
+	}
+	let count = match env::var_os("LISTEN_FDS") {
+		Some(count) => parse_var("LISTEN_FDS", count)?,
+		None => return Err(invalid_data("LISTEN_PID is set, but LISTEN_FDS isn't")),
+	};
+	let fd_names = match env::var("LISTEN_FDNAMES") {
+		Ok(names) => names.split(':').map(|nm| Some(nm.to_owned())).collect(),
+		Err(env::VarError::NotPresent) => vec![None; usize::try_from(count).unwrap_or(0)],
+		Err(env::VarError::NotUnicode(..)) => {
+			return Err(invalid_data("LISTEN_FDNAMES is not valid UTF-8"))
+		}
+	};
+	if u32::try_from(fd_names.len()).ok() != Some(count) {
+		return Err(invalid_data(
+			"LISTEN_FDNAMES doesn't have as many names as LISTEN_FDS says",
+		));
+	}
+
+	// All of the file descriptors are taken ownership of first, so that they're all closed if
+	// any of them is rejected.
+	let mut fds = Vec::with_capacity(fd_names.len());
+	for offset in 0..count {
+		let fd = RawFd::try_from(offset)
+			.ok()
+			.and_then(|offset| LISTEN_FDS_START.checked_add(offset))
+			.ok_or_else(|| invalid_data("LISTEN_FDS is too large"))?;
+		// SAFETY: ensured by the caller.
+		fds.push(unsafe { adopt_inherited(fd) }?);
+	}
+	fds.into_iter()
+		.zip(fd_names)
+		.map(|(fd, fd_name)| adopt(fd, fd_name))
+		.collect()
+}
+
+fn adopt(fd: OwnedFd, fd_name: Option<String>) -> io::Result<ActivatedListener> {
+	let (listener, addr) = check_listener(fd)?;
+	let name = if let Some(path) = addr.as_pathname() {
+		Some(path.to_path_buf().to_fs_name()?)
+	} else {
+		abstract_name(&addr)?
+	};
+	Ok(ActivatedListener {
+		listener: Listener::from(OwnedFd::from(listener)),
+		name,
+		fd_name,
+	})
+}
+
+/// Checks that the file descriptor is a listening Unix domain stream socket, returning it along with
+/// the address it's bound to.
+pub(super) fn check_listener(fd: OwnedFd) -> io::Result<(UnixListener, SocketAddr)> {
+	let raw = fd.as_raw_fd();
+	let not_listener = || {
+		io::Error::new(
+			io::ErrorKind::InvalidInput,
+			format!("file descriptor {raw} is not a listening Unix domain stream socket"),
+		)
+	};
+	let is_stream = c_wrappers::getsockopt_int(fd.as_fd(), libc::SOL_SOCKET, libc::SO_TYPE)
+		.map_err(|_| not_listener())?
+		== libc::SOCK_STREAM;
+	let is_listening =
+		c_wrappers::getsockopt_int(fd.as_fd(), libc::SOL_SOCKET, libc::SO_ACCEPTCONN)? != 0;
+	if !is_stream || !is_listening {
+		return Err(not_listener());
+	}
+	// Also fails if the socket isn't a Unix domain socket.
+	let listener = UnixListener::from(fd);
+	let addr = listener.local_addr().map_err(|_| not_listener())?;
+	Ok((listener, addr))
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn abstract_name(addr: &SocketAddr) -> io::Result<Option<Name<'static>>> {
+	addr.as_abstract_name()
+		.map(|nm| OsString::from_vec(nm.to_vec()).to_ns_name())
+		.transpose()
+}
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+fn abstract_name(_: &SocketAddr) -> io::Result<Option<Name<'static>>> {
+	Ok(None)
+}
+
+fn parse_var(var: &str, val: OsString) -> io::Result<u32> {
+	val.to_str()
+		.and_then(|val| val.parse().ok())
+		.ok_or_else(|| invalid_data(&format!("{var} is not a valid number")))
+}
+
+fn invalid_data(msg: &str) -> io::Error {
+	io::Error::new(io::ErrorKind::InvalidData, msg)
+}
@@ -15,8 +15,8 @@ use crate::local_socket::Name;
 #[cfg(any(target_os = "linux", target_os = "android"))]
 use std::os::linux::net::SocketAddrExt;
 use std::{
-	io,
-	os::unix::{ffi::OsStrExt, net::SocketAddr},
+	fs, io,
+	os::unix::{ffi::OsStrExt, fs::MetadataExt, net::SocketAddr},
 	path::Path,
 };
 
@@ -35,25 +35,58 @@ fn name_to_addr(name: Name<'_>) -> io::Result<SocketAddr> {
 	SocketAddr::from_pathname(Path::new(&name))
 }
 
+/// The device and inode numbers of a file, which tell it apart from any other file that later takes
+/// its path.
+pub(crate) type FileId = (u64, u64);
+
+fn file_id(path: &Path) -> Option<FileId> {
+	// The path itself is what gets unlinked, so symlinks aren't followed.
+	fs::symlink_metadata(path)
+		.ok()
+		.map(|meta| (meta.dev(), meta.ino()))
+}
+
+/// Unlinks the socket file of a listener when dropped, unless the path has been taken by another
+/// file in the meantime, such as the socket of a listener that replaced this one.
 #[derive(Clone, Debug, Default)]
-struct ReclaimGuard(Option<Name<'static>>);
+struct ReclaimGuard {
+	name: Option<Name<'static>>,
+	/// `None` if the socket file could not be examined, in which case the check is skipped.
+	file: Option<FileId>,
+}
 impl ReclaimGuard {
+	/// Creates a guard for the file currently at the path of the name.
 	fn new(name: Name<'static>) -> Self {
-		Self(if name.is_path() { Some(name) } else { None })
+		if !name.is_path() {
+			return Self::default();
+		}
+		let file = file_id(Path::new(name.raw()));
+		Self::with_file(name, file)
+	}
+	fn with_file(name: Name<'static>, file: Option<FileId>) -> Self {
+		Self {
+			name: name.is_path().then_some(name),
+			file,
+		}
 	}
 	fn take(&mut self) -> Self {
-		Self(self.0.take())
+		Self {
+			name: self.name.take(),
+			file: self.file,
+		}
 	}
 	fn forget(&mut self) {
-		self.0 = None;
+		self.name = None;
 	}
 }
 impl Drop for ReclaimGuard {
 	fn drop(&mut self) {
-		if let Self(Some(name)) = self {
-			if name.is_namespaced() {
-				let _ = std::fs::remove_file(name.raw());
-			}
+		let Some(name) = &self.name else {
+			return;
+		};
+		let path = Path::new(name.raw());
+		if self.file.is_none() || file_id(path) == self.file {
+			let _ = fs::remove_file(path);
 		}
 	}
 }
@@ -1,12 +1,30 @@
-use super::{name_to_addr, ReclaimGuard, Stream};
-use crate::local_socket::{traits, Name};
+use super::{file_id, name_to_addr, FileId, ReclaimGuard, Stream};
+use crate::{
+	local_socket::{
+		is_exhaustion, shutdown_error, traits, Exhaustion, ExhaustionPolicy, Name, ShutdownHandle,
+		ShutdownState,
+	},
+	os::unix::c_wrappers,
+};
 use std::{
 	fmt::{self, Debug, Formatter},
-	io,
+	fs, io,
 	os::{
 		fd::{AsFd, BorrowedFd, OwnedFd},
-		unix::{io::AsRawFd, net::UnixListener},
+		unix::{
+			fs::FileTypeExt,
+			io::AsRawFd,
+			net::{SocketAddr, UnixListener},
+		},
+	},
+	path::{Path, PathBuf},
+	process,
+	sync::{
+		atomic::{AtomicBool, AtomicU32, Ordering::Relaxed},
+		Arc, OnceLock,
 	},
+	thread,
+	time::{Duration, Instant},
 };
 
 /// Wrapper around [`UnixListener`] that implements
@@ -14,8 +32,155 @@ use std::{
 pub struct Listener {
 	pub(super) listener: UnixListener,
 	pub(super) reclaim: ReclaimGuard,
+	/// Created on first use of `.shutdown_handle()` or `.accept_timeout()`, after which the socket
+	/// is kept in nonblocking mode and waited on with `poll()`.
+	///
+	/// The nonblocking mode of a socket is shared by all file descriptors duplicated from it, which
+	/// is why blocking accepts wait with `poll()` and retry whenever they get `EWOULDBLOCK`, even
+	/// if this listener never switched the socket to nonblocking mode itself.
+	shutdown: OnceLock<Arc<ShutdownState>>,
+	/// The nonblocking mode as set by the user.
+	nonblocking: AtomicBool,
+	pub(super) exhaustion: Option<Exhaustion>,
 }
 impl Listener {
+	fn new(listener: UnixListener, reclaim: ReclaimGuard) -> Self {
+		Self {
+			listener,
+			reclaim,
+			shutdown: OnceLock::new(),
+			nonblocking: AtomicBool::new(false),
+			exhaustion: None,
+		}
+	}
+
+	/// Creates another listener for the same socket, with the same nonblocking mode and exhaustion
+	/// policy, but without name reclamation and with a shutdown state of its own, if it comes to
+	/// need one.
+	pub(crate) fn detached_copy(&self) -> io::Result<Self> {
+		let listener = UnixListener::from(c_wrappers::duplicate_fd(self.listener.as_fd())?);
+		let exhaustion = self
+			.exhaustion
+			.as_ref()
+			.map(|exhaustion| Exhaustion::new(exhaustion.policy().clone()))
+			.transpose()?;
+		Ok(Self {
+			exhaustion,
+			nonblocking: AtomicBool::new(self.nonblocking.load(Relaxed)),
+			..Self::new(listener, ReclaimGuard::default())
+		})
+	}
+	/// Gives up name reclamation, returning the name that would have been reclaimed, if any, along
+	/// with the socket file it was bound to.
+	pub(crate) fn take_reclaim(&mut self) -> Option<(Name<'static>, Option<FileId>)> {
+		let file = self.reclaim.file;
+		self.reclaim.name.take().map(|name| (name, file))
+	}
+	/// Takes over reclamation of the given name, or gives it up if `None` is passed. The socket
+	/// file is only unlinked if it's still the given one, unless that is `None`.
+	pub(crate) fn set_reclaim(&mut self, reclaim: Option<(Name<'static>, Option<FileId>)>) {
+		self.reclaim = reclaim
+			.map(|(name, file)| ReclaimGuard::with_file(name, file))
+			.unwrap_or_default();
+	}
+
+	fn shutdown_state(&self) -> io::Result<&Arc<ShutdownState>> {
+		if let Some(state) = self.shutdown.get() {
+			return Ok(state);
+		}
+		let state = Arc::new(ShutdownState::new()?);
+		self.listener.set_nonblocking(true)?;
+		Ok(self.shutdown.get_or_init(|| state))
+	}
+
+	fn accept_until(
+		&self,
+		state: Option<&ShutdownState>,
+		deadline: Option<Instant>,
+	) -> io::Result<Stream> {
+		let nonblocking = self.nonblocking.load(Relaxed);
+		// Another process sharing the socket can switch it back to blocking mode, so a listener
+		// which can be shut down waits with `poll()` before accepting rather than getting stuck in
+		// `accept()` past the shutdown.
+		let mut ready = state.is_none() || nonblocking;
+		loop {
+			if state.is_some_and(ShutdownState::is_shut_down) {
+				return Err(shutdown_error());
+			}
+			if ready {
+				match self.listener.accept() {
+					// TODO make use of the second return value in some shape or form
+					Ok((s, _)) => {
+						// Some platforms make accepted sockets inherit the nonblocking mode, which
+						// the socket may be in even if this listener is in blocking mode.
+						if !nonblocking {
+							s.set_nonblocking(false)?;
+						}
+						return Ok(Stream::from(s));
+					}
+					Err(e) if e.kind() == io::ErrorKind::WouldBlock && !nonblocking => {}
+					Err(e) => return Err(e),
+				}
+			}
+			let timeout_ms = match deadline {
+				Some(deadline) => c_wrappers::poll_timeout(remaining(deadline)?),
+				None => -1,
+			};
+			match state {
+				Some(state) => c_wrappers::poll_readable(
+					&[self.listener.as_fd(), state.wake_fd()],
+					timeout_ms,
+				)?,
+				None => c_wrappers::poll_readable(&[self.listener.as_fd()], timeout_ms)?,
+			};
+			ready = true;
+		}
+	}
+
+	/// Accepts a connection, applying the exhaustion policy if there is one.
+	fn accept_with(&self, deadline: Option<Instant>) -> io::Result<Stream> {
+		loop {
+			let rslt = self.accept_until(self.shutdown.get().map(Arc::as_ref), deadline);
+			let Some(exhaustion) = &self.exhaustion else {
+				return rslt;
+			};
+			let e = match rslt {
+				Ok(s) => {
+					exhaustion.reset();
+					return Ok(s);
+				}
+				Err(e) if is_exhaustion(&e) => e,
+				Err(e) => return Err(e),
+			};
+			let backoff = exhaustion.handle(&e, || self.shed())?;
+			if self.nonblocking.load(Relaxed) {
+				return Err(e);
+			}
+			let backoff = match deadline {
+				Some(deadline) => backoff.min(remaining(deadline)?),
+				None => backoff,
+			};
+			match self.shutdown.get() {
+				// Wakes up early if the listener is shut down, which the next iteration picks up.
+				Some(state) => {
+					c_wrappers::poll_readable(
+						&[state.wake_fd()],
+						c_wrappers::poll_timeout(backoff),
+					)?;
+				}
+				None => thread::sleep(backoff),
+			}
+		}
+	}
+
+	/// Accepts a pending connection and closes it right away, without blocking if there is none.
+	fn shed(&self) -> bool {
+		let fd = self.listener.as_fd();
+		// Can still block if another thread takes the connection in between, but only until the
+		// next client arrives.
+		matches!(c_wrappers::poll_readable(&[fd], 0), Ok(true)) && c_wrappers::accept_and_close(fd)
+	}
+
 	fn decode_listen_error(error: io::Error) -> io::Error {
 		io::Error::from(match error.kind() {
 			io::ErrorKind::AlreadyExists => io::ErrorKind::AddrInUse,
@@ -24,16 +189,72 @@ impl Listener {
 	}
 
 	pub(super) fn _bind(name: Name<'_>, keep_name: bool) -> io::Result<Self> {
-		Ok(Self {
-			listener: UnixListener::bind_addr(&name_to_addr(name.borrow())?)
-				.map_err(Self::decode_listen_error)?,
-			reclaim: keep_name
-				.then_some(name.into_owned())
-				.map(ReclaimGuard::new)
-				.unwrap_or_default(),
-		})
+		let listener = UnixListener::bind_addr(&name_to_addr(name.borrow())?)
+			.map_err(Self::decode_listen_error)?;
+		let reclaim = keep_name
+			.then_some(name.into_owned())
+			.map(ReclaimGuard::new)
+			.unwrap_or_default();
+		Ok(Self::new(listener, reclaim))
+	}
+
+	/// Binds to a temporary path next to the one of the name, then renames the socket file over the
+	/// latter, atomically replacing the socket that was there, if any.
+	pub(super) fn _bind_replacing(name: Name<'_>) -> io::Result<Self> {
+		if !name.is_path() {
+			return Self::_bind(name, true);
+		}
+		let path = Path::new(name.raw());
+		match fs::symlink_metadata(path) {
+			Ok(meta) if meta.file_type().is_socket() => {}
+			Ok(..) => {
+				return Err(io::Error::new(
+					io::ErrorKind::AddrInUse,
+					"the path is taken by a file which is not a socket",
+				))
+			}
+			Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+			Err(e) => return Err(e),
+		}
+		let temp = temp_path(path)?;
+		// Only a process with the same ID could have picked this path, which means that a file left
+		// there belongs to one that is no longer running.
+		let _ = fs::remove_file(&temp);
+		let listener = UnixListener::bind(&temp).map_err(Self::decode_listen_error)?;
+		// Renaming preserves the inode, which is what the reclamation guard checks for.
+		let file = file_id(&temp);
+		if let Err(e) = fs::rename(&temp, path) {
+			let _ = fs::remove_file(&temp);
+			return Err(e);
+		}
+		let reclaim = ReclaimGuard::with_file(name.into_owned(), file);
+		Ok(Self::new(listener, reclaim))
 	}
 }
+
+/// Makes a path unique to this call in the same directory as the given one, by appending a suffix
+/// to it, or by using the suffix alone as the file name if the former doesn't fit into a socket
+/// address.
+fn temp_path(path: &Path) -> io::Result<PathBuf> {
+	static COUNTER: AtomicU32 = AtomicU32::new(0);
+	let count = COUNTER.fetch_add(1, Relaxed);
+	let suffix = format!(".{:x}-{count:x}.tmp", process::id());
+	let mut temp = path.as_os_str().to_owned();
+	temp.push(&suffix);
+	let temp = PathBuf::from(temp);
+	if SocketAddr::from_pathname(&temp).is_ok() {
+		return Ok(temp);
+	}
+	let temp = path.with_file_name(suffix);
+	SocketAddr::from_pathname(&temp).map_err(|_| {
+		io::Error::new(
+			io::ErrorKind::InvalidInput,
+			"path too long to fit a temporary socket file next to it",
+		)
+	})?;
+	Ok(temp)
+}
+
 impl crate::Sealed for Listener {}
 impl traits::Listener for Listener {
 	type Stream = Stream;
@@ -47,31 +268,65 @@ impl traits::Listener for Listener {
 		Self::_bind(name, false)
 	}
 	#[inline]
+	fn bind_replacing(name: Name<'_>) -> io::Result<Self> {
+		Self::_bind_replacing(name)
+	}
+	#[inline]
 	fn accept(&self) -> io::Result<Stream> {
-		// TODO make use of the second return value in some shape or form
-		self.listener.accept().map(|(s, _)| Stream::from(s))
+		self.accept_with(None)
+	}
+	fn accept_timeout(&self, timeout: Duration) -> io::Result<Stream> {
+		self.shutdown_state()?;
+		// A timeout too long to be represented is the same as none at all.
+		self.accept_with(Instant::now().checked_add(timeout))
+	}
+	fn shutdown_handle(&self) -> io::Result<ShutdownHandle> {
+		self.shutdown_state().map(Arc::clone).map(ShutdownHandle)
 	}
 	#[inline]
 	fn set_nonblocking(&self, nonblocking: bool) -> io::Result<()> {
-		self.listener.set_nonblocking(nonblocking)
+		self.nonblocking.store(nonblocking, Relaxed);
+		if self.shutdown.get().is_none() {
+			self.listener.set_nonblocking(nonblocking)?;
+		}
+		Ok(())
+	}
+	fn set_exhaustion_policy(&mut self, policy: Option<ExhaustionPolicy>) -> io::Result<()> {
+		self.exhaustion = policy.map(Exhaustion::new).transpose()?;
+		Ok(())
 	}
 	fn do_not_reclaim_name_on_drop(&mut self) {
 		self.reclaim.forget();
 	}
 }
 
+/// Returns the time left until the deadline, failing if there is none.
+fn remaining(deadline: Instant) -> io::Result<Duration> {
+	let left = deadline.saturating_duration_since(Instant::now());
+	if left.is_zero() {
+		return Err(io::ErrorKind::TimedOut.into());
+	}
+	Ok(left)
+}
+
 impl Debug for Listener {
 	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
 		f.debug_struct("Listener")
 			.field("fd", &self.listener.as_raw_fd())
 			.field("reclaim", &self.reclaim)
-			.finish()
+			.field("nonblocking", &self.nonblocking.load(Relaxed))
+			.field("exhaustion", &self.exhaustion)
+			.finish_non_exhaustive()
 	}
 }
 
 impl From<Listener> for UnixListener {
 	fn from(mut l: Listener) -> Self {
 		l.reclaim.forget();
+		if l.shutdown.get().is_some() {
+			// Restores the mode the user expects the socket to be in.
+			let _ = l.listener.set_nonblocking(l.nonblocking.load(Relaxed));
+		}
 		l.listener
 	}
 }
@@ -89,9 +344,6 @@ impl From<Listener> for OwnedFd {
 }
 impl From<OwnedFd> for Listener {
 	fn from(fd: OwnedFd) -> Self {
-		Listener {
-			listener: fd.into(),
-			reclaim: ReclaimGuard::default(),
-		}
+		Listener::new(fd.into(), ReclaimGuard::default())
 	}
 }
@@ -26,6 +26,10 @@ impl traits::Stream for Stream {
 		UnixStream::connect_addr(&name_to_addr(name)?).map(Self::from)
 	}
 	#[inline]
+	fn pair() -> io::Result<(Self, Self)> {
+		UnixStream::pair().map(|(s1, s2)| (s1.into(), s2.into()))
+	}
+	#[inline]
 	fn set_nonblocking(&self, nonblocking: bool) -> io::Result<()> {
 		self.0.set_nonblocking(nonblocking)
 	}
@@ -1,26 +1,54 @@
 use super::Stream;
 use crate::{
-	local_socket::{prelude::*, Name},
-	os::unix::uds_local_socket::{listener::Listener as SyncListener, ReclaimGuard},
+	local_socket::{is_exhaustion, prelude::*, Exhaustion, ExhaustionPolicy, Name},
+	os::unix::{
+		c_wrappers,
+		uds_local_socket::{listener::Listener as SyncListener, ReclaimGuard},
+	},
 };
 use std::{
 	fmt::{self, Debug, Formatter},
 	io,
 	os::unix::prelude::*,
 };
-use tokio::net::UnixListener;
+use tokio::{net::UnixListener, time};
 
 pub struct Listener {
 	listener: UnixListener,
 	reclaim: ReclaimGuard,
+	exhaustion: Option<Exhaustion>,
 }
 impl Listener {
 	pub fn bind(name: Name<'_>, keep_name: bool) -> io::Result<Self> {
 		Self::try_from(SyncListener::_bind(name, keep_name)?)
 	}
+	pub fn bind_replacing(name: Name<'_>) -> io::Result<Self> {
+		Self::try_from(SyncListener::_bind_replacing(name)?)
+	}
 	pub async fn accept(&self) -> io::Result<Stream> {
-		let inner = self.listener.accept().await?.0;
-		Ok(Stream::from(inner))
+		loop {
+			let rslt = self.listener.accept().await;
+			let Some(exhaustion) = &self.exhaustion else {
+				return Ok(Stream::from(rslt?.0));
+			};
+			match rslt {
+				Ok((inner, _)) => {
+					exhaustion.reset();
+					return Ok(Stream::from(inner));
+				}
+				Err(e) if is_exhaustion(&e) => {
+					// The socket is in nonblocking mode, so this doesn't block.
+					let shed = || c_wrappers::accept_and_close(self.listener.as_fd());
+					time::sleep(exhaustion.handle(&e, shed)?).await;
+				}
+				Err(e) => return Err(e),
+			}
+		}
+	}
+
+	pub fn set_exhaustion_policy(&mut self, policy: Option<ExhaustionPolicy>) -> io::Result<()> {
+		self.exhaustion = policy.map(Exhaustion::new).transpose()?;
+		Ok(())
 	}
 
 	pub fn do_not_reclaim_name_on_drop(&mut self) {
@@ -33,9 +61,11 @@ impl TryFrom<SyncListener> for Listener {
 	fn try_from(mut sync: SyncListener) -> io::Result<Self> {
 		sync.set_nonblocking(true)?;
 		let reclaim = sync.reclaim.take();
+		let exhaustion = sync.exhaustion.take();
 		Ok(Self {
 			listener: UnixListener::from_std(sync.into())?,
 			reclaim,
+			exhaustion,
 		})
 	}
 }
@@ -45,6 +75,7 @@ impl Debug for Listener {
 		f.debug_struct("Listener")
 			.field("fd", &self.listener.as_raw_fd())
 			.field("reclaim", &self.reclaim)
+			.field("exhaustion", &self.exhaustion)
 			.finish()
 	}
 }
@@ -48,6 +48,9 @@ impl Stream {
 		}
 		UnixStream::connect(addr.as_pathname().unwrap()).await
 	}
+	pub async fn pair() -> io::Result<(Self, Self)> {
+		UnixStream::pair().map(|(s1, s2)| (s1.into(), s2.into()))
+	}
 
 	pub fn split(self) -> (RecvHalf, SendHalf) {
 		let (r, w) = self.0.into_split();
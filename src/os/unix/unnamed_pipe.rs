@@ -12,10 +12,15 @@ use std::{
 		unix::io::{AsRawFd, FromRawFd},
 	},
 };
+#[cfg(not(target_os = "linux"))]
+use {super::c_wrappers, std::os::fd::AsFd};
 
 pub(crate) fn pipe() -> io::Result<(PubSender, PubRecver)> {
 	let (success, fds) = unsafe {
 		let mut fds: [c_int; 2] = [0; 2];
+		#[cfg(target_os = "linux")]
+		let result = libc::pipe2(fds.as_mut_ptr(), libc::O_CLOEXEC);
+		#[cfg(not(target_os = "linux"))]
 		let result = libc::pipe(fds.as_mut_ptr());
 		(result == 0, fds)
 	};
@@ -27,6 +32,11 @@ pub(crate) fn pipe() -> io::Result<(PubSender, PubRecver)> {
 			let r = OwnedFd::from_raw_fd(fds[0]);
 			(w, r)
 		};
+		#[cfg(not(target_os = "linux"))]
+		{
+			c_wrappers::set_cloexec(w.as_fd())?;
+			c_wrappers::set_cloexec(r.as_fd())?;
+		}
 		let w = PubSender(Sender(FdOps(w)));
 		let r = PubRecver(Recver(FdOps(r)));
 		Ok((w, r))
@@ -12,6 +12,15 @@ pub fn bind_without_name_reclamation(name: Name<'_>) -> io::Result<Listener> {
 	np_impl::Listener::bind(name).map(Listener::from)
 }
 
+#[inline]
+pub fn bind_replacing(name: Name<'_>) -> io::Result<Listener> {
+	np_impl::Listener::bind_replacing(name).map(Listener::from)
+}
+
 pub fn connect(name: Name<'_>) -> io::Result<Stream> {
 	np_impl::Stream::connect(name).map(Stream::from)
 }
+
+pub fn pair() -> io::Result<(Stream, Stream)> {
+	np_impl::Stream::pair().map(|(s1, s2)| (s1.into(), s2.into()))
+}
@@ -1,19 +1,97 @@
 use super::stream::Stream;
 use crate::{
-	local_socket::{traits, Name},
+	local_socket::{shutdown_error, traits, ExhaustionPolicy, Name, ShutdownHandle, ShutdownState},
 	os::windows::{
 		named_pipe::{pipe_mode::Bytes, PipeListener, PipeListenerOptions},
 		path_conversion::*,
 	},
 };
-use std::{io, path::Path};
+use std::{
+	io,
+	path::Path,
+	sync::{
+		atomic::{AtomicBool, Ordering::Relaxed},
+		Arc, OnceLock,
+	},
+	thread,
+	time::{Duration, Instant},
+};
+use windows_sys::Win32::Foundation::ERROR_PIPE_LISTENING;
 
 type ListenerImpl = PipeListener<Bytes, Bytes>;
 
+/// How often an interruptible accept checks whether the listener has been shut down.
+///
+/// Waiting on an event object instead would require overlapped I/O, which the streams produced by
+/// the listener would then have to use as well.
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
 /// Wrapper around [`PipeListener`] that implements
 /// [`Listener`](crate::local_socket::traits::Listener).
 #[derive(Debug)]
-pub struct Listener(ListenerImpl);
+pub struct Listener(ListenerImpl, Interrupt);
+
+/// Created on first use of `.shutdown_handle()` or `.accept_timeout()`, after which the pipe
+/// listener is kept in nonblocking mode and polled.
+#[derive(Debug, Default)]
+struct Interrupt {
+	shutdown: OnceLock<ShutdownHandle>,
+	/// The nonblocking mode as set by the user.
+	nonblocking: AtomicBool,
+}
+
+impl Listener {
+	fn shutdown_state(&self) -> io::Result<&ShutdownHandle> {
+		if let Some(handle) = self.1.shutdown.get() {
+			return Ok(handle);
+		}
+		let handle = ShutdownHandle(Arc::new(ShutdownState::new()?));
+		self.0.set_nonblocking(true)?;
+		Ok(self.1.shutdown.get_or_init(|| handle))
+	}
+	fn accept_until(
+		&self,
+		handle: &ShutdownHandle,
+		deadline: Option<Instant>,
+	) -> io::Result<Stream> {
+		loop {
+			if handle.is_shut_down() {
+				return Err(shutdown_error());
+			}
+			match self.0.accept() {
+				Ok(s) => {
+					let nonblocking = self.1.nonblocking.load(Relaxed);
+					if !nonblocking {
+						s.set_nonblocking(false)?;
+					}
+					return Ok(s);
+				}
+				// Nonblocking pipe instances report that no client has connected yet this way.
+				Err(e)
+					if e.kind() == io::ErrorKind::WouldBlock
+						|| e.raw_os_error() == Some(ERROR_PIPE_LISTENING as i32) =>
+				{
+					if self.1.nonblocking.load(Relaxed) {
+						return Err(io::ErrorKind::WouldBlock.into());
+					}
+				}
+				Err(e) => return Err(e),
+			}
+			let sleep_for = match deadline {
+				Some(deadline) => {
+					let left = deadline.saturating_duration_since(Instant::now());
+					if left.is_zero() {
+						return Err(io::ErrorKind::TimedOut.into());
+					}
+					left.min(POLL_INTERVAL)
+				}
+				None => POLL_INTERVAL,
+			};
+			thread::sleep(sleep_for);
+		}
+	}
+}
+
 impl crate::Sealed for Listener {}
 impl traits::Listener for Listener {
 	type Stream = Stream;
@@ -22,6 +100,10 @@ impl traits::Listener for Listener {
 	fn bind(name: Name<'_>) -> io::Result<Self> {
 		Self::bind_without_name_reclamation(name)
 	}
+	fn bind_replacing(name: Name<'_>) -> io::Result<Self> {
+		// Named pipes have no files to replace.
+		Self::bind_without_name_reclamation(name)
+	}
 	fn bind_without_name_reclamation(name: Name<'_>) -> io::Result<Self> {
 		let mut options = PipeListenerOptions::new();
 		options.path = if name.is_path() {
@@ -31,13 +113,35 @@ impl traits::Listener for Listener {
 				.to_wtf_16()
 				.map_err(to_io_error)?
 		};
-		options.create().map(Self)
+		options
+			.create()
+			.map(|listener| Self(listener, Interrupt::default()))
 	}
 	fn accept(&self) -> io::Result<Stream> {
+		if let Some(handle) = self.1.shutdown.get() {
+			return self.accept_until(handle, None);
+		}
 		self.0.accept()
 	}
+	fn accept_timeout(&self, timeout: Duration) -> io::Result<Stream> {
+		let handle = self.shutdown_state()?;
+		// A timeout too long to be represented is the same as none at all.
+		self.accept_until(handle, Instant::now().checked_add(timeout))
+	}
+	fn shutdown_handle(&self) -> io::Result<ShutdownHandle> {
+		self.shutdown_state().map(ShutdownHandle::clone)
+	}
 	fn set_nonblocking(&self, nonblocking: bool) -> io::Result<()> {
-		self.0.set_nonblocking(nonblocking)
+		self.1.nonblocking.store(nonblocking, Relaxed);
+		if self.1.shutdown.get().is_none() {
+			self.0.set_nonblocking(nonblocking)?;
+		}
+		Ok(())
+	}
+	#[inline]
+	fn set_exhaustion_policy(&mut self, _: Option<ExhaustionPolicy>) -> io::Result<()> {
+		// Named pipes don't run out of file descriptors.
+		Ok(())
 	}
 	fn do_not_reclaim_name_on_drop(&mut self) {}
 }
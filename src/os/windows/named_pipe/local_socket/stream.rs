@@ -3,10 +3,16 @@ use crate::{
 		traits::{self, ReuniteResult},
 		Name,
 	},
-	os::windows::named_pipe::{pipe_mode::Bytes, DuplexPipeStream, RecvPipeStream, SendPipeStream},
+	os::windows::named_pipe::{
+		pipe_mode::Bytes, DuplexPipeStream, PipeListenerOptions, RecvPipeStream, SendPipeStream,
+	},
 	Sealed,
 };
-use std::io;
+use std::{
+	ffi::OsStr,
+	io, process,
+	sync::atomic::{AtomicU32, Ordering::Relaxed},
+};
 
 pub type Stream = DuplexPipeStream<Bytes>;
 pub type RecvHalf = RecvPipeStream<Bytes>;
@@ -25,6 +31,24 @@ impl traits::Stream for Stream {
 		}
 	}
 
+	fn pair() -> io::Result<(Self, Self)> {
+		for path in pair_paths() {
+			let listener = match PipeListenerOptions::new()
+				.path(OsStr::new(&path))
+				.create_duplex::<Bytes>()
+			{
+				Err(e) if e.kind() == io::ErrorKind::PermissionDenied => continue,
+				els => els?,
+			};
+			let client = Stream::connect_by_path(&path)?;
+			let server = listener.accept()?;
+			if server.client_process_id()? == process::id() {
+				return Ok((server, client));
+			}
+		}
+		Err(pair_paths_exhausted())
+	}
+
 	forward_to_self!(
 		fn set_nonblocking(&self, nonblocking: bool) -> io::Result<()>;
 		fn split(self) -> (Self::RecvHalf, Self::SendHalf);
@@ -39,3 +63,27 @@ impl Sealed for SendHalf {}
 impl traits::SendHalf for SendHalf {
 	type Stream = Stream;
 }
+
+/// How many times `pair()` tries a new pipe name before giving up.
+const PAIR_ATTEMPTS: u32 = 8;
+
+/// Generates the names of the named pipes used to implement `pair()`.
+///
+/// The listener is to be created as the first instance of the named pipe, which fails with
+/// [`PermissionDenied`](io::ErrorKind::PermissionDenied) if the name is already taken, and the
+/// client process ID is to be checked to make sure that no other process managed to connect before
+/// we did. In both cases, the next name is to be tried.
+pub(super) fn pair_paths() -> impl Iterator<Item = String> {
+	static COUNTER: AtomicU32 = AtomicU32::new(0);
+	let pid = process::id();
+	(0..PAIR_ATTEMPTS).map(move |_| {
+		let nr = COUNTER.fetch_add(1, Relaxed);
+		format!(r"\\.\pipe\interprocess-pair-{pid}-{nr}")
+	})
+}
+pub(super) fn pair_paths_exhausted() -> io::Error {
+	io::Error::new(
+		io::ErrorKind::AddrInUse,
+		"could not find a free named pipe name for the stream pair",
+	)
+}
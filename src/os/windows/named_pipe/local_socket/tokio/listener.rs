@@ -1,6 +1,6 @@
 use super::Stream;
 use crate::{
-	local_socket::Name,
+	local_socket::{ExhaustionPolicy, Name},
 	os::windows::{
 		named_pipe::{
 			pipe_mode,
@@ -28,9 +28,17 @@ impl Listener {
 		};
 		options.create_tokio().map(Self)
 	}
+	pub fn bind_replacing(name: Name<'_>) -> io::Result<Self> {
+		// Named pipes have no files to replace.
+		Self::bind(name, true)
+	}
 	pub async fn accept(&self) -> io::Result<Stream> {
 		let inner = self.0.accept().await?;
 		Ok(Stream(inner))
 	}
+	pub fn set_exhaustion_policy(&mut self, _: Option<ExhaustionPolicy>) -> io::Result<()> {
+		// Named pipes don't run out of file descriptors.
+		Ok(())
+	}
 	pub fn do_not_reclaim_name_on_drop(&mut self) {}
 }
@@ -1,12 +1,14 @@
+use super::super::stream::{pair_paths, pair_paths_exhausted};
 use crate::{
 	error::{FromHandleError, ReuniteError},
 	local_socket::Name,
 	os::windows::named_pipe::{
 		pipe_mode::Bytes,
-		tokio::{DuplexPipeStream, RecvPipeStream, SendPipeStream},
+		tokio::{DuplexPipeStream, PipeListenerOptionsExt, RecvPipeStream, SendPipeStream},
+		PipeListenerOptions,
 	},
 };
-use std::{io, os::windows::prelude::*};
+use std::{ffi::OsStr, io, os::windows::prelude::*, process};
 
 type StreamImpl = DuplexPipeStream<Bytes>;
 type RecvHalfImpl = RecvPipeStream<Bytes>;
@@ -23,6 +25,23 @@ impl Stream {
 		}
 		.map(Self)
 	}
+	pub async fn pair() -> io::Result<(Self, Self)> {
+		for path in pair_paths() {
+			let listener = match PipeListenerOptions::new()
+				.path(OsStr::new(&path))
+				.create_tokio_duplex::<Bytes>()
+			{
+				Err(e) if e.kind() == io::ErrorKind::PermissionDenied => continue,
+				els => els?,
+			};
+			let (server, client) =
+				::tokio::try_join!(listener.accept(), StreamImpl::connect_by_path(&path))?;
+			if server.client_process_id()? == process::id() {
+				return Ok((Self(server), Self(client)));
+			}
+		}
+		Err(pair_paths_exhausted())
+	}
 	#[inline]
 	pub fn split(self) -> (RecvHalf, SendHalf) {
 		let (r, w) = self.0.split();
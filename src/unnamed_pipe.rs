@@ -5,10 +5,18 @@
 //! can both be useful or problematic, depending on the use case. Unnamed pipes work best when a
 //! child process is used. With the fork model on Unix-like systems, the handle can be transferred
 //! to the child process thanks to the cloned address space; on Windows, inheritable handles (the
-//! default for unnamed pipes in this crate) can be used.
+//! default for unnamed pipes in this crate) can be used. On Unix, the pipe ends are not inherited
+//! across `exec` by default, which the `Inheritable` trait in `interprocess::os::unix` can be used
+//! to opt into.
 //!
 //! Another way to use unnamed pipes is to use a named pipe or a Unix domain socket to establish an
 //! unnamed pipe connection. It just so happens that this crate supports all three.
+//!
+//! Unnamed pipes are unidirectional. For a bidirectional channel without a name, use
+//! [`Stream::pair()`](crate::local_socket::traits::Stream::pair) instead.
+//!
+//! Chains of child processes connected with unnamed pipes, as in a shell pipeline, can be spawned
+//! with the [`Pipeline`] builder.
 
 impmod! {unnamed_pipe,
 	Recver as RecverImpl,
@@ -17,6 +25,9 @@ impmod! {unnamed_pipe,
 }
 use std::io;
 
+mod pipeline;
+pub use pipeline::*;
+
 /// Creates a new pipe with the default creation settings and returns the handles to its sending end
 /// and receiving end.
 ///
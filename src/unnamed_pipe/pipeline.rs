@@ -0,0 +1,206 @@
+use super::{pipe, Recver, Sender};
+use std::{
+	io::{self, prelude::*},
+	process::{Child, Command, ExitStatus, Stdio},
+	thread,
+};
+
+/// Builder for shell-style pipelines of child processes connected with unnamed pipes.
+///
+/// Each stage of the pipeline is a [`Command`] whose standard output is connected to the standard
+/// input of the next stage, much like `first | second | third` would in a shell. The standard input
+/// of the first stage and the standard output of the last one are made available to the spawning
+/// process as a [`Sender`] and a [`Recver`] respectively. Standard error is left as configured on
+/// the `Command`s.
+///
+/// Intermediate stages can be [tapped](Self::tap), which copies their output into a separate
+/// [`Recver`] in addition to passing it on to the next stage. Copying is performed by a helper
+/// thread, which blocks if the tap is not being read from; to keep the pipeline going, either keep
+/// reading from the tap or drop it.
+///
+/// # Examples
+/// ```no_run
+/// use interprocess::unnamed_pipe::Pipeline;
+/// use std::{io::prelude::*, process::Command};
+///
+/// let mut uppercase = Command::new("tr");
+/// uppercase.args(["a-z", "A-Z"]);
+/// let mut pipeline = Pipeline::new()
+/// 	.stage(uppercase)
+/// 	.tap()
+/// 	.stage(Command::new("rev"))
+/// 	.spawn()?;
+///
+/// if let Some(mut stdin) = pipeline.stdin.take() {
+/// 	stdin.write_all(b"Hello world!\n")?;
+/// }
+///
+/// let mut output = String::new();
+/// pipeline.stdout.read_to_string(&mut output)?;
+/// assert_eq!(output, "!DLROW OLLEH\n");
+/// output.clear();
+/// pipeline.taps[0].read_to_string(&mut output)?;
+/// assert_eq!(output, "HELLO WORLD!\n");
+/// pipeline.wait()?;
+/// # std::io::Result::<()>::Ok(())
+/// ```
+#[derive(Debug, Default)]
+pub struct Pipeline {
+	stages: Vec<Stage>,
+}
+
+#[derive(Debug)]
+struct Stage {
+	command: Command,
+	tap: bool,
+}
+
+impl Pipeline {
+	/// Creates a pipeline with no stages.
+	#[inline]
+	pub fn new() -> Self {
+		Self::default()
+	}
+	/// Appends a stage to the end of the pipeline.
+	///
+	/// The standard input and standard output of `command` will be overwritten when the pipeline
+	/// is spawned.
+	#[must_use = "this is not an in-place operation"]
+	pub fn stage(mut self, command: Command) -> Self {
+		self.stages.push(Stage {
+			command,
+			tap: false,
+		});
+		self
+	}
+	/// Taps the output of the most recently added stage, making a copy of it available in the
+	/// [`taps`](SpawnedPipeline::taps) of the spawned pipeline.
+	///
+	/// Does nothing if no stages have been added yet.
+	#[must_use = "this is not an in-place operation"]
+	pub fn tap(mut self) -> Self {
+		if let Some(stage) = self.stages.last_mut() {
+			stage.tap = true;
+		}
+		self
+	}
+	/// Spawns all stages of the pipeline.
+	///
+	/// If one of the stages fails to spawn, the ones that have already been spawned are killed.
+	///
+	/// # Errors
+	/// In addition to errors from pipe creation and process spawning, fails with
+	/// [`InvalidInput`](io::ErrorKind::InvalidInput) if the pipeline has no stages.
+	pub fn spawn(self) -> io::Result<SpawnedPipeline> {
+		if self.stages.is_empty() {
+			return Err(io::Error::new(
+				io::ErrorKind::InvalidInput,
+				"pipeline has no stages",
+			));
+		}
+		let mut children = Vec::with_capacity(self.stages.len());
+		let spawned = spawn_stages(self.stages, &mut children);
+		match spawned {
+			Ok((stdin, stdout, taps)) => Ok(SpawnedPipeline {
+				children,
+				stdin: Some(stdin),
+				stdout,
+				taps,
+			}),
+			Err(e) => {
+				for mut child in children {
+					let _ = child.kill();
+					let _ = child.wait();
+				}
+				Err(e)
+			}
+		}
+	}
+}
+
+fn spawn_stages(
+	stages: Vec<Stage>,
+	children: &mut Vec<Child>,
+) -> io::Result<(Sender, Recver, Vec<Recver>)> {
+	let (stdin, mut next_stdin) = pipe()?;
+	let mut taps = Vec::new();
+	// Each command is dropped right after being spawned, which closes the pipe ends it holds.
+	for Stage { mut command, tap } in stages {
+		let (stdout_tx, stdout_rx) = pipe()?;
+		command
+			.stdin(to_stdio(next_stdin))
+			.stdout(to_stdio(stdout_tx));
+		children.push(command.spawn()?);
+		next_stdin = if tap {
+			let (fwd_tx, fwd_rx) = pipe()?;
+			let (tap_tx, tap_rx) = pipe()?;
+			thread::Builder::new()
+				.name("interprocess pipeline tap".to_owned())
+				.spawn(move || tee(stdout_rx, fwd_tx, tap_tx))?;
+			taps.push(tap_rx);
+			fwd_rx
+		} else {
+			stdout_rx
+		};
+	}
+	Ok((stdin, next_stdin, taps))
+}
+
+/// Copies everything from `src` into both `fwd` and `tap`, until end-of-file is reached or both
+/// destinations are closed.
+fn tee(mut src: Recver, fwd: Sender, tap: Sender) {
+	let mut dsts = [Some(fwd), Some(tap)];
+	let mut buf = [0; 8192];
+	loop {
+		let data = match src.read(&mut buf) {
+			Ok(0) => break,
+			Ok(n) => buf.get(..n).unwrap_or_default(),
+			Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+			Err(..) => break,
+		};
+		for dst in &mut dsts {
+			if dst.as_mut().is_some_and(|d| d.write_all(data).is_err()) {
+				*dst = None;
+			}
+		}
+		if dsts.iter().all(Option::is_none) {
+			break;
+		}
+	}
+}
+
+#[cfg(unix)]
+fn to_stdio(pipe_end: impl Into<std::os::unix::io::OwnedFd>) -> Stdio {
+	Stdio::from(pipe_end.into())
+}
+#[cfg(windows)]
+fn to_stdio(pipe_end: impl Into<std::os::windows::io::OwnedHandle>) -> Stdio {
+	Stdio::from(pipe_end.into())
+}
+
+/// A running [`Pipeline`], obtained from [`.spawn()`](Pipeline::spawn).
+#[derive(Debug)]
+pub struct SpawnedPipeline {
+	/// The child processes of the pipeline, in the order in which the stages were added.
+	pub children: Vec<Child>,
+	/// The sending end of the pipe connected to the standard input of the first stage.
+	///
+	/// Dropping it signals end-of-file to the first stage. Set to `None` by
+	/// [`.wait()`](Self::wait).
+	pub stdin: Option<Sender>,
+	/// The receiving end of the pipe connected to the standard output of the last stage.
+	pub stdout: Recver,
+	/// Copies of the output of [tapped](Pipeline::tap) stages, in the order of the stages.
+	pub taps: Vec<Recver>,
+}
+impl SpawnedPipeline {
+	/// Waits for all stages of the pipeline to exit and returns their exit statuses, in the order
+	/// of the stages.
+	///
+	/// The [`stdin`](Self::stdin) of the pipeline is closed before waiting, so that the first
+	/// stage doesn't wait for more input forever.
+	pub fn wait(&mut self) -> io::Result<Vec<ExitStatus>> {
+		drop(self.stdin.take());
+		self.children.iter_mut().map(Child::wait).collect()
+	}
+}
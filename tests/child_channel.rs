@@ -0,0 +1,52 @@
+#![cfg(all(unix, feature = "serde"))]
+
+use crate::{
+	os::unix::child_channel::{spawn_with_channel, Channel},
+	tests::util::*,
+};
+use color_eyre::eyre::ensure;
+use std::{
+	env,
+	process::{Command, Stdio},
+};
+
+const CHILD_ENV_VAR: &str = "INTERPROCESS_TEST_CHILD_CHANNEL_CHILD";
+
+#[test]
+fn child_channel() -> TestResult {
+	testinit();
+	let mut command = Command::new(env::current_exe().opname("current executable query")?);
+	command
+		.args(["--exact", "tests::child_channel::child_channel_child"])
+		.env(CHILD_ENV_VAR, "1")
+		.stdout(Stdio::null());
+	let (mut child, mut channel) =
+		spawn_with_channel::<(u32, String), Vec<u32>>(command).opname("child spawn")?;
+
+	for (nr, text) in [(1, "one"), (2, "two, three")] {
+		channel.send(&(nr, text.to_owned())).opname("send")?;
+		let lens = channel.recv().opname("receive")?;
+		ensure_eq!(lens, Some(vec![nr, text.len() as u32]));
+	}
+	drop(channel);
+
+	ensure!(child.wait().opname("child wait")?.success(), "child failed");
+	Ok(())
+}
+
+/// Child side of the `child_channel` test, which does nothing unless run by it.
+#[test]
+fn child_channel_child() -> TestResult {
+	if env::var_os(CHILD_ENV_VAR).is_none() {
+		return Ok(());
+	}
+	let mut channel =
+		unsafe { Channel::<Vec<u32>, (u32, String)>::from_parent() }.opname("channel adoption")?;
+	let mut nmsgs = 0;
+	while let Some((nr, text)) = channel.recv().opname("receive")? {
+		channel.send(&vec![nr, text.len() as u32]).opname("send")?;
+		nmsgs += 1;
+	}
+	ensure_eq!(nmsgs, 2);
+	Ok(())
+}
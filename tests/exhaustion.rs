@@ -0,0 +1,201 @@
+#![cfg(unix)]
+
+use crate::{
+	local_socket::{
+		prelude::*,
+		server::{serve, ServerOptions},
+		ExhaustionPolicy, Listener, NameTypeSupport, Stream,
+	},
+	os::unix::Inheritable,
+	poison_error,
+	tests::util::*,
+	unnamed_pipe,
+};
+use color_eyre::eyre::{bail, ensure};
+use std::{
+	env,
+	fs::File,
+	io::{self, prelude::*, BufReader},
+	os::unix::{io::OwnedFd, net::UnixStream},
+	process::{Command, Stdio},
+	sync::{
+		atomic::{AtomicBool, Ordering::SeqCst},
+		mpsc, Mutex,
+	},
+	time::Duration,
+};
+
+const CHILD_ENV_VAR: &str = "INTERPROCESS_TEST_EXHAUSTION_CHILD";
+const PIPE_FD: i32 = 10;
+const LISTENER_FD: i32 = 11;
+
+/// Runs a listener in a child process which has used up all of its file descriptors, checking that
+/// the connection made while it's out of them is shed and that it recovers once they're freed up.
+#[test]
+fn exhaustion() -> TestResult {
+	testinit();
+	run_parent(make_id!(), "tests::exhaustion::exhaustion_child")
+}
+
+/// Like `exhaustion`, but with the listener run by a thread pool server, which has to keep going
+/// after accepting fails.
+#[test]
+fn exhaustion_server() -> TestResult {
+	testinit();
+	run_parent(make_id!(), "tests::exhaustion::exhaustion_server_child")
+}
+
+fn run_parent(id: &'static str, child_test: &str) -> TestResult {
+	let path = !NameTypeSupport::query().ns_supported();
+	let (name, listener) = listen_and_pick_name(&mut namegen_local_socket(id, path), |nm| {
+		Listener::bind(nm.borrow())
+	})?;
+	let (pipe_sender, pipe_recver) = unnamed_pipe::pipe().opname("pipe creation")?;
+
+	let mut command = Command::new(env::current_exe().opname("current executable query")?);
+	command
+		.args(["--exact", child_test])
+		.env(CHILD_ENV_VAR, "1")
+		.stdout(Stdio::null());
+	pipe_sender
+		.inherit_at(&mut command, PIPE_FD)
+		.opname("pipe inheritance")?;
+	listener
+		.inherit_at(&mut command, LISTENER_FD)
+		.opname("listener inheritance")?;
+	let mut child = command.spawn().opname("child spawn")?;
+	drop((command, pipe_sender));
+
+	let mut pipe_recver = BufReader::new(pipe_recver);
+	let mut buf = String::new();
+	pipe_recver.read_line(&mut buf).opname("pipe receive")?;
+	ensure_eq!(buf, "exhausted\n");
+
+	// Shed by the child, which closes it without reading or writing anything.
+	let mut shed = Stream::connect(name.borrow()).opname("connect")?;
+	match shed.read(&mut [0; 16]) {
+		Ok(0) => {}
+		Err(e) if e.kind() == io::ErrorKind::ConnectionReset => {}
+		els => bail!("shed connection wasn't closed: {els:?}"),
+	}
+
+	// The listener stays open here even if the child stops accepting, which would leave the
+	// connection waiting in the backlog forever without a timeout.
+	let conn = UnixStream::from(OwnedFd::from(
+		Stream::connect(name.borrow()).opname("connect")?,
+	));
+	conn.set_read_timeout(Some(Duration::from_secs(10)))
+		.opname("timeout")?;
+	let mut conn = BufReader::new(conn);
+	buf.clear();
+	conn.read_line(&mut buf).opname("receive")?;
+	ensure_eq!(buf, "accepted\n");
+
+	ensure!(child.wait().opname("child wait")?.success(), "child failed");
+	drop(listener);
+	Ok(())
+}
+
+/// Files which take up all of the file descriptors in the child process.
+static FILLERS: Mutex<Vec<File>> = Mutex::new(Vec::new());
+static SHED: AtomicBool = AtomicBool::new(false);
+
+/// Child side of the `exhaustion` test, which does nothing unless run by it.
+#[test]
+fn exhaustion_child() -> TestResult {
+	if env::var_os(CHILD_ENV_VAR).is_none() {
+		return Ok(());
+	}
+	let (mut pipe_sender, listener) = adopt()?;
+	lower_fd_limit()?;
+	exhaust()?;
+	pipe_sender.write_all(b"exhausted\n").opname("pipe send")?;
+
+	let mut conn = listener.accept().opname("accept")?;
+	ensure!(
+		SHED.load(SeqCst),
+		"accepted a connection without shedding one"
+	);
+	conn.write_all(b"accepted\n").opname("send")?;
+	Ok(())
+}
+
+/// Child side of the `exhaustion_server` test, which does nothing unless run by it.
+#[test]
+fn exhaustion_server_child() -> TestResult {
+	if env::var_os(CHILD_ENV_VAR).is_none() {
+		return Ok(());
+	}
+	let (mut pipe_sender, listener) = adopt()?;
+	lower_fd_limit()?;
+	let (accepted_sender, accepted_recver) = mpsc::channel();
+	let handler = move |mut conn: Stream| {
+		let _ = conn.write_all(b"accepted\n");
+		let _ = accepted_sender.send(SHED.load(SeqCst));
+	};
+	let server =
+		serve(listener, handler, ServerOptions::new().worker_threads(1)).opname("serve")?;
+	exhaust()?;
+	pipe_sender.write_all(b"exhausted\n").opname("pipe send")?;
+
+	let Ok(shed) = accepted_recver.recv_timeout(Duration::from_secs(10)) else {
+		bail!("server stopped accepting: {:?}", server.shutdown());
+	};
+	ensure!(shed, "accepted a connection without shedding one");
+	server.shutdown().opname("server")
+}
+
+/// Adopts the pipe and the listener inherited from the parent, setting an exhaustion policy which
+/// frees up the file descriptors once a connection is shed.
+fn adopt() -> TestResult<(unnamed_pipe::Sender, Listener)> {
+	let pipe_sender =
+		unsafe { unnamed_pipe::Sender::from_inherited(PIPE_FD) }.opname("pipe adoption")?;
+	let mut listener =
+		unsafe { Listener::from_inherited(LISTENER_FD) }.opname("listener adoption")?;
+	let policy = ExhaustionPolicy::new()
+		.initial_backoff(Duration::from_millis(1))
+		.max_backoff(Duration::from_millis(20))
+		.on_exhaustion(|event| {
+			if event.shed_connection {
+				SHED.store(true, SeqCst);
+				if let Ok(mut fillers) = FILLERS.lock() {
+					fillers.clear();
+				}
+			}
+		});
+	listener
+		.set_exhaustion_policy(Some(policy))
+		.opname("policy")?;
+	Ok((pipe_sender, listener))
+}
+
+/// Keeps the number of files to open small.
+fn lower_fd_limit() -> TestResult {
+	let mut limit = libc::rlimit {
+		rlim_cur: 0,
+		rlim_max: 0,
+	};
+	ensure!(
+		unsafe { libc::getrlimit(libc::RLIMIT_NOFILE, &mut limit) } == 0,
+		"failed to query file descriptor limit: {}",
+		io::Error::last_os_error()
+	);
+	limit.rlim_cur = limit.rlim_cur.min(256);
+	ensure!(
+		unsafe { libc::setrlimit(libc::RLIMIT_NOFILE, &limit) } == 0,
+		"failed to lower file descriptor limit: {}",
+		io::Error::last_os_error()
+	);
+	Ok(())
+}
+
+fn exhaust() -> TestResult {
+	let mut fillers = FILLERS.lock().map_err(poison_error)?;
+	loop {
+		match File::open("/dev/null") {
+			Ok(file) => fillers.push(file),
+			Err(e) if e.raw_os_error() == Some(libc::EMFILE) => return Ok(()),
+			Err(e) => return Err(e).opname("filler open"),
+		}
+	}
+}
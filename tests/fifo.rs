@@ -0,0 +1,175 @@
+#![cfg(unix)]
+
+use crate::{os::unix::fifo_file::*, tests::util::*};
+use color_eyre::eyre::{bail, ensure};
+use std::{
+	io::{self, prelude::*, BufReader},
+	process::Command,
+	thread,
+	time::Duration,
+};
+
+const TIMEOUT: Duration = Duration::from_millis(100);
+
+fn msg(nr: u8) -> Box<str> {
+	message(Some(format_args!("Message {nr}")), false, Some('\n'))
+}
+
+#[test]
+fn send_and_recv() -> TestResult {
+	testinit();
+	let fifo = Fifo::create_in_private_dir("fifo", 0o600).opname("FIFO creation")?;
+	let path = fifo.path().to_owned();
+
+	let recver = fifo
+		.open_recver_nonblocking()
+		.opname("nonblocking receiver open")?;
+	let sender = thread::spawn(move || {
+		let mut sender = fifo.open_sender().opname("sender open")?;
+		for nr in 0..2 {
+			sender.write_all(msg(nr).as_bytes()).opname("send")?;
+		}
+		sender.flush().opname("flush")?;
+		TestResult::<_>::Ok(fifo)
+	});
+
+	let mut recver = BufReader::new(recver);
+	for nr in 0..2 {
+		let mut buf = String::new();
+		// The first receive may see EOF if the sender thread hasn't connected yet.
+		while buf.is_empty() {
+			recver.read_line(&mut buf).opname("receive")?;
+		}
+		ensure_eq!(buf, &*msg(nr));
+	}
+
+	let Ok(rslt) = sender.join() else {
+		bail!("sender thread panicked");
+	};
+	let fifo = rslt?;
+	ensure!(path.exists(), "FIFO file vanished while the Fifo was alive");
+	drop(fifo);
+	ensure!(!path.exists(), "FIFO file was not deleted on drop");
+	ensure!(
+		!path.parent().unwrap().exists(),
+		"private directory was not deleted on drop"
+	);
+	Ok(())
+}
+
+#[test]
+fn open_without_peer() -> TestResult {
+	testinit();
+	let fifo = Fifo::create_in_private_dir("fifo", 0o600).opname("FIFO creation")?;
+
+	let err = fifo.open_sender_nonblocking().unwrap_err();
+	ensure_eq!(err.kind(), io::ErrorKind::WouldBlock);
+	let err = fifo.open_sender_timeout(TIMEOUT).unwrap_err();
+	ensure_eq!(err.kind(), io::ErrorKind::TimedOut);
+	let err = fifo.open_recver_timeout(TIMEOUT).unwrap_err();
+	ensure_eq!(err.kind(), io::ErrorKind::TimedOut);
+
+	// After the receiver times out, the FIFO file must be usable again.
+	let _recver = fifo
+		.open_recver_nonblocking()
+		.opname("nonblocking receiver open")?;
+	fifo.open_sender_timeout(TIMEOUT)
+		.opname("sender open with receiver present")?;
+	Ok(())
+}
+
+#[test]
+fn recver_timeout_with_sender() -> TestResult {
+	testinit();
+	let fifo = Fifo::create_in_private_dir("fifo", 0o600).opname("FIFO creation")?;
+	let path = fifo.path().to_owned();
+	let sender = thread::spawn(move || open_sender_timeout(path, Duration::from_secs(10)));
+	let mut recver = fifo
+		.open_recver_timeout(Duration::from_secs(10))
+		.opname("receiver open")?;
+	let Ok(rslt) = sender.join() else {
+		bail!("sender thread panicked");
+	};
+	let mut sender = rslt.opname("sender open")?;
+	sender.write_all(b"x").opname("send")?;
+	drop(sender);
+	let mut buf = Vec::new();
+	recver.read_to_end(&mut buf).opname("receive")?;
+	ensure_eq!(buf, b"x");
+	Ok(())
+}
+
+#[test]
+fn private_dir_name_validation() -> TestResult {
+	testinit();
+	for bad in ["", "a/b"] {
+		let err = Fifo::create_in_private_dir(bad, 0o600).unwrap_err();
+		ensure_eq!(err.kind(), io::ErrorKind::InvalidInput);
+	}
+	Ok(())
+}
+
+#[test]
+fn duplex() -> TestResult {
+	testinit();
+	let channel = DuplexFifo::create_in_private_dir("channel", 0o600).opname("FIFO creation")?;
+	let base = channel.in_path().with_extension("");
+	let client = thread::spawn(move || {
+		let mut conn = BufReader::new(DuplexStream::connect_timeout(
+			base,
+			Duration::from_secs(10),
+		)?);
+		conn.get_mut().write_all(b"ping\n")?;
+		let mut buf = String::new();
+		conn.read_line(&mut buf)?;
+		io::Result::Ok(buf)
+	});
+
+	let mut conn = BufReader::new(
+		channel
+			.accept_timeout(Duration::from_secs(10))
+			.opname("accept")?,
+	);
+	let mut buf = String::new();
+	conn.read_line(&mut buf).opname("receive")?;
+	ensure_eq!(buf, "ping\n");
+	conn.get_mut().write_all(b"pong\n").opname("send")?;
+
+	let Ok(rslt) = client.join() else {
+		bail!("client thread panicked");
+	};
+	ensure_eq!(rslt.opname("client")?, "pong\n");
+	Ok(())
+}
+
+#[test]
+fn duplex_shell_client() -> TestResult {
+	testinit();
+	let channel = DuplexFifo::create_in_private_dir("channel", 0o600).opname("FIFO creation")?;
+	// Opens the receiving end first, in a blocking fashion.
+	let mut client = Command::new("sh")
+		.arg("-c")
+		.arg(r#"exec 4<"$1.out" 3>"$1.in"; echo ping >&3; read -r reply <&4; echo "$reply" >&3"#)
+		.arg("sh")
+		.arg(channel.in_path().with_extension(""))
+		.spawn()
+		.opname("shell spawn")?;
+
+	let mut conn = BufReader::new(
+		channel
+			.accept_timeout(Duration::from_secs(10))
+			.opname("accept")?,
+	);
+	let mut buf = String::new();
+	conn.read_line(&mut buf).opname("receive")?;
+	ensure_eq!(buf, "ping\n");
+	conn.get_mut().write_all(b"pong\n").opname("send")?;
+	buf.clear();
+	conn.read_line(&mut buf).opname("receive")?;
+	ensure_eq!(buf, "pong\n");
+	ensure!(
+		client.wait().opname("shell wait")?.success(),
+		"shell failed"
+	);
+	Ok(())
+}
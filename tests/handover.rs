@@ -0,0 +1,167 @@
+#![cfg(unix)]
+
+use crate::{
+	local_socket::{prelude::*, Listener, Name, NameTypeSupport, Stream},
+	os::unix::handover,
+	tests::util::*,
+};
+use color_eyre::eyre::ensure;
+use std::{
+	env,
+	io::{prelude::*, BufReader},
+	os::unix::io::{AsFd, AsRawFd},
+	path::Path,
+	process::{Command, Stdio},
+	sync::Arc,
+	thread,
+	time::Duration,
+};
+
+const CHILD_ENV_VAR: &str = "INTERPROCESS_TEST_HANDOVER_CHILD";
+const LISTENER_ENV_VAR: &str = "INTERPROCESS_TEST_HANDOVER_LISTENER";
+
+fn bind(id: &'static str) -> TestResult<(Arc<Name<'static>>, Listener)> {
+	// Filesystem names are preferred, since they're the ones that are reclaimed.
+	let path = NameTypeSupport::query().fs_supported();
+	listen_and_pick_name(&mut namegen_local_socket(id, path), |nm| {
+		Listener::bind(nm.borrow())
+	})
+}
+
+/// Returns whether the socket file of the name exists, or `true` for namespaced names.
+fn exists(name: &Name<'_>) -> bool {
+	!name.is_path() || Path::new(name.raw()).exists()
+}
+
+fn roundtrip(name: &Name<'_>, listener: &Listener) -> TestResult {
+	let client = {
+		let name = name.borrow().into_owned();
+		thread::spawn(move || -> TestResult {
+			let mut conn = BufReader::new(Stream::connect(name).opname("connect")?);
+			let mut buf = String::new();
+			conn.read_line(&mut buf).opname("receive")?;
+			ensure_eq!(buf, "hello\n");
+			Ok(())
+		})
+	};
+	let mut conn = listener.accept().opname("accept")?;
+	conn.write_all(b"hello\n").opname("send")?;
+	client.join().unwrap_or_else(|_| Ok(()))
+}
+
+#[test]
+fn handover_socket() -> TestResult {
+	testinit();
+	let (name, mut old) = bind(make_id!())?;
+	let (old_conn, new_conn) = Stream::pair().opname("stream pair creation")?;
+	handover::send_listener(&mut old, &old_conn).opname("send")?;
+	let new = handover::recv_listener(&new_conn).opname("receive")?;
+
+	// Both copies accept connections until the old one is dropped, which doesn't unlink the file.
+	roundtrip(&name, &old)?;
+	drop(old);
+	ensure!(
+		exists(&name),
+		"old listener reclaimed the name after handover"
+	);
+	roundtrip(&name, &new)?;
+	drop(new);
+	ensure!(
+		!name.is_path() || !exists(&name),
+		"new listener didn't reclaim the name"
+	);
+	Ok(())
+}
+
+#[test]
+fn handover_nonblocking() -> TestResult {
+	testinit();
+	let (name, mut old) = bind(make_id!())?;
+	// Switches the socket to nonblocking mode, which the old process relies on.
+	let _handle = old.shutdown_handle().opname("shutdown handle")?;
+	let (old_conn, new_conn) = Stream::pair().opname("stream pair creation")?;
+	handover::send_listener(&mut old, &old_conn).opname("send")?;
+	let new = handover::recv_listener(&new_conn).opname("receive")?;
+	let flags = unsafe { libc::fcntl(old.as_fd().as_raw_fd(), libc::F_GETFL) };
+	ensure!(
+		flags != -1 && flags & libc::O_NONBLOCK != 0,
+		"receiving the listener changed the mode of the socket"
+	);
+
+	// The new listener is in blocking mode and waits for the client despite the socket's mode.
+	let client = {
+		let name = Arc::clone(&name);
+		thread::spawn(move || -> TestResult {
+			// Makes sure that the listener gets to wait for the client.
+			thread::sleep(Duration::from_millis(50));
+			Stream::connect(name.borrow()).opname("connect")?;
+			Ok(())
+		})
+	};
+	let rslt = new.accept().opname("accept").map(drop);
+	drop((old, new));
+	rslt?;
+	client.join().unwrap_or_else(|_| Ok(()))
+}
+
+#[test]
+fn handover_replaced() -> TestResult {
+	testinit();
+	let (name, mut old) = bind(make_id!())?;
+	if !name.is_path() {
+		return Ok(());
+	}
+	let (old_conn, new_conn) = Stream::pair().opname("stream pair creation")?;
+	handover::send_listener(&mut old, &old_conn).opname("send")?;
+	// Takes the path over before the new process adopts the listener.
+	let replacement = Listener::bind_replacing(name.borrow()).opname("replacing bind")?;
+	let new = handover::recv_listener(&new_conn).opname("receive")?;
+	drop((old, new));
+	ensure!(
+		exists(&name),
+		"adopted listener deleted the socket file of its replacement"
+	);
+	drop(replacement);
+	ensure!(!exists(&name), "replacement didn't reclaim the name");
+	Ok(())
+}
+
+#[test]
+fn handover_inherit() -> TestResult {
+	testinit();
+	let (name, mut listener) = bind(make_id!())?;
+	let mut command = Command::new(env::current_exe().opname("current executable query")?);
+	command
+		.args(["--exact", "tests::handover::handover_inherit_child"])
+		.env(CHILD_ENV_VAR, "1")
+		.stdout(Stdio::null());
+	handover::inherit_listener(&mut listener, &mut command, LISTENER_ENV_VAR)
+		.opname("inheritance")?;
+	let mut child = command.spawn().opname("child spawn")?;
+	drop((command, listener));
+	ensure!(exists(&name), "parent reclaimed the name after handover");
+
+	let mut conn = BufReader::new(Stream::connect(name.borrow()).opname("connect")?);
+	let mut buf = String::new();
+	conn.read_line(&mut buf).opname("receive")?;
+	ensure_eq!(buf, "hello\n");
+	ensure!(child.wait().opname("child wait")?.success(), "child failed");
+	ensure!(
+		!name.is_path() || !exists(&name),
+		"child didn't reclaim the name"
+	);
+	Ok(())
+}
+
+/// Child side of the `handover_inherit` test, which does nothing unless run by it.
+#[test]
+fn handover_inherit_child() -> TestResult {
+	if env::var_os(CHILD_ENV_VAR).is_none() {
+		return Ok(());
+	}
+	let listener =
+		unsafe { handover::adopt_inherited_listener(LISTENER_ENV_VAR) }.opname("adoption")?;
+	let mut conn = listener.accept().opname("accept")?;
+	conn.write_all(b"hello\n").opname("send")?;
+	Ok(())
+}
@@ -2,7 +2,19 @@
 #[macro_use]
 mod util;
 
+mod child_channel;
+mod exhaustion;
+mod fifo;
+mod handover;
+mod inherit;
 mod local_socket;
 mod named_pipe;
+mod pipeline;
+mod prefork;
+mod replace;
+mod sd_notify;
+mod single_instance;
+mod socket_activation;
+mod tokio_fifo;
 mod tokio_local_socket;
 mod tokio_named_pipe;
@@ -0,0 +1,74 @@
+#![cfg(unix)]
+
+use crate::{
+	local_socket::{prelude::*, Stream},
+	os::unix::Inheritable,
+	tests::util::*,
+	unnamed_pipe,
+};
+use color_eyre::eyre::ensure;
+use std::{
+	env,
+	io::{prelude::*, BufReader},
+	process::{Command, Stdio},
+};
+
+const CHILD_ENV_VAR: &str = "INTERPROCESS_TEST_INHERIT_CHILD";
+const STREAM_ENV_VAR: &str = "INTERPROCESS_TEST_INHERIT_STREAM";
+const PIPE_FD: i32 = 10;
+
+#[test]
+fn inherit() -> TestResult {
+	testinit();
+	let (ours, theirs) = Stream::pair().opname("stream pair creation")?;
+	let (pipe_sender, pipe_recver) = unnamed_pipe::pipe().opname("pipe creation")?;
+
+	let mut command = Command::new(env::current_exe().opname("current executable query")?);
+	command
+		.args(["--exact", "tests::inherit::inherit_child"])
+		.env(CHILD_ENV_VAR, "1")
+		.stdout(Stdio::null());
+	theirs
+		.inherit_env(&mut command, STREAM_ENV_VAR)
+		.opname("stream inheritance")?;
+	pipe_sender
+		.inherit_at(&mut command, PIPE_FD)
+		.opname("pipe inheritance")?;
+	let mut child = command.spawn().opname("child spawn")?;
+	// Our copies of the child's objects must be gone for end-of-file conditions to be observed.
+	drop((command, theirs, pipe_sender));
+
+	let mut ours = BufReader::new(ours);
+	ours.get_mut().write_all(b"ping\n").opname("send")?;
+	let mut buf = String::new();
+	ours.read_line(&mut buf).opname("receive")?;
+	ensure_eq!(buf, "pong\n");
+
+	buf.clear();
+	BufReader::new(pipe_recver)
+		.read_to_string(&mut buf)
+		.opname("pipe receive")?;
+	ensure_eq!(buf, "done\n");
+
+	ensure!(child.wait().opname("child wait")?.success(), "child failed");
+	Ok(())
+}
+
+/// Child side of the `inherit` test, which does nothing unless run by it.
+#[test]
+fn inherit_child() -> TestResult {
+	if env::var_os(CHILD_ENV_VAR).is_none() {
+		return Ok(());
+	}
+	let conn = unsafe { Stream::from_inherited_env(STREAM_ENV_VAR) }.opname("stream adoption")?;
+	let mut pipe_sender =
+		unsafe { unnamed_pipe::Sender::from_inherited(PIPE_FD) }.opname("pipe adoption")?;
+
+	let mut conn = BufReader::new(conn);
+	let mut buf = String::new();
+	conn.read_line(&mut buf).opname("receive")?;
+	ensure_eq!(buf, "ping\n");
+	conn.get_mut().write_all(b"pong\n").opname("send")?;
+	pipe_sender.write_all(b"done\n").opname("pipe send")?;
+	Ok(())
+}
@@ -1,7 +1,18 @@
 // TODO test various error conditions
 
+#[cfg(unix)]
+mod exec;
+pub(crate) mod framing;
+#[cfg(feature = "jsonrpc")]
+mod jsonrpc;
 mod no_server;
+mod pair;
+mod reclaim;
+mod server;
+mod shutdown;
 mod stream;
+#[cfg(feature = "serde")]
+pub(crate) mod typed;
 
 use crate::{local_socket::NameTypeSupport, tests::util::*};
 
@@ -18,6 +29,28 @@ fn test_no_server(id: &'static str, path: bool) -> TestResult {
 	no_server::run_and_verify_error(id, path)
 }
 
+fn test_server(id: &'static str, path: bool) -> TestResult {
+	testinit();
+	server::run(id, path)
+}
+
+fn test_shutdown(id: &'static str, path: bool) -> TestResult {
+	testinit();
+	shutdown::run(id, path)
+}
+
+#[cfg(unix)]
+fn test_shutdown_shared(id: &'static str, path: bool) -> TestResult {
+	testinit();
+	shutdown::run_shared(id, path)
+}
+
+#[cfg(unix)]
+fn test_exec(id: &'static str, path: bool) -> TestResult {
+	testinit();
+	exec::run(id, path)
+}
+
 macro_rules! tests {
 	(@querymethod true $e:expr) => { NameTypeSupport::fs_supported($e) };
 	(@querymethod false $e:expr) => { NameTypeSupport::ns_supported($e) };
@@ -43,3 +76,83 @@ tests! {test_no_server
 	no_server_file			true
 	no_server_namespaced	false
 }
+
+tests! {test_server
+	server_file			true
+	server_namespaced	false
+}
+
+tests! {test_shutdown
+	shutdown_file		true
+	shutdown_namespaced	false
+}
+
+#[cfg(unix)]
+tests! {test_shutdown_shared
+	shutdown_shared_file		true
+	shutdown_shared_namespaced	false
+}
+
+#[cfg(unix)]
+tests! {test_exec
+	exec_file		true
+	exec_namespaced	false
+}
+
+#[test]
+fn stream_pair() -> TestResult {
+	testinit();
+	pair::run()
+}
+
+#[test]
+fn framing() -> TestResult {
+	testinit();
+	for opts in framing::options() {
+		framing::run(opts)?;
+	}
+	Ok(())
+}
+
+#[test]
+fn framing_limits() -> TestResult {
+	testinit();
+	framing::run_limits()
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn typed() -> TestResult {
+	testinit();
+	use crate::local_socket::typed::*;
+	typed::run(Bincode)?;
+	typed::run_errors::<Bincode>()?;
+	#[cfg(feature = "json")]
+	{
+		typed::run(Json)?;
+		typed::run_errors::<Json>()?;
+	}
+	#[cfg(feature = "postcard")]
+	{
+		typed::run(Postcard)?;
+		typed::run_errors::<Postcard>()?;
+	}
+	Ok(())
+}
+
+#[cfg(feature = "jsonrpc")]
+#[test]
+fn jsonrpc() -> TestResult {
+	testinit();
+	jsonrpc::run_framing()?;
+	jsonrpc::run_messages()
+}
+
+#[test]
+fn name_reclamation() -> TestResult {
+	testinit();
+	if NameTypeSupport::query().fs_supported() {
+		reclaim::run(make_id!())?;
+	}
+	Ok(())
+}
@@ -0,0 +1,67 @@
+use crate::{
+	local_socket::{
+		prelude::*,
+		server::{serve_exec, ExecOptions, ServerOptions},
+		Listener, Name, Stream,
+	},
+	tests::util::*,
+};
+use std::{
+	io::{prelude::*, BufReader},
+	process::{self, Command},
+	sync::Arc,
+	thread,
+};
+
+/// Replies with the line it receives and the credentials it was given, then exits.
+const SCRIPT: &str = r#"read -r line; echo "$line:$PROTO:$UNIXREMOTEEUID:$UNIXREMOTEPID""#;
+
+/// Sends a line and returns the line sent back, checking that the connection is closed afterwards.
+fn roundtrip(name: &Name<'_>, line: &str) -> TestResult<String> {
+	let mut conn = BufReader::new(Stream::connect(name.borrow()).opname("connect")?);
+	conn.get_mut()
+		.write_all(format!("{line}\n").as_bytes())
+		.opname("send")?;
+	let mut response = String::new();
+	conn.read_line(&mut response).opname("receive")?;
+	let mut rest = String::new();
+	conn.read_to_string(&mut rest).opname("receive")?;
+	ensure_eq!(rest, "");
+	Ok(response)
+}
+
+pub fn run(id: &'static str, path: bool) -> TestResult {
+	let (name, listener) = listen_and_pick_name(&mut namegen_local_socket(id, path), |nm| {
+		Listener::bind(nm.borrow())
+	})?;
+	let command = || {
+		let mut command = Command::new("/bin/sh");
+		command.args(["-c", SCRIPT]);
+		command
+	};
+	let opts = ExecOptions::new()
+		.server(ServerOptions::new().worker_threads(2))
+		.peer_credentials(true);
+	let server = serve_exec(listener, command, opts).opname("serve")?;
+
+	// SAFETY: geteuid() is always successful.
+	let euid = unsafe { libc::geteuid() };
+	let pid = if cfg!(any(target_os = "linux", target_os = "android")) {
+		process::id().to_string()
+	} else {
+		String::new()
+	};
+	let clients = (0..4)
+		.map(|i| {
+			let name = Arc::clone(&name);
+			thread::spawn(move || roundtrip(&name, &format!("client {i}")))
+		})
+		.collect::<Vec<_>>();
+	for (i, client) in clients.into_iter().enumerate() {
+		let response = client.join().unwrap_or_else(|_| Ok(String::new()))?;
+		ensure_eq!(response, format!("client {i}:UNIX:{euid}:{pid}\n"));
+	}
+
+	server.shutdown().opname("shutdown")?;
+	Ok(())
+}
@@ -0,0 +1,94 @@
+use crate::{
+	local_socket::{
+		framing::{Endianness, Framed, FramingOptions, LengthPrefix},
+		prelude::*,
+		Stream,
+	},
+	tests::util::*,
+};
+use color_eyre::eyre::bail;
+use std::{
+	io::{self, prelude::*},
+	thread,
+};
+
+pub fn frames() -> Vec<Vec<u8>> {
+	vec![
+		Vec::new(),
+		b"a".to_vec(),
+		(0..300).map(|i| i as u8).collect(),
+		(0..20000).map(|i| (i % 251) as u8).collect(),
+	]
+}
+
+pub fn options() -> [FramingOptions; 4] {
+	let opts = FramingOptions::new();
+	[
+		opts,
+		opts.endianness(Endianness::Big),
+		opts.length_prefix(LengthPrefix::U64)
+			.endianness(Endianness::Big),
+		opts.length_prefix(LengthPrefix::Varint),
+	]
+}
+
+pub fn run(opts: FramingOptions) -> TestResult {
+	let (s1, s2) = Stream::pair().opname("pair creation")?;
+
+	let thread = thread::spawn(move || -> TestResult {
+		let (recver, sender) = s2.split();
+		let mut recver = Framed::with_options(recver, opts);
+		let mut sender = Framed::with_options(sender, opts);
+		while let Some(frame) = recver.recv_frame().opname("receive")? {
+			sender.send_frame(frame).opname("send")?;
+		}
+		Ok(())
+	});
+
+	let mut s1 = Framed::with_options(s1, opts);
+	let frames = frames();
+	for frame in &frames {
+		s1.send_frame(frame).opname("send")?;
+	}
+	for frame in &frames {
+		let echoed = s1.recv_frame().opname("receive")?;
+		ensure_eq!(echoed, Some(&frame[..]));
+	}
+	drop(s1);
+
+	let Ok(rslt) = thread.join() else {
+		bail!("thread panicked");
+	};
+	rslt
+}
+
+pub fn run_limits() -> TestResult {
+	let (s1, s2) = Stream::pair().opname("pair creation")?;
+	let small = FramingOptions::new().max_frame_size(16);
+	let (mut s1, mut s2) = (Framed::new(s1), Framed::with_options(s2, small));
+
+	let err = s2.send_frame(&[0; 17]).err().map(|e| e.kind());
+	ensure_eq!(err, Some(io::ErrorKind::InvalidInput));
+
+	s1.send_frame(&[0; 16]).opname("send")?;
+	s1.send_frame(&[0; 17]).opname("send")?;
+	ensure_eq!(s2.recv_frame().opname("receive")?, Some(&[0; 16][..]));
+	let err = s2.recv_frame().err().map(|e| e.kind());
+	ensure_eq!(err, Some(io::ErrorKind::InvalidData));
+
+	let (s1, s2) = Stream::pair().opname("pair creation")?;
+	let mut s2 = Framed::new(s2);
+	let mut s1 = Framed::new(s1);
+	s1.send_frame(b"last").opname("send")?;
+	drop(s1);
+	ensure_eq!(s2.recv_frame().opname("receive")?, Some(&b"last"[..]));
+	ensure_eq!(s2.recv_frame().opname("receive")?, None);
+
+	let (mut s1, s2) = Stream::pair().opname("pair creation")?;
+	let mut s2 = Framed::new(s2);
+	s1.write_all(&[4, 0, 0, 0, b'a']).opname("send")?;
+	drop(s1);
+	let err = s2.recv_frame().err().map(|e| e.kind());
+	ensure_eq!(err, Some(io::ErrorKind::UnexpectedEof));
+	Ok(())
+}
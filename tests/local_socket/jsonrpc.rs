@@ -0,0 +1,101 @@
+use crate::{
+	local_socket::{
+		jsonrpc::{ErrorObject, Id, LspFramed, Message, Request, Response},
+		prelude::*,
+		Stream,
+	},
+	tests::util::*,
+};
+use color_eyre::eyre::ensure;
+use serde_json::{json, Value};
+use std::io::{self, prelude::*};
+
+pub fn run_framing() -> TestResult {
+	let (s1, s2) = Stream::pair().opname("pair creation")?;
+	let (mut a, mut b) = (LspFramed::new(s1), LspFramed::new(s2));
+	a.send_message(b"{}").opname("send")?;
+	a.send_message(b"").opname("send")?;
+	// Other header fields are ignored and the name of the field is case-insensitive.
+	a.get_mut()
+		.write_all(b"Content-Type: application/vscode-jsonrpc; charset=utf-8\r\ncontent-length:  5\r\n\r\nhello")
+		.opname("raw send")?;
+	ensure_eq!(b.recv_message().opname("receive")?, Some(&b"{}"[..]));
+	ensure_eq!(b.recv_message().opname("receive")?, Some(&b""[..]));
+	ensure_eq!(b.recv_message().opname("receive")?, Some(&b"hello"[..]));
+	drop(a);
+	ensure_eq!(b.recv_message().opname("receive")?, None);
+
+	let bad_headers: [&[u8]; 4] = [
+		b"Content-Type: text/plain\r\n\r\n",
+		b"Content-Length: five\r\n\r\n",
+		b"Content-Length: 1\r\nContent-Length: 1\r\n\r\nx",
+		b"Content-Length: 1000\r\n\r\n",
+	];
+	for header in bad_headers {
+		let (s1, s2) = Stream::pair().opname("pair creation")?;
+		let mut a = s1;
+		let mut b = LspFramed::with_max_message_size(s2, 100);
+		a.write_all(header).opname("raw send")?;
+		let err = b.recv_message().err().map(|e| e.kind());
+		ensure_eq!(err, Some(io::ErrorKind::InvalidData));
+	}
+
+	let (s1, _s2) = Stream::pair().opname("pair creation")?;
+	let mut a = LspFramed::with_max_message_size(s1, 1);
+	let err = a.send_message(b"{}").err().map(|e| e.kind());
+	ensure_eq!(err, Some(io::ErrorKind::InvalidInput));
+	Ok(())
+}
+
+pub fn run_messages() -> TestResult {
+	let parse = |v: Value| Message::try_from(v);
+	let request = Request::new("add", Some(json!([1, 2])), 7);
+	ensure_eq!(
+		serde_json::to_value(&request)?,
+		json!({ "jsonrpc": "2.0", "method": "add", "params": [1, 2], "id": 7 })
+	);
+	ensure_eq!(
+		parse(serde_json::to_value(&request)?),
+		Ok(Message::Request(request))
+	);
+
+	let notification = Request::notification("exit", None);
+	ensure!(notification.is_notification(), "notification has an ID");
+	ensure_eq!(
+		serde_json::to_value(&notification)?,
+		json!({ "jsonrpc": "2.0", "method": "exit" })
+	);
+
+	// A null result must survive the round trip.
+	let response = Response::success(Some(Id::from("a")), Value::Null);
+	let value = serde_json::to_value(&response)?;
+	ensure_eq!(
+		value,
+		json!({ "jsonrpc": "2.0", "result": null, "id": "a" })
+	);
+	ensure_eq!(serde_json::from_value::<Response>(value)?, response);
+
+	let error = ErrorObject::method_not_found("nope").with_data(json!("extra"));
+	let response = Response::error(None, error.clone());
+	let value = serde_json::to_value(&response)?;
+	ensure_eq!(value["error"]["code"], json!(ErrorObject::METHOD_NOT_FOUND));
+	ensure_eq!(value["id"], Value::Null);
+	ensure_eq!(parse(value), Ok(Message::Response(response)));
+
+	let invalid = [
+		json!([]),
+		json!({ "method": "no version" }),
+		json!({ "jsonrpc": "1.0", "method": "wrong version" }),
+		json!({ "jsonrpc": "2.0", "method": 1 }),
+		json!({ "jsonrpc": "2.0", "method": "m", "params": 1 }),
+		json!({ "jsonrpc": "2.0", "method": "m", "id": 1.5 }),
+		json!({ "jsonrpc": "2.0", "result": 1 }),
+		json!({ "jsonrpc": "2.0", "id": 1 }),
+		json!({ "jsonrpc": "2.0", "id": 1, "result": 1, "error": error }),
+	];
+	for value in invalid {
+		let code = parse(value.clone()).err().map(|e| e.code);
+		ensure_eq!(code, Some(ErrorObject::INVALID_REQUEST), "{value}");
+	}
+	Ok(())
+}
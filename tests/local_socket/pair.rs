@@ -0,0 +1,39 @@
+use crate::{
+	local_socket::{prelude::*, Stream},
+	tests::util::*,
+};
+use color_eyre::eyre::bail;
+use std::{
+	io::{prelude::*, BufReader},
+	thread,
+};
+
+pub fn run() -> TestResult {
+	let (s1, s2) = Stream::pair().opname("pair creation")?;
+
+	let thread = thread::spawn(move || -> TestResult<Stream> {
+		let (recver, mut sender) = s2.split();
+		let mut recver = BufReader::new(recver);
+		let mut buf = String::new();
+		recver.read_line(&mut buf).opname("receive")?;
+		ensure_eq!(buf, "ping\n");
+		sender.write_all(b"pong\n").opname("send")?;
+		Stream::reunite(recver.into_inner(), sender).opname("reunite")
+	});
+
+	let mut s1 = BufReader::new(s1);
+	s1.get_mut().write_all(b"ping\n").opname("send")?;
+	let mut buf = String::new();
+	s1.read_line(&mut buf).opname("receive")?;
+	ensure_eq!(buf, "pong\n");
+
+	let Ok(rslt) = thread.join() else {
+		bail!("thread panicked");
+	};
+	let mut s2 = rslt?;
+	drop(s1);
+	let mut rest = Vec::new();
+	s2.read_to_end(&mut rest).opname("receive")?;
+	ensure_eq!(rest, b"");
+	Ok(())
+}
@@ -0,0 +1,24 @@
+//! Tests that listeners delete their socket files when dropped, unless told not to.
+
+use crate::{
+	local_socket::{prelude::*, Listener},
+	tests::util::*,
+};
+use color_eyre::eyre::ensure;
+use std::{fs, path::Path};
+
+pub fn run(id: &'static str) -> TestResult {
+	let (name, listener) = listen_and_pick_name(&mut namegen_local_socket(id, true), |nm| {
+		Listener::bind(nm.borrow())
+	})?;
+	let path = Path::new(name.raw());
+	ensure!(path.exists(), "socket file wasn't created");
+	drop(listener);
+	ensure!(!path.exists(), "socket file wasn't deleted");
+
+	let listener = Listener::bind_without_name_reclamation(name.borrow()).opname("rebind")?;
+	drop(listener);
+	ensure!(path.exists(), "socket file was deleted despite opting out");
+	fs::remove_file(path).opname("cleanup")?;
+	Ok(())
+}
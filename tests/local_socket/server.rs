@@ -0,0 +1,85 @@
+use crate::{
+	local_socket::{
+		prelude::*,
+		server::{serve, ServerOptions},
+		Listener, Name, Stream,
+	},
+	tests::util::*,
+};
+use color_eyre::eyre::ensure;
+use std::{
+	io::{BufRead, BufReader, Write},
+	sync::{
+		atomic::{AtomicUsize, Ordering::SeqCst},
+		Arc,
+	},
+	thread,
+	time::Duration,
+};
+
+/// Sends a line and returns the line sent back, or `None` if the connection was closed first.
+fn roundtrip(name: &Name<'_>, line: &str) -> TestResult<Option<String>> {
+	let mut conn = BufReader::new(Stream::connect(name.borrow()).opname("connect")?);
+	conn.get_mut()
+		.write_all(format!("{line}\n").as_bytes())
+		.opname("send")?;
+	let mut response = String::new();
+	conn.read_line(&mut response).opname("receive")?;
+	Ok(response.strip_suffix('\n').map(str::to_owned))
+}
+
+/// Handler which echoes a line back, panicking instead if the line is `panic`, and records the
+/// maximum number of connections handled at once.
+fn handler(max_active: Arc<AtomicUsize>) -> impl Fn(Stream) + Send + Sync {
+	let active = AtomicUsize::new(0);
+	move |conn| {
+		let now_active = active.fetch_add(1, SeqCst).saturating_add(1);
+		max_active.fetch_max(now_active, SeqCst);
+		let mut conn = BufReader::new(conn);
+		let mut line = String::new();
+		let _ = conn.read_line(&mut line);
+		// Gives the other clients a chance to exceed the limit, if it isn't enforced.
+		thread::sleep(Duration::from_millis(10));
+		active.fetch_sub(1, SeqCst);
+		if line == "panic\n" {
+			panic!("requested panic");
+		}
+		let _ = conn.get_mut().write_all(line.as_bytes());
+	}
+}
+
+pub fn run(id: &'static str, path: bool) -> TestResult {
+	let (name, listener) = listen_and_pick_name(&mut namegen_local_socket(id, path), |nm| {
+		Listener::bind(nm.borrow())
+	})?;
+	let max_active = Arc::new(AtomicUsize::new(0));
+	let handler = handler(Arc::clone(&max_active));
+	let opts = ServerOptions::new().worker_threads(4).max_connections(2);
+	let server = serve(listener, handler, opts).opname("serve")?;
+
+	let clients = (0..8)
+		.map(|i| {
+			let name = Arc::clone(&name);
+			thread::spawn(move || roundtrip(&name, &format!("client {i}")))
+		})
+		.collect::<Vec<_>>();
+	for (i, client) in clients.into_iter().enumerate() {
+		let response = client.join().unwrap_or_else(|_| Ok(None))?;
+		ensure_eq!(response, Some(format!("client {i}")));
+	}
+	ensure!(
+		max_active.load(SeqCst) <= 2,
+		"more connections than the maximum were handled at once"
+	);
+
+	// A panicking handler only drops its own connection.
+	ensure_eq!(roundtrip(&name, "panic")?, None);
+	ensure_eq!(roundtrip(&name, "still up")?, Some("still up".to_owned()));
+
+	server.shutdown().opname("shutdown")?;
+	ensure!(
+		Stream::connect(name.borrow()).is_err(),
+		"listener still accepting after shutdown"
+	);
+	Ok(())
+}
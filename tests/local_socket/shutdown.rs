@@ -0,0 +1,102 @@
+use crate::{
+	error::ListenerShutdownError,
+	local_socket::{prelude::*, Listener, Stream},
+	tests::util::*,
+};
+use color_eyre::eyre::{bail, ensure};
+use std::{
+	io::{self, Read, Write},
+	sync::{mpsc, Arc},
+	thread,
+	time::{Duration, Instant},
+};
+
+pub fn run(id: &'static str, path: bool) -> TestResult {
+	let (name, listener) = listen_and_pick_name(&mut namegen_local_socket(id, path), |nm| {
+		Listener::bind(nm.borrow())
+	})?;
+
+	// Nobody connects, so the timeout elapses.
+	let start = Instant::now();
+	match listener.accept_timeout(Duration::from_millis(50)) {
+		Err(e) if e.kind() == io::ErrorKind::TimedOut => {}
+		Err(e) => bail!("accepting failed with an unexpected error: {e}"),
+		Ok(..) => bail!("accepted a connection nobody made"),
+	}
+	ensure!(
+		start.elapsed() >= Duration::from_millis(50),
+		"accept timed out too early"
+	);
+
+	// A client that does connect is accepted before the timeout, with a blocking stream.
+	let client = {
+		let name = Arc::clone(&name);
+		thread::spawn(move || -> TestResult {
+			let mut conn = Stream::connect(name.borrow()).opname("connect")?;
+			conn.write_all(b"hi").opname("send")?;
+			Ok(())
+		})
+	};
+	let mut conn = listener
+		.accept_timeout(Duration::from_secs(10))
+		.opname("accept")?;
+	let mut buf = [0; 2];
+	conn.read_exact(&mut buf).opname("receive")?;
+	ensure_eq!(&buf, b"hi");
+	client
+		.join()
+		.unwrap_or_else(|_| bail!("client thread panicked"))?;
+
+	// Shutting down wakes up a thread blocked in the main loop.
+	let handle = listener.shutdown_handle().opname("shutdown handle")?;
+	let (done_tx, done_rx) = mpsc::channel();
+	let server = thread::spawn(move || {
+		let accepted = listener.incoming().count();
+		let after = listener.accept().map(drop);
+		let _ = done_tx.send(());
+		(accepted, after)
+	});
+	thread::sleep(Duration::from_millis(50));
+	ensure!(!handle.is_shut_down(), "shut down before being asked to");
+	handle.shutdown();
+	if done_rx.recv_timeout(Duration::from_secs(10)).is_err() {
+		bail!("blocked accept wasn't woken up by the shutdown");
+	}
+	let Ok((accepted, after)) = server.join() else {
+		bail!("server thread panicked");
+	};
+	ensure_eq!(accepted, 0);
+	match after {
+		Err(e) if ListenerShutdownError::is(&e) => {}
+		els => bail!("accepting after shutdown didn't fail as expected: {els:?}"),
+	}
+	Ok(())
+}
+
+/// Checks that a listener which shares its socket with one that has a shutdown handle, and thus
+/// puts the socket in nonblocking mode, still blocks in `.accept()`.
+#[cfg(unix)]
+pub fn run_shared(id: &'static str, path: bool) -> TestResult {
+	use std::os::unix::io::AsFd;
+	let (name, listener) = listen_and_pick_name(&mut namegen_local_socket(id, path), |nm| {
+		Listener::bind(nm.borrow())
+	})?;
+	let fd = listener.as_fd().try_clone_to_owned().opname("duplication")?;
+	let shared = Listener::from(fd);
+	let _handle = listener.shutdown_handle().opname("shutdown handle")?;
+
+	let client = thread::spawn(move || -> TestResult {
+		// Makes sure that the listener gets to wait for the client.
+		thread::sleep(Duration::from_millis(50));
+		let mut conn = Stream::connect(name.borrow()).opname("connect")?;
+		conn.write_all(b"hi").opname("send")?;
+		Ok(())
+	});
+	let mut conn = shared.accept().opname("accept")?;
+	let mut buf = [0; 2];
+	conn.read_exact(&mut buf).opname("receive")?;
+	ensure_eq!(&buf, b"hi");
+	client
+		.join()
+		.unwrap_or_else(|_| bail!("client thread panicked"))
+}
@@ -0,0 +1,93 @@
+use crate::{
+	local_socket::{
+		framing::FramingOptions,
+		prelude::*,
+		typed::{Format, TypedStream},
+		Stream,
+	},
+	tests::util::*,
+};
+use color_eyre::eyre::{bail, ensure};
+use serde::{Deserialize, Serialize};
+use std::{io, thread};
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Request {
+	pub id: u32,
+	pub text: String,
+}
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Response {
+	Length(u32, usize),
+	Empty(u32),
+}
+impl Response {
+	pub fn to(req: &Request) -> Self {
+		if req.text.is_empty() {
+			Self::Empty(req.id)
+		} else {
+			Self::Length(req.id, req.text.len())
+		}
+	}
+}
+
+pub fn requests() -> Vec<Request> {
+	["", "Hello", "world!"]
+		.into_iter()
+		.zip(0..)
+		.map(|(text, id)| Request {
+			id,
+			text: text.to_owned(),
+		})
+		.collect()
+}
+
+pub fn run<F: Format + Clone + Send + 'static>(format: F) -> TestResult {
+	let opts = FramingOptions::new();
+	let (s1, s2) = Stream::pair().opname("pair creation")?;
+
+	let server_format = format.clone();
+	let thread = thread::spawn(move || -> TestResult {
+		let server = TypedStream::<Response, Request, F>::with_format(s2, server_format, opts);
+		let (mut recver, mut sender) = server.split();
+		while let Some(req) = recver.recv().opname("receive")? {
+			sender.send(&Response::to(&req)).opname("send")?;
+		}
+		let Ok(server) = TypedStream::reunite(recver, sender) else {
+			bail!("reunite failed");
+		};
+		drop(server);
+		Ok(())
+	});
+
+	let mut client = TypedStream::<Request, Response, F>::with_format(s1, format, opts);
+	for req in requests() {
+		client.send(&req).opname("send")?;
+		ensure_eq!(client.recv().opname("receive")?, Some(Response::to(&req)));
+	}
+	drop(client);
+
+	let Ok(rslt) = thread.join() else {
+		bail!("thread panicked");
+	};
+	rslt
+}
+
+pub fn run_errors<F: Format + Default>() -> TestResult {
+	let (s1, s2) = Stream::pair().opname("pair creation")?;
+	let mut client = TypedStream::<String, u32, F>::new(s1);
+	let mut server = TypedStream::<u32, u8, F>::new(s2);
+	client.send(&"too long for u8".to_owned()).opname("send")?;
+	let err = server.recv().err().map(|e| e.kind());
+	ensure_eq!(err, Some(io::ErrorKind::InvalidData));
+
+	let (a1, _a2) = Stream::pair().opname("pair creation")?;
+	let (b1, _b2) = Stream::pair().opname("pair creation")?;
+	let (arh, _ash) = TypedStream::<u8, u8>::new(a1).split();
+	let (_brh, bsh) = TypedStream::<u8, u8>::new(b1).split();
+	ensure!(
+		TypedStream::reunite(arh, bsh).is_err(),
+		"halves of different streams were reunited"
+	);
+	Ok(())
+}
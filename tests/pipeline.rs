@@ -0,0 +1,54 @@
+#![cfg(unix)]
+
+use crate::{tests::util::*, unnamed_pipe::Pipeline};
+use color_eyre::eyre::{bail, ensure};
+use std::{io::prelude::*, process::Command};
+
+fn tr(args: &[&str]) -> Command {
+	let mut command = Command::new("tr");
+	command.args(args);
+	command
+}
+
+#[test]
+fn pipeline() -> TestResult {
+	testinit();
+	let mut pipeline = Pipeline::new()
+		.stage(tr(&["a-z", "A-Z"]))
+		.tap()
+		.stage(tr(&["-d", "O"]))
+		.spawn()
+		.opname("pipeline spawn")?;
+	ensure_eq!(pipeline.children.len(), 2);
+	ensure_eq!(pipeline.taps.len(), 1);
+
+	if let Some(mut stdin) = pipeline.stdin.take() {
+		stdin.write_all(b"hello world\n").opname("send")?;
+	}
+	let mut buf = String::new();
+	pipeline.stdout.read_to_string(&mut buf).opname("receive")?;
+	ensure_eq!(buf, "HELL WRLD\n");
+
+	buf.clear();
+	let Some(tap) = pipeline.taps.first_mut() else {
+		bail!("tap missing")
+	};
+	tap.read_to_string(&mut buf).opname("tap receive")?;
+	ensure_eq!(buf, "HELLO WORLD\n");
+
+	for status in pipeline.wait().opname("pipeline wait")? {
+		ensure!(status.success(), "stage failed");
+	}
+	Ok(())
+}
+
+#[test]
+fn pipeline_empty() -> TestResult {
+	testinit();
+	let err = Pipeline::new().tap().spawn().err();
+	ensure_eq!(
+		err.map(|e| e.kind()),
+		Some(std::io::ErrorKind::InvalidInput)
+	);
+	Ok(())
+}
@@ -0,0 +1,198 @@
+#![cfg(unix)]
+
+use crate::{
+	local_socket::{prelude::*, Listener, Name, NameTypeSupport, Stream},
+	os::unix::{
+		handover,
+		prefork::{PreforkOptions, Supervisor},
+	},
+	tests::util::*,
+};
+use color_eyre::eyre::{bail, ensure};
+use std::{
+	env,
+	io::{self, prelude::*, BufReader},
+	os::unix::{io::OwnedFd, net::UnixStream, process::ExitStatusExt},
+	path::Path,
+	process::{self, Child, Command, ExitStatus, Stdio},
+	sync::atomic::{AtomicBool, Ordering::SeqCst},
+	thread,
+	time::{Duration, Instant},
+};
+
+const CHILD_ENV_VAR: &str = "INTERPROCESS_TEST_PREFORK_CHILD";
+const LISTENER_ENV_VAR: &str = "INTERPROCESS_TEST_PREFORK_LISTENER";
+const WORKERS: usize = 2;
+/// The exit code of a worker whose function failed, which is how it tells the supervisor to shut
+/// down in this test.
+const STOP_CODE: i32 = 1;
+
+/// Sends a command to whichever worker accepts the connection, returning the index and process ID
+/// of that worker.
+fn request(name: &Name<'_>, command: &str) -> TestResult<(usize, u32)> {
+	let conn = UnixStream::from(OwnedFd::from(
+		Stream::connect(name.borrow()).opname("connect")?,
+	));
+	// Connections wait in the backlog forever if no worker accepts them.
+	conn.set_read_timeout(Some(Duration::from_secs(10)))
+		.opname("timeout")?;
+	let mut conn = BufReader::new(conn);
+	conn.get_mut()
+		.write_all(format!("{command}\n").as_bytes())
+		.opname("send")?;
+	let mut buf = String::new();
+	conn.read_line(&mut buf).opname("receive")?;
+	let Some((index, pid)) = buf.trim_end().split_once(' ') else {
+		bail!("malformed reply {buf:?}");
+	};
+	Ok((index.parse()?, pid.parse()?))
+}
+
+/// Runs the supervisor in a child process, so that it forks a process with only one thread in it,
+/// and checks that workers are replaced, that the socket file outlives them and that workers which
+/// ignore `SIGTERM` are killed on shutdown. One of the workers uses a shutdown handle, which
+/// switches the socket they share to nonblocking mode.
+#[test]
+fn prefork() -> TestResult {
+	testinit();
+	// Filesystem names are preferred, since they're the ones that are reclaimed.
+	let path = NameTypeSupport::query().fs_supported();
+	let (name, mut listener) =
+		listen_and_pick_name(&mut namegen_local_socket(make_id!(), path), |nm| {
+			Listener::bind(nm.borrow())
+		})?;
+	let mut command = Command::new(env::current_exe().opname("current executable query")?);
+	command
+		.args([
+			"--exact",
+			"tests::prefork::prefork_child",
+			"--test-threads=1",
+		])
+		.env(CHILD_ENV_VAR, "1")
+		.stdout(Stdio::null());
+	handover::inherit_listener(&mut listener, &mut command, LISTENER_ENV_VAR)
+		.opname("inheritance")?;
+	let mut child = command.spawn().opname("child spawn")?;
+	drop((command, listener));
+
+	let rslt = check_replacement(&name).and_then(|()| ignore_sigterm(&name));
+	// Brings the supervisor down even if the check failed, so as not to leave it running.
+	let stopped = request(&name, "stop");
+	let status = wait(&mut child, Duration::from_secs(10));
+	if !matches!(status, Ok(Some(..))) {
+		let _ = child.kill();
+		let _ = child.wait();
+	}
+	rslt?;
+	stopped?;
+	let Some(status) = status? else {
+		bail!("supervisor didn't stop");
+	};
+	ensure!(status.success(), "child failed");
+	ensure!(
+		!name.is_path() || !Path::new(name.raw()).exists(),
+		"supervisor didn't reclaim the name"
+	);
+	Ok(())
+}
+
+fn check_replacement(name: &Name<'_>) -> TestResult {
+	let (index, pid) = request(name, "exit")?;
+	for _ in 0..200 {
+		let (new_index, new_pid) = request(name, "id")?;
+		ensure!(new_pid != pid, "exited worker accepted a connection");
+		if new_index == index {
+			return Ok(());
+		}
+		thread::sleep(Duration::from_millis(10));
+	}
+	bail!("exited worker wasn't replaced")
+}
+
+/// Makes every worker ignore `SIGTERM`.
+fn ignore_sigterm(name: &Name<'_>) -> TestResult {
+	let mut pids = Vec::new();
+	for _ in 0..200 {
+		let (_, pid) = request(name, "ignore")?;
+		if !pids.contains(&pid) {
+			pids.push(pid);
+		}
+		if pids.len() == WORKERS {
+			return Ok(());
+		}
+	}
+	bail!("not every worker accepted a connection")
+}
+
+fn wait(child: &mut Child, timeout: Duration) -> TestResult<Option<ExitStatus>> {
+	let start = Instant::now();
+	loop {
+		let status = child.try_wait().opname("child wait")?;
+		if status.is_some() || start.elapsed() >= timeout {
+			return Ok(status);
+		}
+		thread::sleep(Duration::from_millis(10));
+	}
+}
+
+/// Set when a worker is reported to have been killed with `SIGKILL`.
+static KILLED: AtomicBool = AtomicBool::new(false);
+
+/// Child side of the `prefork` test, which does nothing unless run by it.
+#[test]
+fn prefork_child() -> TestResult {
+	if env::var_os(CHILD_ENV_VAR).is_none() {
+		return Ok(());
+	}
+	let listener =
+		unsafe { handover::adopt_inherited_listener(LISTENER_ENV_VAR) }.opname("adoption")?;
+	// The workers are to be unaffected by the supervisor's listener being shut down.
+	listener
+		.shutdown_handle()
+		.opname("shutdown handle")?
+		.shutdown();
+	let supervisor = Supervisor::new(&listener).opname("supervisor creation")?;
+	let handle = supervisor.shutdown_handle();
+	let options = PreforkOptions::new()
+		.workers(WORKERS)
+		.respawn_delay(Duration::from_millis(10))
+		.grace_period(Duration::from_millis(100))
+		.on_worker_exit(move |exit| {
+			if exit.status.code() == Some(STOP_CODE) {
+				handle.shutdown();
+			}
+			if exit.status.signal() == Some(libc::SIGKILL) {
+				KILLED.store(true, SeqCst);
+			}
+		});
+	// SAFETY: the test harness doesn't run anything else with one test thread, and the main thread
+	// is blocked waiting for this one.
+	unsafe { supervisor.run(&options, worker) }.opname("supervisor")?;
+	drop(listener);
+	ensure!(KILLED.load(SeqCst), "worker ignoring SIGTERM wasn't killed");
+	Ok(())
+}
+
+fn worker(listener: &Listener, index: usize) -> io::Result<()> {
+	let pid = process::id();
+	// Puts the shared socket in nonblocking mode, which the other workers are to be unaffected by.
+	let _handle = (index == 0)
+		.then(|| listener.shutdown_handle())
+		.transpose()?;
+	for conn in listener.incoming() {
+		let mut conn = BufReader::new(conn?);
+		let mut command = String::new();
+		conn.read_line(&mut command)?;
+		conn.get_mut()
+			.write_all(format!("{index} {pid}\n").as_bytes())?;
+		match command.trim_end() {
+			"exit" => return Ok(()),
+			"ignore" => unsafe {
+				libc::signal(libc::SIGTERM, libc::SIG_IGN);
+			},
+			"stop" => return Err(io::Error::other("stop requested")),
+			_ => {}
+		}
+	}
+	Ok(())
+}
@@ -0,0 +1,152 @@
+#![cfg(unix)]
+
+use crate::{
+	local_socket::{prelude::*, Listener, Name, NameTypeSupport, Stream, ToFsName},
+	tests::util::*,
+};
+use color_eyre::eyre::ensure;
+use std::{
+	env, fs,
+	io::{self, prelude::*, BufReader},
+	iter,
+	path::Path,
+	process,
+	sync::Arc,
+	thread,
+};
+
+/// Binds to a filesystem name, returning `None` if the platform doesn't support those.
+fn bind(id: &'static str) -> TestResult<Option<(Arc<Name<'static>>, Listener)>> {
+	if !NameTypeSupport::query().fs_supported() {
+		return Ok(None);
+	}
+	listen_and_pick_name(&mut namegen_local_socket(id, true), |nm| {
+		Listener::bind(nm.borrow())
+	})
+	.map(Some)
+}
+
+fn exists(name: &Name<'_>) -> bool {
+	Path::new(name.raw()).exists()
+}
+
+fn roundtrip(name: &Name<'_>, listener: &Listener) -> TestResult {
+	let client = {
+		let name = name.borrow().into_owned();
+		thread::spawn(move || -> TestResult {
+			let mut conn = BufReader::new(Stream::connect(name).opname("connect")?);
+			let mut buf = String::new();
+			conn.read_line(&mut buf).opname("receive")?;
+			ensure_eq!(buf, "hello\n");
+			Ok(())
+		})
+	};
+	let mut conn = listener.accept().opname("accept")?;
+	conn.write_all(b"hello\n").opname("send")?;
+	client.join().unwrap_or_else(|_| Ok(()))
+}
+
+#[test]
+fn replace_live_listener() -> TestResult {
+	testinit();
+	let Some((name, old)) = bind(make_id!())? else {
+		return Ok(());
+	};
+	let new = Listener::bind_replacing(name.borrow()).opname("replacing bind")?;
+
+	roundtrip(&name, &new)?;
+	old.set_nonblocking(true).opname("nonblocking mode")?;
+	ensure!(
+		matches!(old.accept(), Err(e) if e.kind() == io::ErrorKind::WouldBlock),
+		"old listener received a connection after being replaced"
+	);
+	drop(old);
+	ensure!(
+		exists(&name),
+		"old listener reclaimed the name of the new one"
+	);
+	roundtrip(&name, &new)?;
+	drop(new);
+	ensure!(!exists(&name), "new listener didn't reclaim the name");
+	Ok(())
+}
+
+#[test]
+fn replace_without_listener() -> TestResult {
+	testinit();
+	let Some((name, old)) = bind(make_id!())? else {
+		return Ok(());
+	};
+	// No file is to be replaced this time.
+	drop(old);
+	let new = Listener::bind_replacing(name.borrow()).opname("replacing bind")?;
+	roundtrip(&name, &new)?;
+	drop(new);
+	ensure!(!exists(&name), "listener didn't reclaim the name");
+	Ok(())
+}
+
+#[test]
+fn reclaim_after_unlink() -> TestResult {
+	testinit();
+	let Some((name, old)) = bind(make_id!())? else {
+		return Ok(());
+	};
+	fs::remove_file(name.raw()).opname("unlink")?;
+	let new = Listener::bind(name.borrow()).opname("rebind")?;
+	drop(old);
+	ensure!(
+		exists(&name),
+		"old listener reclaimed the name of the new one"
+	);
+	drop(new);
+	ensure!(!exists(&name), "new listener didn't reclaim the name");
+	Ok(())
+}
+
+#[test]
+fn replace_regular_file() -> TestResult {
+	testinit();
+	let Some((name, listener)) = bind(make_id!())? else {
+		return Ok(());
+	};
+	drop(listener);
+	fs::write(name.raw(), "important").opname("file creation")?;
+	let rslt = Listener::bind_replacing(name.borrow());
+	let contents = fs::read_to_string(name.raw()).opname("file read");
+	fs::remove_file(name.raw()).opname("cleanup")?;
+	ensure!(
+		matches!(&rslt, Err(e) if e.kind() == io::ErrorKind::AddrInUse),
+		"replacing a regular file didn't fail as expected: {rslt:?}"
+	);
+	ensure_eq!(contents?, "important");
+	Ok(())
+}
+
+#[test]
+fn replace_long_path() -> TestResult {
+	testinit();
+	if !NameTypeSupport::query().fs_supported() {
+		return Ok(());
+	}
+	// Short enough to bind to on every platform, but too long to have a suffix appended.
+	const LEN: usize = 100;
+	let mut path = env::temp_dir()
+		.join(format!("interprocess-test-{}-", process::id()))
+		.into_os_string()
+		.into_string()
+		.unwrap_or_default();
+	if path.is_empty() || path.len() > LEN {
+		return Ok(());
+	}
+	path.extend(iter::repeat('x').take(LEN - path.len()));
+	let name = path.to_fs_name().opname("name")?;
+
+	let old = Listener::bind(name.borrow()).opname("bind")?;
+	let new = Listener::bind_replacing(name.borrow()).opname("replacing bind")?;
+	drop(old);
+	roundtrip(&name, &new)?;
+	drop(new);
+	ensure!(!exists(&name), "listener didn't reclaim the name");
+	Ok(())
+}
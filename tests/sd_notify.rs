@@ -0,0 +1,113 @@
+#![cfg(unix)]
+
+use crate::{
+	os::unix::sd_notify::{Notifier, State},
+	tests::util::*,
+	unnamed_pipe,
+};
+use color_eyre::eyre::ensure;
+use std::{
+	env, fs,
+	io::{self, prelude::*},
+	mem,
+	os::unix::{net::UnixDatagram, prelude::*},
+	process,
+};
+
+/// Receives a notification and the file descriptors sent with it.
+fn recv_with_fds(socket: &UnixDatagram) -> io::Result<(String, Vec<OwnedFd>)> {
+	let mut buf = [0_u8; 1024];
+	let mut cmsg_buf = [0_u64; 16];
+	let mut iov = libc::iovec {
+		iov_base: buf.as_mut_ptr().cast(),
+		iov_len: buf.len(),
+	};
+	let mut msg: libc::msghdr = unsafe { mem::zeroed() };
+	msg.msg_iov = &mut iov;
+	msg.msg_iovlen = 1;
+	msg.msg_control = cmsg_buf.as_mut_ptr().cast();
+	msg.msg_controllen = mem::size_of_val(&cmsg_buf) as _;
+	let len = unsafe { libc::recvmsg(socket.as_raw_fd(), &mut msg, 0) };
+	if len < 0 {
+		return Err(io::Error::last_os_error());
+	}
+	let mut fds = Vec::new();
+	let mut cmsg = unsafe { libc::CMSG_FIRSTHDR(&msg) };
+	while !cmsg.is_null() {
+		let (level, ty, cmsg_len) =
+			unsafe { ((*cmsg).cmsg_level, (*cmsg).cmsg_type, (*cmsg).cmsg_len) };
+		if level == libc::SOL_SOCKET && ty == libc::SCM_RIGHTS {
+			// The type of the length differs between platforms.
+			#[allow(clippy::unnecessary_cast)]
+			let data_len = cmsg_len as usize - unsafe { libc::CMSG_LEN(0) } as usize;
+			let data = unsafe { libc::CMSG_DATA(cmsg) }.cast::<RawFd>();
+			for i in 0..data_len / mem::size_of::<RawFd>() {
+				let fd = unsafe { data.add(i).read_unaligned() };
+				fds.push(unsafe { OwnedFd::from_raw_fd(fd) });
+			}
+		}
+		cmsg = unsafe { libc::CMSG_NXTHDR(&msg, cmsg) };
+	}
+	let text = String::from_utf8_lossy(&buf[..len as usize]).into_owned();
+	Ok((text, fds))
+}
+
+fn run(manager: &UnixDatagram, addr: &str) -> TestResult {
+	let notifier = Notifier::connect(addr).opname("connect")?;
+	notifier
+		.notify(&[State::Ready, State::Status("Accepting connections")])
+		.opname("notify")?;
+	let (text, fds) = recv_with_fds(manager).opname("receive")?;
+	ensure_eq!(text, "READY=1\nSTATUS=Accepting connections\n");
+	ensure!(
+		fds.is_empty(),
+		"file descriptors received without any being sent"
+	);
+
+	// Stores one end of a pipe and checks that it's the same pipe on the other side.
+	let (sender, recver) = unnamed_pipe::pipe().opname("pipe creation")?;
+	notifier
+		.notify_with_fds(&[State::FdStore, State::FdName("pipe")], &[sender.as_fd()])
+		.opname("notify with file descriptors")?;
+	drop(sender);
+	let (text, mut fds) = recv_with_fds(manager).opname("receive")?;
+	ensure_eq!(text, "FDSTORE=1\nFDNAME=pipe\n");
+	ensure_eq!(fds.len(), 1);
+	let mut stored = unnamed_pipe::Sender::from(fds.remove(0));
+	stored.write_all(b"stored\n").opname("stored pipe send")?;
+	drop(stored);
+	let mut buf = String::new();
+	io::BufReader::new(recver)
+		.read_to_string(&mut buf)
+		.opname("pipe receive")?;
+	ensure_eq!(buf, "stored\n");
+
+	let err = notifier.notify(&[State::Status("two\nlines")]);
+	ensure!(
+		matches!(&err, Err(e) if e.kind() == io::ErrorKind::InvalidInput),
+		"notification with a newline was sent: {err:?}"
+	);
+	Ok(())
+}
+
+#[test]
+fn sd_notify_path() -> TestResult {
+	testinit();
+	let path = env::temp_dir().join(format!("interprocess-test-notify-{}", process::id()));
+	let _ = fs::remove_file(&path);
+	let manager = UnixDatagram::bind(&path).opname("bind")?;
+	let rslt = run(&manager, path.to_str().unwrap_or_default());
+	let _ = fs::remove_file(&path);
+	rslt
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+#[test]
+fn sd_notify_abstract() -> TestResult {
+	use std::os::{linux::net::SocketAddrExt, unix::net::SocketAddr};
+	testinit();
+	let name = format!("interprocess-test-notify-{}", process::id());
+	let addr = SocketAddr::from_abstract_name(&name).opname("address")?;
+	let manager = UnixDatagram::bind_addr(&addr).opname("bind")?;
+	run(&manager, &format!("@{name}"))
+}
@@ -0,0 +1,145 @@
+use crate::{
+	local_socket::{
+		prelude::*,
+		single_instance::{Primary, SingleInstance},
+		Name, NameTypeSupport, Stream,
+	},
+	tests::util::*,
+};
+use color_eyre::eyre::{bail, ensure};
+use std::{ffi::OsString, io, sync::Arc, thread, time::Duration};
+
+fn acquire(name: &Name<'_>) -> TestResult<SingleInstance> {
+	SingleInstance::acquire(name.borrow()).opname("acquisition")
+}
+
+/// Picks a name that isn't taken, by becoming the primary instance.
+fn primary(id: &'static str, path: bool) -> TestResult<(Arc<Name<'static>>, Primary)> {
+	listen_and_pick_name(
+		&mut namegen_local_socket(id, path),
+		|nm| match SingleInstance::acquire(nm.borrow())? {
+			SingleInstance::Primary(primary) => Ok(primary),
+			SingleInstance::Secondary(..) => Err(std::io::ErrorKind::AddrInUse.into()),
+		},
+	)
+}
+
+fn forward(id: &'static str, path: bool) -> TestResult {
+	let (name, primary) = primary(id, path)?;
+	let args = ["--open", "file with spaces.txt", ""];
+	let secondary = {
+		let name = Arc::clone(&name);
+		thread::spawn(move || -> TestResult {
+			let SingleInstance::Secondary(secondary) = acquire(&name)? else {
+				bail!("second instance became primary");
+			};
+			secondary.send_args(args).opname("send")?;
+			let SingleInstance::Secondary(secondary) = acquire(&name)? else {
+				bail!("third instance became primary");
+			};
+			secondary.send(b"raw payload").opname("send")?;
+			Ok(())
+		})
+	};
+	let msg = primary.recv().opname("receive")?;
+	ensure_eq!(msg.args()?, args.map(OsString::from));
+	let msg = primary.recv().opname("receive")?;
+	ensure_eq!(msg.payload(), b"raw payload");
+	secondary.join().unwrap_or_else(|_| Ok(()))?;
+
+	// The name is free again once the primary instance goes away.
+	drop(primary);
+	let SingleInstance::Primary(_) = acquire(&name)? else {
+		bail!("no instance became primary after the first one exited");
+	};
+	Ok(())
+}
+
+/// A client which connects and never sends anything doesn't hold up the primary instance.
+fn stall(id: &'static str, path: bool) -> TestResult {
+	let (name, mut primary) = primary(id, path)?;
+	primary.set_recv_timeout(Duration::from_millis(100));
+	let stalled = Stream::connect(name.borrow()).opname("connect")?;
+	let err = primary.recv().err().map(|e| e.kind());
+	ensure_eq!(err, Some(io::ErrorKind::TimedOut));
+	drop(stalled);
+
+	let secondary = {
+		let name = Arc::clone(&name);
+		thread::spawn(move || -> TestResult {
+			let SingleInstance::Secondary(secondary) = acquire(&name)? else {
+				bail!("second instance became primary");
+			};
+			secondary.send(b"payload").opname("send")
+		})
+	};
+	let msg = primary.recv().opname("receive")?;
+	ensure_eq!(msg.payload(), b"payload");
+	secondary.join().unwrap_or_else(|_| Ok(()))
+}
+
+#[test]
+fn single_instance_file() -> TestResult {
+	testinit();
+	if !NameTypeSupport::query().fs_supported() {
+		return Ok(());
+	}
+	forward(make_id!(), true)?;
+	stall(make_id!(), true)
+}
+
+#[test]
+fn single_instance_namespaced() -> TestResult {
+	testinit();
+	if !NameTypeSupport::query().ns_supported() {
+		return Ok(());
+	}
+	forward(make_id!(), false)?;
+	stall(make_id!(), false)
+}
+
+/// A socket file left behind by a crashed primary instance is replaced.
+#[cfg(unix)]
+#[test]
+fn single_instance_stale() -> TestResult {
+	use crate::local_socket::traits::Listener as _;
+	testinit();
+	if !NameTypeSupport::query().fs_supported() {
+		return Ok(());
+	}
+	let (name, primary) = primary(make_id!(), true)?;
+	let mut listener = primary.into_listener();
+	listener.do_not_reclaim_name_on_drop();
+	drop(listener);
+	ensure!(
+		std::path::Path::new(name.raw()).exists(),
+		"socket file was deleted"
+	);
+	let SingleInstance::Primary(_) = acquire(&name)? else {
+		bail!("no instance became primary over a stale socket file");
+	};
+	Ok(())
+}
+
+/// A file which isn't a socket is left alone.
+#[cfg(unix)]
+#[test]
+fn single_instance_regular_file() -> TestResult {
+	use std::{fs, io};
+	testinit();
+	if !NameTypeSupport::query().fs_supported() {
+		return Ok(());
+	}
+	let (name, primary) = primary(make_id!(), true)?;
+	drop(primary);
+	fs::write(name.raw(), "important").opname("file creation")?;
+	let rslt = SingleInstance::acquire(name.borrow());
+	let contents = fs::read_to_string(name.raw()).opname("file read");
+	let _ = fs::remove_file(name.raw());
+	ensure!(
+		matches!(&rslt, Err(e) if e.kind() == io::ErrorKind::AddrInUse),
+		"acquiring over a regular file didn't fail as expected: {rslt:?}"
+	);
+	ensure_eq!(contents?, "important");
+	Ok(())
+}
@@ -0,0 +1,82 @@
+#![cfg(unix)]
+
+use crate::{
+	local_socket::{prelude::*, Listener, NameTypeSupport, Stream},
+	os::unix::{socket_activation, Inheritable},
+	tests::util::*,
+};
+use color_eyre::eyre::{bail, ensure};
+use std::{
+	env,
+	io::{prelude::*, BufReader},
+	process::{Command, Stdio},
+};
+
+const CHILD_ENV_VAR: &str = "INTERPROCESS_TEST_SOCKET_ACTIVATION_CHILD";
+const NAME_ENV_VAR: &str = "INTERPROCESS_TEST_SOCKET_ACTIVATION_NAME";
+
+/// Passes a listener to a child process the way systemd does, with a shell setting `LISTEN_PID`
+/// to its own process ID before replacing itself with the test executable.
+#[test]
+fn socket_activation() -> TestResult {
+	testinit();
+	let path = !NameTypeSupport::query().ns_supported();
+	let (name, listener) =
+		listen_and_pick_name(&mut namegen_local_socket(make_id!(), path), |nm| {
+			Listener::bind(nm.borrow())
+		})?;
+
+	let mut command = Command::new("/bin/sh");
+	command
+		.arg("-c")
+		.arg(r#"LISTEN_PID=$$ exec "$0" "$@""#)
+		.arg(env::current_exe().opname("current executable query")?)
+		.args([
+			"--exact",
+			"tests::socket_activation::socket_activation_child",
+		])
+		.env(CHILD_ENV_VAR, "1")
+		.env(NAME_ENV_VAR, name.raw())
+		.env("LISTEN_FDS", "1")
+		.env("LISTEN_FDNAMES", "test")
+		.stdout(Stdio::null());
+	listener
+		.inherit_at(&mut command, 3)
+		.opname("listener inheritance")?;
+	let mut child = command.spawn().opname("child spawn")?;
+	drop(command);
+
+	let mut conn = BufReader::new(Stream::connect(name.borrow()).opname("connect")?);
+	let mut buf = String::new();
+	conn.read_line(&mut buf).opname("receive")?;
+	ensure_eq!(buf, "activated\n");
+
+	ensure!(child.wait().opname("child wait")?.success(), "child failed");
+	drop(listener);
+	Ok(())
+}
+
+/// Child side of the `socket_activation` test, which does nothing unless run by it.
+#[test]
+fn socket_activation_child() -> TestResult {
+	if env::var_os(CHILD_ENV_VAR).is_none() {
+		return Ok(());
+	}
+	let mut activated = unsafe { socket_activation::listeners() }.opname("adoption")?;
+	ensure_eq!(activated.len(), 1);
+	let Some(activated) = activated.pop() else {
+		bail!("no listeners passed");
+	};
+	ensure_eq!(activated.fd_name(), Some("test"));
+	let expected_name = env::var_os(NAME_ENV_VAR).unwrap_or_default();
+	ensure!(
+		activated.name().map(|nm| nm.raw() == expected_name) == Some(true),
+		"wrong name recovered: {:?}",
+		activated.name()
+	);
+
+	let listener = activated.into_listener();
+	let mut conn = listener.accept().opname("accept")?;
+	conn.write_all(b"activated\n").opname("send")?;
+	Ok(())
+}
@@ -0,0 +1,74 @@
+#![cfg(all(unix, feature = "tokio"))]
+
+use crate::{
+	os::unix::fifo_file::{
+		tokio::{Recver, Sender},
+		Fifo,
+	},
+	tests::util::{testinit, TestResult, WrapErrExt},
+};
+use color_eyre::eyre::ensure;
+use std::{io, time::Duration};
+use tokio::{
+	io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+	time::timeout,
+};
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+#[tokio::test]
+async fn persistent_recver() -> TestResult {
+	testinit();
+	let fifo = Fifo::create_in_private_dir("fifo", 0o600).opname("FIFO creation")?;
+	let recver = Recver::open_persistent(fifo.path()).opname("receiver open")?;
+	ensure!(recver.is_persistent(), "receiver not persistent");
+	let mut recver = BufReader::new(recver);
+
+	// Each sender disconnects before the next one connects, which would produce EOF for a
+	// non-persistent receiver.
+	for nr in 0..3 {
+		let mut sender = Sender::open(fifo.path()).await.opname("sender open")?;
+		let msg = format!("Message {nr}\n");
+		sender.write_all(msg.as_bytes()).await.opname("send")?;
+		drop(sender);
+
+		let mut buf = String::new();
+		timeout(TIMEOUT, recver.read_line(&mut buf))
+			.await
+			.opname("receive timeout")?
+			.opname("receive")?;
+		ensure_eq!(buf, msg);
+	}
+	Ok(())
+}
+
+#[tokio::test]
+async fn sender_waits_for_recver() -> TestResult {
+	testinit();
+	let fifo = Fifo::create_in_private_dir("fifo", 0o600).opname("FIFO creation")?;
+
+	let err = Sender::open_nonblocking(fifo.path()).unwrap_err();
+	ensure_eq!(err.kind(), io::ErrorKind::WouldBlock);
+
+	let path = fifo.path().to_owned();
+	let sender = tokio::spawn(async move {
+		let mut sender = Sender::open(path).await?;
+		sender.write_all(b"Hello\n").await?;
+		io::Result::Ok(())
+	});
+	tokio::time::sleep(Duration::from_millis(20)).await;
+	let mut recver = BufReader::new(Recver::open(fifo.path()).opname("receiver open")?);
+	timeout(TIMEOUT, sender)
+		.await
+		.opname("sender timeout")?
+		.opname("sender task")?
+		.opname("sender")?;
+
+	let mut buf = String::new();
+	recver.read_line(&mut buf).await.opname("receive")?;
+	ensure_eq!(buf, "Hello\n");
+	// The only sender is gone, so a non-persistent receiver sees EOF.
+	buf.clear();
+	ensure_eq!(recver.read_line(&mut buf).await.opname("receive")?, 0);
+	Ok(())
+}
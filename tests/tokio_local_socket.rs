@@ -1,8 +1,20 @@
 // TODO test various error conditions
 #![cfg(feature = "tokio")]
 
+#[cfg(feature = "codec")]
+mod codec;
+mod framing;
+#[cfg(feature = "jsonrpc")]
+mod jsonrpc;
+mod mux;
 mod no_server;
+mod pair;
+mod pubsub;
+mod rpc;
+mod server;
 mod stream;
+#[cfg(feature = "serde")]
+mod typed;
 
 use crate::{
 	local_socket::{tokio::Stream, Name, NameTypeSupport},
@@ -74,3 +86,84 @@ async fn no_server_namespaced() -> TestResult {
 	}
 	Ok(())
 }
+
+#[tokio::test]
+async fn stream_pair() -> TestResult {
+	testinit();
+	pair::run().await
+}
+
+#[tokio::test]
+async fn framing() -> TestResult {
+	testinit();
+	framing::run().await
+}
+
+#[cfg(feature = "codec")]
+#[tokio::test]
+async fn codec_frames() -> TestResult {
+	testinit();
+	codec::run_frames().await?;
+	codec::run_frames_compat().await
+}
+
+#[cfg(feature = "codec")]
+#[tokio::test]
+async fn codec_lines() -> TestResult {
+	testinit();
+	codec::run_lines().await
+}
+
+#[cfg(feature = "serde")]
+#[tokio::test]
+async fn typed() -> TestResult {
+	testinit();
+	typed::run(crate::local_socket::tokio::typed::Bincode).await?;
+	#[cfg(feature = "json")]
+	typed::run(crate::local_socket::tokio::typed::Json).await?;
+	#[cfg(feature = "postcard")]
+	typed::run(crate::local_socket::tokio::typed::Postcard).await?;
+	Ok(())
+}
+
+#[cfg(feature = "jsonrpc")]
+#[tokio::test]
+async fn jsonrpc() -> TestResult {
+	testinit();
+	jsonrpc::run().await?;
+	jsonrpc::run_errors().await
+}
+
+#[tokio::test]
+async fn rpc() -> TestResult {
+	testinit();
+	rpc::run().await?;
+	rpc::run_closed().await
+}
+
+#[tokio::test]
+async fn mux() -> TestResult {
+	testinit();
+	mux::run().await?;
+	mux::run_flow_control().await?;
+	mux::run_reset_and_close().await
+}
+
+#[tokio::test]
+async fn pubsub() -> TestResult {
+	testinit();
+	let path = NameTypeSupport::query().fs_supported();
+	pubsub::run(make_id!(), path).await?;
+	pubsub::run_drop_oldest(make_id!(), path).await?;
+	pubsub::run_subscribe_while_publishing(make_id!(), path).await?;
+	pubsub::run_disconnect(make_id!(), path).await?;
+	pubsub::run_block(make_id!(), path).await
+}
+
+#[tokio::test]
+async fn server() -> TestResult {
+	testinit();
+	let path = NameTypeSupport::query().fs_supported();
+	server::run(make_id!(), path).await?;
+	server::run_greeting(make_id!(), path).await
+}
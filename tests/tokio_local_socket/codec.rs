@@ -0,0 +1,105 @@
+use crate::{
+	local_socket::{
+		framing::{Framed as SyncFramed, FramingOptions, LengthPrefix},
+		tokio::{
+			codec::{FrameCodec, LineCodec},
+			Stream,
+		},
+	},
+	tests::util::{TestResult, WrapErrExt},
+};
+use futures::{SinkExt, StreamExt};
+use std::io;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio_util::codec::{Framed, FramedRead, FramedWrite};
+
+pub async fn run_frames() -> TestResult {
+	let opts = FramingOptions::new().length_prefix(LengthPrefix::Varint);
+	let (s1, s2) = Stream::pair().await.opname("pair creation")?;
+	let (recver, sender) = s2.split();
+	let mut recver = FramedRead::new(recver, FrameCodec::with_options(opts));
+	let mut sender = FramedWrite::new(sender, FrameCodec::with_options(opts));
+	let mut s1 = Framed::new(s1, FrameCodec::with_options(opts));
+
+	let big = vec![7; 20000];
+	s1.send(&b"Hello"[..]).await.opname("send")?;
+	s1.send(&big[..]).await.opname("send")?;
+	for expected in [&b"Hello"[..], &big[..]] {
+		let frame = recver.next().await.transpose().opname("receive")?;
+		ensure_eq!(frame.as_deref(), Some(expected));
+		sender.send(expected).await.opname("send")?;
+	}
+	for expected in [&b"Hello"[..], &big[..]] {
+		let frame = s1.next().await.transpose().opname("receive")?;
+		ensure_eq!(frame.as_deref(), Some(expected));
+	}
+	drop(s1);
+	ensure_eq!(recver.next().await.transpose().opname("receive")?, None);
+	Ok(())
+}
+
+/// Checks that the codec and the sync framing module use the same wire format.
+pub async fn run_frames_compat() -> TestResult {
+	let (mut s1, s2) = Stream::pair().await.opname("pair creation")?;
+	let mut s2 = Framed::new(s2, FrameCodec::new());
+
+	let mut sync_sender = SyncFramed::new(Vec::new());
+	sync_sender.send_frame(b"sync").opname("sync encode")?;
+	s1.write_all(&sync_sender.into_inner())
+		.await
+		.opname("send")?;
+	let frame = s2.next().await.transpose().opname("receive")?;
+	ensure_eq!(frame.as_deref(), Some(&b"sync"[..]));
+
+	s2.send(&b"async"[..]).await.opname("send")?;
+	let mut raw = [0; 9];
+	s1.read_exact(&mut raw).await.opname("receive")?;
+	let mut sync_recver = SyncFramed::new(&raw[..]);
+	let frame = sync_recver.recv_frame().opname("sync decode")?;
+	ensure_eq!(frame, Some(&b"async"[..]));
+	Ok(())
+}
+
+pub async fn run_lines() -> TestResult {
+	let (s1, s2) = Stream::pair().await.opname("pair creation")?;
+	let mut s1 = Framed::new(s1, LineCodec::with_max_length(8));
+	let (recver, mut sender) = s2.split();
+	let mut recver = FramedRead::new(recver, LineCodec::with_max_length(8));
+
+	let err = s1.send("newline\n").await.err().map(|e| e.kind());
+	ensure_eq!(err, Some(io::ErrorKind::InvalidInput));
+	let err = s1.send("too long!").await.err().map(|e| e.kind());
+	ensure_eq!(err, Some(io::ErrorKind::InvalidInput));
+
+	s1.send("first").await.opname("send")?;
+	s1.send("").await.opname("send")?;
+	s1.send(&b"\xFFbinary"[..]).await.opname("send")?;
+	for expected in [&b"first"[..], b"", b"\xFFbinary"] {
+		let line = recver.next().await.transpose().opname("receive")?;
+		ensure_eq!(line.as_deref(), Some(expected));
+	}
+
+	sender
+		.write_all(b"ok\nway too long\n")
+		.await
+		.opname("send")?;
+	sender.write_all(b"last").await.opname("send")?;
+	drop(sender);
+	let line = s1.next().await.transpose().opname("receive")?;
+	ensure_eq!(line.as_deref(), Some(&b"ok"[..]));
+	let err = s1.next().await.transpose().err().map(|e| e.kind());
+	ensure_eq!(err, Some(io::ErrorKind::InvalidData));
+
+	let (s1, mut s2) = Stream::pair().await.opname("pair creation")?;
+	s2.write_all(b"one\nlast").await.opname("send")?;
+	drop(s2);
+	let lines = FramedRead::new(s1, LineCodec::new())
+		.map(|line| line.map(|l| l.to_vec()))
+		.collect::<Vec<_>>()
+		.await
+		.into_iter()
+		.collect::<io::Result<Vec<_>>>()
+		.opname("receive")?;
+	ensure_eq!(lines, [b"one".to_vec(), b"last".to_vec()]);
+	Ok(())
+}
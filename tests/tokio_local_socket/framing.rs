@@ -0,0 +1,48 @@
+use crate::{
+	local_socket::tokio::{framing::Framed, Stream},
+	tests::{
+		local_socket::framing::{frames, options},
+		util::{TestResult, WrapErrExt},
+	},
+};
+use tokio::try_join;
+
+pub async fn run() -> TestResult {
+	for opts in options() {
+		let (s1, s2) = Stream::pair().await.opname("pair creation")?;
+
+		let other_side = async move {
+			let (recver, sender) = s2.split();
+			let mut recver = Framed::with_options(recver, opts);
+			let mut sender = Framed::with_options(sender, opts);
+			while let Some(frame) = recver.recv_frame().await.opname("receive")? {
+				sender.send_frame(frame).await.opname("send")?;
+			}
+			TestResult::Ok(())
+		};
+		let this_side = async move {
+			let (recver, sender) = s1.split();
+			let mut recver = Framed::with_options(recver, opts);
+			let mut sender = Framed::with_options(sender, opts);
+			let frames = frames();
+			let send = async {
+				for frame in &frames {
+					sender.send_frame(frame).await.opname("send")?;
+				}
+				drop(sender);
+				TestResult::Ok(())
+			};
+			let recv = async {
+				for frame in &frames {
+					let echoed = recver.recv_frame().await.opname("receive")?;
+					ensure_eq!(echoed, Some(&frame[..]));
+				}
+				ensure_eq!(recver.recv_frame().await.opname("receive")?, None);
+				TestResult::Ok(())
+			};
+			try_join!(send, recv).map(|_| ())
+		};
+		try_join!(other_side, this_side)?;
+	}
+	Ok(())
+}
@@ -0,0 +1,170 @@
+use crate::{
+	local_socket::tokio::{
+		jsonrpc::{serve, Client, ErrorObject, LspFramed, Request, Response},
+		Stream,
+	},
+	tests::util::{TestResult, WrapErrExt},
+};
+use color_eyre::eyre::{bail, ensure};
+use serde_json::{json, Value};
+use std::{future::Future, pin::Pin, time::Duration};
+use tokio::{sync::mpsc, time::timeout};
+
+type HandlerFuture = Pin<Box<dyn Future<Output = Result<Value, ErrorObject>> + Send>>;
+
+/// Sends a message through the channel when dropped, which happens when a handler is aborted.
+struct DropSignal(mpsc::UnboundedSender<&'static str>);
+impl Drop for DropSignal {
+	fn drop(&mut self) {
+		let _ = self.0.send("dropped");
+	}
+}
+
+/// Handler which adds numbers, records notifications and never completes `hang` requests.
+fn handler(events: mpsc::UnboundedSender<&'static str>) -> impl Fn(Request) -> HandlerFuture {
+	move |request| {
+		let events = events.clone();
+		Box::pin(async move {
+			match request.method.as_str() {
+				"add" => {
+					let Some(Value::Array(params)) = request.params else {
+						return Err(ErrorObject::invalid_params("expected an array"));
+					};
+					Ok(json!(params.iter().filter_map(Value::as_i64).sum::<i64>()))
+				}
+				"note" => {
+					let _ = events.send("note");
+					Ok(Value::Null)
+				}
+				"hang" => {
+					let _signal = DropSignal(events);
+					std::future::pending().await
+				}
+				"panic" => panic!("requested panic"),
+				method => Err(ErrorObject::method_not_found(method)),
+			}
+		})
+	}
+}
+
+pub async fn run() -> TestResult {
+	let (s1, s2) = Stream::pair().await.opname("pair creation")?;
+	let (events_tx, mut events) = mpsc::unbounded_channel();
+	let server = tokio::spawn(serve(s2, handler(events_tx)));
+	let client = Client::new(s1);
+
+	let sum = client
+		.request("add", Some(json!([1, 2, 3])))
+		.await
+		.opname("request")?;
+	ensure_eq!(sum, Ok(json!(6)));
+	let err = client.request("sub", None).await.opname("request")?;
+	ensure_eq!(err.map_err(|e| e.code), Err(ErrorObject::METHOD_NOT_FOUND));
+	let err = client.request("panic", None).await.opname("request")?;
+	ensure_eq!(err.map_err(|e| e.code), Err(ErrorObject::INTERNAL_ERROR));
+
+	client.notify("note", None).opname("notify")?;
+	ensure_eq!(events.recv().await, Some("note"));
+
+	// Concurrent requests are matched with their responses.
+	let (r1, r2) = tokio::join!(
+		client.request("add", Some(json!([1]))),
+		client.request("add", Some(json!([2]))),
+	);
+	ensure_eq!((r1?, r2?), (Ok(json!(1)), Ok(json!(2))));
+
+	let results = client
+		.batch()
+		.request("add", Some(json!([40, 2])))
+		.notify("note", None)
+		.request("add", Some(json!({})))
+		.send()
+		.await
+		.opname("batch")?;
+	ensure_eq!(results.len(), 2);
+	ensure_eq!(results[0], Ok(json!(42)));
+	ensure_eq!(
+		results[1].as_ref().map_err(|e| e.code),
+		Err(ErrorObject::INVALID_PARAMS)
+	);
+	ensure_eq!(events.recv().await, Some("note"));
+	ensure_eq!(client.batch().send().await.opname("empty batch")?, vec![]);
+
+	// Dropping the future of a request cancels it on the server.
+	let hang = client.request("hang", None);
+	ensure!(
+		timeout(Duration::from_millis(50), hang).await.is_err(),
+		"hanging request completed"
+	);
+	ensure_eq!(
+		timeout(Duration::from_secs(10), events.recv())
+			.await
+			.ok()
+			.flatten(),
+		Some("dropped")
+	);
+
+	drop(client);
+	let Ok(rslt) = server.await else {
+		bail!("server task panicked");
+	};
+	rslt.opname("serve")?;
+	Ok(())
+}
+
+pub async fn run_errors() -> TestResult {
+	let (s1, s2) = Stream::pair().await.opname("pair creation")?;
+	let (events_tx, _events) = mpsc::unbounded_channel();
+	let server = tokio::spawn(serve(s2, handler(events_tx)));
+	let mut raw = LspFramed::new(s1);
+
+	let response: Response = serde_json::from_value(roundtrip(&mut raw, b"{").await?)?;
+	ensure_eq!(response.id, None);
+	ensure_eq!(
+		response.result.map_err(|e| e.code),
+		Err(ErrorObject::PARSE_ERROR)
+	);
+
+	let response: Response = serde_json::from_value(roundtrip(&mut raw, b"[]").await?)?;
+	ensure_eq!(
+		response.result.map_err(|e| e.code),
+		Err(ErrorObject::INVALID_REQUEST)
+	);
+
+	// Invalid members of a batch get their own error responses, with the ID if it is known.
+	let batch = roundtrip(
+		&mut raw,
+		br#"[{"jsonrpc":"2.0","id":1,"method":"add","params":[1]},{"jsonrpc":"2.0","id":2,"method":5},7]"#,
+	)
+	.await?;
+	let Value::Array(responses) = batch else {
+		bail!("batch response is not an array");
+	};
+	let responses = responses
+		.into_iter()
+		.map(serde_json::from_value)
+		.collect::<Result<Vec<Response>, _>>()?;
+	ensure_eq!(responses.len(), 3);
+	ensure_eq!(responses[0], Response::success(Some(1.into()), json!(1)));
+	ensure_eq!(responses[1].id, Some(2.into()));
+	ensure_eq!(
+		responses[1].result.as_ref().map_err(|e| e.code),
+		Err(ErrorObject::INVALID_REQUEST)
+	);
+	ensure_eq!(responses[2].id, None);
+
+	drop(raw);
+	let Ok(rslt) = server.await else {
+		bail!("server task panicked");
+	};
+	rslt.opname("serve")?;
+	Ok(())
+}
+
+async fn roundtrip(raw: &mut LspFramed<Stream>, msg: &[u8]) -> TestResult<Value> {
+	raw.send_message(msg).await.opname("send")?;
+	let Some(response) = raw.recv_message().await.opname("receive")? else {
+		bail!("server closed the connection");
+	};
+	Ok(serde_json::from_slice(response)?)
+}
@@ -0,0 +1,148 @@
+use crate::{
+	local_socket::tokio::{
+		mux::{Mode, Session, SessionOptions, Substream},
+		Stream,
+	},
+	tests::util::{TestResult, WrapErrExt},
+};
+use color_eyre::eyre::{bail, ensure};
+use std::{io, time::Duration};
+use tokio::{
+	io::{AsyncReadExt, AsyncWriteExt},
+	task::JoinHandle,
+	time::{sleep, timeout},
+	try_join,
+};
+
+async fn sessions() -> TestResult<(Session, Session)> {
+	let (a, b) = Stream::pair().await.opname("pair creation")?;
+	Ok((Session::new(a, Mode::Client), Session::new(b, Mode::Server)))
+}
+
+/// Reads a substream to its end and writes the data back in reverse.
+fn spawn_reverser(mut sub: Substream) -> JoinHandle<TestResult> {
+	tokio::spawn(async move {
+		let mut buf = Vec::new();
+		sub.read_to_end(&mut buf).await.opname("receive")?;
+		buf.reverse();
+		sub.write_all(&buf).await.opname("send")?;
+		sub.shutdown().await.opname("shutdown")?;
+		Ok(())
+	})
+}
+
+async fn reverse(session: &Session, msg: &[u8]) -> TestResult<Vec<u8>> {
+	let mut sub = session.open().opname("open")?;
+	sub.write_all(msg).await.opname("send")?;
+	// Half-close: the other end sees end-of-file but can still respond.
+	sub.shutdown().await.opname("shutdown")?;
+	let mut buf = Vec::new();
+	sub.read_to_end(&mut buf).await.opname("receive")?;
+	Ok(buf)
+}
+
+pub async fn run() -> TestResult {
+	let (client, server) = sessions().await?;
+	// Both ends open and accept substreams.
+	for (opener, acceptor) in [(&client, &server), (&server, &client)] {
+		let acceptor = acceptor.clone();
+		let accept_loop = tokio::spawn(async move {
+			let mut reversers = Vec::new();
+			for _ in 0..3 {
+				reversers.push(spawn_reverser(acceptor.accept().await?));
+			}
+			TestResult::Ok(reversers)
+		});
+		let (r1, r2, r3) = tokio::join!(
+			reverse(opener, b"first"),
+			reverse(opener, b""),
+			reverse(opener, &[7; 100_000]),
+		);
+		ensure_eq!(r1?, b"tsrif");
+		ensure_eq!(r2?, b"");
+		ensure_eq!(r3?, [7; 100_000]);
+		let Ok(reversers) = accept_loop.await else {
+			bail!("accept task panicked");
+		};
+		for reverser in reversers? {
+			let Ok(rslt) = reverser.await else {
+				bail!("reverser panicked");
+			};
+			rslt?;
+		}
+	}
+	Ok(())
+}
+
+pub async fn run_flow_control() -> TestResult {
+	let (a, b) = Stream::pair().await.opname("pair creation")?;
+	let opts = SessionOptions::new().window_size(512 * 1024);
+	let client = Session::with_options(a, Mode::Client, opts);
+	let server = Session::with_options(b, Mode::Server, opts);
+
+	let mut slow = client.open().opname("open")?;
+	let mut slow_peer = server.accept().await.opname("accept")?;
+	let big = vec![1; 2 * 1024 * 1024];
+	let write = tokio::spawn(async move {
+		slow.write_all(&big).await.opname("send")?;
+		slow.shutdown().await.opname("shutdown")?;
+		TestResult::Ok(slow)
+	});
+	sleep(Duration::from_millis(50)).await;
+	ensure!(!write.is_finished(), "write exceeding the window completed");
+
+	// The blocked substream doesn't hold up the others.
+	let accept_fast = server.accept();
+	let (fast, reversed) = tokio::join!(
+		async { TestResult::Ok(spawn_reverser(accept_fast.await.opname("accept")?)) },
+		timeout(Duration::from_secs(10), reverse(&client, b"fast")),
+	);
+	let Ok(reversed) = reversed else {
+		bail!("substream was blocked by another one");
+	};
+	ensure_eq!(reversed?, b"tsaf");
+	let Ok(rslt) = fast?.await else {
+		bail!("reverser panicked");
+	};
+	rslt?;
+
+	let mut buf = Vec::new();
+	slow_peer.read_to_end(&mut buf).await.opname("receive")?;
+	ensure_eq!(buf.len(), 2 * 1024 * 1024);
+	let Ok(rslt) = write.await else {
+		bail!("writer panicked");
+	};
+	rslt?;
+	Ok(())
+}
+
+pub async fn run_reset_and_close() -> TestResult {
+	let (client, server) = sessions().await?;
+	let mut sub = client.open().opname("open")?;
+	let (accepted, ()) =
+		try_join!(server.accept(), async { sub.write_all(b"unread").await }).opname("accept")?;
+	drop(accepted);
+	let err = timeout(Duration::from_secs(10), async {
+		loop {
+			if let Err(e) = sub.write_all(b"more").await {
+				break e.kind();
+			}
+			sleep(Duration::from_millis(5)).await;
+		}
+	})
+	.await;
+	ensure_eq!(err.ok(), Some(io::ErrorKind::BrokenPipe));
+	// The dropped end has shut its side down before resetting.
+	ensure_eq!(sub.read(&mut [0; 8]).await.opname("receive")?, 0);
+
+	// Closing the session aborts substreams that haven't been shut down.
+	let mut sub = client.open().opname("open")?;
+	drop(server);
+	let err = sub.read(&mut [0; 8]).await.err().map(|e| e.kind());
+	ensure_eq!(err, Some(io::ErrorKind::ConnectionAborted));
+	let err = client.accept().await.err().map(|e| e.kind());
+	ensure_eq!(err, Some(io::ErrorKind::ConnectionAborted));
+	let err = client.open().err().map(|e| e.kind());
+	ensure_eq!(err, Some(io::ErrorKind::ConnectionAborted));
+	Ok(())
+}
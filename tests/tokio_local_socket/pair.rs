@@ -0,0 +1,36 @@
+use crate::{
+	local_socket::tokio::Stream,
+	tests::util::{TestResult, WrapErrExt},
+};
+use tokio::{
+	io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader},
+	try_join,
+};
+
+pub async fn run() -> TestResult {
+	let (s1, s2) = Stream::pair().await.opname("pair creation")?;
+
+	let other_side = async move {
+		let (recver, mut sender) = s2.split();
+		let mut recver = BufReader::new(recver);
+		let mut buf = String::new();
+		recver.read_line(&mut buf).await.opname("receive")?;
+		ensure_eq!(buf, "ping\n");
+		sender.write_all(b"pong\n").await.opname("send")?;
+		Stream::reunite(recver.into_inner(), sender).opname("reunite")
+	};
+	let this_side = async move {
+		let mut s1 = BufReader::new(s1);
+		s1.get_mut().write_all(b"ping\n").await.opname("send")?;
+		let mut buf = String::new();
+		s1.read_line(&mut buf).await.opname("receive")?;
+		ensure_eq!(buf, "pong\n");
+		TestResult::Ok(())
+	};
+	let (mut s2, ()) = try_join!(other_side, this_side)?;
+
+	let mut rest = Vec::new();
+	s2.read_to_end(&mut rest).await.opname("receive")?;
+	ensure_eq!(rest, b"");
+	Ok(())
+}
@@ -0,0 +1,211 @@
+use crate::{
+	local_socket::{
+		tokio::{
+			pubsub::{Broker, BrokerOptions, Client, Message, SlowSubscriberPolicy},
+			Listener, Stream,
+		},
+		Name,
+	},
+	tests::util::{listen_and_pick_name, namegen_local_socket, TestResult, WrapErrExt},
+};
+use color_eyre::eyre::{bail, ensure};
+use std::{io, sync::Arc, time::Duration};
+use tokio::time::timeout;
+
+/// Number of messages published in the slow subscriber tests.
+const COUNT: u32 = 100;
+/// Size of the messages published in the slow subscriber tests, large enough for them to fill up
+/// the buffers of the socket and back up into the queue of the broker.
+const SIZE: usize = 64 * 1024;
+
+fn start_broker(
+	id: &'static str,
+	path: bool,
+	opts: BrokerOptions,
+) -> TestResult<Arc<Name<'static>>> {
+	let (name, listener) = listen_and_pick_name(&mut namegen_local_socket(id, path), |nm| {
+		Listener::bind(nm.borrow())
+	})?;
+	// The broker is left running until the runtime of the test shuts down.
+	tokio::spawn(async move { Broker::with_options(listener, opts).run().await });
+	Ok(name)
+}
+
+async fn connect(name: &Name<'_>) -> TestResult<Client> {
+	Ok(Client::new(
+		Stream::connect(name.borrow()).await.opname("connect")?,
+	))
+}
+
+fn msg(topic: &str, payload: &[u8]) -> Option<Message> {
+	Some(Message {
+		topic: topic.to_owned(),
+		payload: payload.to_owned(),
+	})
+}
+
+fn numbered(i: u32) -> Vec<u8> {
+	let mut payload = vec![0; SIZE];
+	if let Some(head) = payload.get_mut(..4) {
+		head.copy_from_slice(&i.to_le_bytes());
+	}
+	payload
+}
+fn number(msg: &Message) -> Option<u32> {
+	Some(u32::from_le_bytes(msg.payload.get(..4)?.try_into().ok()?))
+}
+
+/// Publishes [`COUNT`] numbered messages, then waits until the broker has processed them, which it
+/// has once a subscription change that follows them is acknowledged.
+async fn publish_numbered(publisher: &mut Client) -> TestResult {
+	for i in 0..COUNT {
+		publisher
+			.publish("numbers", &numbered(i))
+			.await
+			.opname("publish")?;
+	}
+	publisher.subscribe("sync").await.opname("subscribe")
+}
+
+pub async fn run(id: &'static str, path: bool) -> TestResult {
+	let name = start_broker(id, path, BrokerOptions::new())?;
+	let mut a = connect(&name).await?;
+	let mut b = connect(&name).await?;
+	let mut publisher = connect(&name).await?;
+	a.subscribe("news").await.opname("subscribe")?;
+	b.subscribe("news").await.opname("subscribe")?;
+	b.subscribe("weather").await.opname("subscribe")?;
+
+	publisher
+		.publish("news", b"first")
+		.await
+		.opname("publish")?;
+	publisher
+		.publish("sports", b"unheard")
+		.await
+		.opname("publish")?;
+	publisher
+		.publish("weather", b"rain")
+		.await
+		.opname("publish")?;
+	ensure_eq!(a.recv().await.opname("receive")?, msg("news", b"first"));
+	ensure_eq!(b.recv().await.opname("receive")?, msg("news", b"first"));
+	ensure_eq!(b.recv().await.opname("receive")?, msg("weather", b"rain"));
+
+	// Unsubscribing stops delivery of that topic only.
+	b.unsubscribe("news").await.opname("unsubscribe")?;
+	publisher
+		.publish("news", b"second")
+		.await
+		.opname("publish")?;
+	publisher.publish("weather", b"").await.opname("publish")?;
+	ensure_eq!(a.recv().await.opname("receive")?, msg("news", b"second"));
+	ensure_eq!(b.recv().await.opname("receive")?, msg("weather", b""));
+
+	// Publishers receive their own messages if subscribed.
+	a.publish("news", b"third").await.opname("publish")?;
+	ensure_eq!(a.recv().await.opname("receive")?, msg("news", b"third"));
+
+	let err = a
+		.publish(&"x".repeat(70_000), b"")
+		.await
+		.err()
+		.map(|e| e.kind());
+	ensure_eq!(err, Some(io::ErrorKind::InvalidInput));
+	Ok(())
+}
+
+pub async fn run_drop_oldest(id: &'static str, path: bool) -> TestResult {
+	let opts = BrokerOptions::new().queue_capacity(2);
+	let name = start_broker(id, path, opts)?;
+	let mut subscriber = connect(&name).await?;
+	let mut publisher = connect(&name).await?;
+	subscriber.subscribe("numbers").await.opname("subscribe")?;
+	publish_numbered(&mut publisher).await?;
+
+	let mut received = Vec::new();
+	while received.last() != Some(&(COUNT - 1)) {
+		let Some(msg) = subscriber.recv().await.opname("receive")? else {
+			bail!("broker closed the connection");
+		};
+		received.extend(number(&msg));
+	}
+	ensure!(received.len() < COUNT as usize, "no messages were dropped");
+	ensure!(
+		received.windows(2).all(|w| w.first() < w.get(1)),
+		"messages arrived out of order: {received:?}"
+	);
+	Ok(())
+}
+
+pub async fn run_subscribe_while_publishing(id: &'static str, path: bool) -> TestResult {
+	let opts = BrokerOptions::new().queue_capacity(1);
+	let name = start_broker(id, path, opts)?;
+	let mut subscriber = connect(&name).await?;
+	let mut publisher = connect(&name).await?;
+	subscriber.subscribe("numbers").await.opname("subscribe")?;
+	let publish = tokio::spawn(async move {
+		for i in 0.. {
+			if publisher.publish("numbers", &numbered(i)).await.is_err() {
+				break;
+			}
+		}
+	});
+	ensure!(
+		subscriber.recv().await.opname("receive")?.is_some(),
+		"broker closed the connection"
+	);
+
+	// The acknowledgement has to make it through a full queue which keeps getting published to.
+	let rslt = timeout(Duration::from_secs(10), subscriber.subscribe("other")).await;
+	publish.abort();
+	let Ok(rslt) = rslt else {
+		bail!("subscription change was never acknowledged");
+	};
+	rslt.opname("subscribe")
+}
+
+pub async fn run_disconnect(id: &'static str, path: bool) -> TestResult {
+	let opts = BrokerOptions::new()
+		.queue_capacity(2)
+		.slow_subscriber_policy(SlowSubscriberPolicy::Disconnect);
+	let name = start_broker(id, path, opts)?;
+	let mut subscriber = connect(&name).await?;
+	let mut publisher = connect(&name).await?;
+	subscriber.subscribe("numbers").await.opname("subscribe")?;
+	publish_numbered(&mut publisher).await?;
+
+	let mut received = 0;
+	let err = loop {
+		match subscriber.recv().await {
+			Ok(Some(_)) => received += 1,
+			Ok(None) => bail!("connection closed without a notice"),
+			Err(e) => break e.kind(),
+		}
+	};
+	ensure_eq!(err, io::ErrorKind::ConnectionAborted);
+	ensure!(received < COUNT, "subscriber wasn't disconnected");
+	// The publisher isn't affected.
+	publisher.publish("numbers", b"").await.opname("publish")?;
+	Ok(())
+}
+
+pub async fn run_block(id: &'static str, path: bool) -> TestResult {
+	let opts = BrokerOptions::new()
+		.queue_capacity(2)
+		.slow_subscriber_policy(SlowSubscriberPolicy::Block);
+	let name = start_broker(id, path, opts)?;
+	let mut subscriber = connect(&name).await?;
+	let mut publisher = connect(&name).await?;
+	subscriber.subscribe("numbers").await.opname("subscribe")?;
+	let publish = tokio::spawn(async move { publish_numbered(&mut publisher).await });
+
+	for i in 0..COUNT {
+		let received = subscriber.recv().await.opname("receive")?;
+		ensure_eq!(received.as_ref().and_then(number), Some(i));
+	}
+	let Ok(rslt) = publish.await else {
+		bail!("publisher task panicked");
+	};
+	rslt
+}
@@ -0,0 +1,112 @@
+use crate::{
+	local_socket::{
+		framing::FramingOptions,
+		tokio::{
+			rpc::{serve_with_options, Caller, CallerOptions},
+			Stream,
+		},
+	},
+	tests::util::{TestResult, WrapErrExt},
+};
+use color_eyre::eyre::bail;
+use std::{future::Future, io, pin::Pin, time::Duration};
+use tokio::{sync::mpsc, time::sleep};
+
+/// Sends a message through the channel when dropped, which happens when a handler is aborted.
+struct DropSignal(mpsc::UnboundedSender<()>);
+impl Drop for DropSignal {
+	fn drop(&mut self) {
+		let _ = self.0.send(());
+	}
+}
+
+type HandlerFuture = Pin<Box<dyn Future<Output = Vec<u8>> + Send>>;
+
+/// Handler which echoes requests after sleeping for as many milliseconds as the first byte says,
+/// never responds to empty requests and panics on `panic`.
+fn handler(dropped: mpsc::UnboundedSender<()>) -> impl Fn(Vec<u8>) -> HandlerFuture {
+	move |request| {
+		let signal = DropSignal(dropped.clone());
+		Box::pin(async move {
+			let Some(&delay) = request.first() else {
+				return std::future::pending().await;
+			};
+			if request == b"panic" {
+				panic!("requested panic");
+			}
+			sleep(Duration::from_millis(delay.into())).await;
+			std::mem::forget(signal);
+			request
+		})
+	}
+}
+
+pub async fn run() -> TestResult {
+	let framing = FramingOptions::new().max_frame_size(64);
+	let (s1, s2) = Stream::pair().await.opname("pair creation")?;
+	let (dropped_tx, mut dropped) = mpsc::unbounded_channel();
+	let server = tokio::spawn(serve_with_options(s2, framing, handler(dropped_tx)));
+	let caller = Caller::with_options(s1, CallerOptions::new().framing(framing));
+
+	// Responses arrive in reverse order of the requests.
+	let requests: Vec<Vec<u8>> = (0..10_u8).map(|i| vec![50 - i * 5, i]).collect();
+	let calls = requests.iter().map(|request| {
+		let caller = caller.clone();
+		let request = request.clone();
+		tokio::spawn(async move { caller.call(&request).await })
+	});
+	let calls: Vec<_> = calls.collect();
+	for (call, request) in calls.into_iter().zip(&requests) {
+		let Ok(response) = call.await else {
+			bail!("call task panicked");
+		};
+		ensure_eq!(&response.opname("call")?, request);
+	}
+
+	let err = caller
+		.call_timeout(b"", Duration::from_millis(20))
+		.await
+		.err()
+		.map(|e| e.kind());
+	ensure_eq!(err, Some(io::ErrorKind::TimedOut));
+	let cancelled = tokio::time::timeout(Duration::from_secs(10), dropped.recv()).await;
+	ensure_eq!(cancelled.ok().flatten(), Some(()));
+
+	let err = caller.call(b"panic").await.err().map(|e| e.kind());
+	ensure_eq!(err, Some(io::ErrorKind::Other));
+	let err = caller.call(&[0; 64]).await.err().map(|e| e.kind());
+	ensure_eq!(err, Some(io::ErrorKind::InvalidInput));
+	// The connection survives both errors.
+	ensure_eq!(caller.call(&[0, 1]).await.opname("call")?, [0, 1]);
+
+	drop(caller);
+	let Ok(rslt) = server.await else {
+		bail!("server task panicked");
+	};
+	rslt.opname("serve")?;
+	Ok(())
+}
+
+pub async fn run_closed() -> TestResult {
+	let (s1, s2) = Stream::pair().await.opname("pair creation")?;
+	let caller = Caller::with_options(
+		s1,
+		CallerOptions::new().timeout(Some(Duration::from_secs(10))),
+	);
+	let call = tokio::spawn({
+		let caller = caller.clone();
+		async move { caller.call(b"unanswered").await }
+	});
+	sleep(Duration::from_millis(20)).await;
+	drop(s2);
+	let Ok(rslt) = call.await else {
+		bail!("call task panicked");
+	};
+	ensure_eq!(
+		rslt.err().map(|e| e.kind()),
+		Some(io::ErrorKind::ConnectionAborted)
+	);
+	let err = caller.call(b"late").await.err().map(|e| e.kind());
+	ensure_eq!(err, Some(io::ErrorKind::ConnectionAborted));
+	Ok(())
+}
@@ -0,0 +1,165 @@
+use crate::{
+	local_socket::{
+		tokio::{
+			server::{serve, Connection, ServerOptions},
+			Listener, Stream,
+		},
+		Name,
+	},
+	tests::util::*,
+};
+use ::tokio::{
+	io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader},
+	sync::oneshot,
+	time::{sleep, timeout},
+};
+use color_eyre::eyre::{bail, ensure};
+use std::{
+	future::Future,
+	pin::Pin,
+	sync::{
+		atomic::{AtomicUsize, Ordering::SeqCst},
+		Arc,
+	},
+	time::Duration,
+};
+
+type HandlerFuture = Pin<Box<dyn Future<Output = ()> + Send>>;
+
+/// Handler which echoes a line back, never responding to `hang`, and records the maximum number of
+/// connections handled at once. Every handler future holds a clone of `live` until it's dropped.
+fn handler(max_active: Arc<AtomicUsize>, live: Arc<()>) -> impl Fn(Connection) -> HandlerFuture {
+	let active = Arc::new(AtomicUsize::new(0));
+	move |conn| {
+		let (active, max_active) = (Arc::clone(&active), Arc::clone(&max_active));
+		let live = Arc::clone(&live);
+		Box::pin(async move {
+			let _live = live;
+			let now_active = active.fetch_add(1, SeqCst).saturating_add(1);
+			max_active.fetch_max(now_active, SeqCst);
+			let mut conn = BufReader::new(conn);
+			let mut line = String::new();
+			let _ = conn.read_line(&mut line).await;
+			// Gives the other clients a chance to exceed the limit, if it isn't enforced.
+			sleep(Duration::from_millis(10)).await;
+			active.fetch_sub(1, SeqCst);
+			if line == "hang\n" {
+				std::future::pending().await
+			}
+			let _ = conn.get_mut().write_all(line.as_bytes()).await;
+		})
+	}
+}
+
+/// Sends a line and returns the line sent back, or `None` if the connection was closed first.
+async fn roundtrip(name: &Name<'_>, line: &str) -> TestResult<Option<String>> {
+	let mut conn = BufReader::new(Stream::connect(name.borrow()).await.opname("connect")?);
+	conn.get_mut()
+		.write_all(format!("{line}\n").as_bytes())
+		.await
+		.opname("send")?;
+	let mut response = String::new();
+	conn.read_line(&mut response).await.opname("receive")?;
+	Ok(response.strip_suffix('\n').map(str::to_owned))
+}
+
+pub async fn run(id: &'static str, path: bool) -> TestResult {
+	let (name, listener) = listen_and_pick_name(&mut namegen_local_socket(id, path), |nm| {
+		Listener::bind(nm.borrow())
+	})?;
+	let max_active = Arc::new(AtomicUsize::new(0));
+	let live = Arc::new(());
+	let opts = ServerOptions::new()
+		.max_connections(2)
+		.handshake_timeout(Some(Duration::from_millis(100)))
+		.drain_timeout(Duration::from_millis(100));
+	let (stop, stopped) = oneshot::channel::<()>();
+	let shutdown = async move {
+		let _ = stopped.await;
+	};
+	let server = ::tokio::spawn(serve(
+		listener,
+		handler(Arc::clone(&max_active), Arc::clone(&live)),
+		shutdown,
+		opts,
+	));
+
+	let clients = (0..8).map(|i| {
+		let name = Arc::clone(&name);
+		::tokio::spawn(async move { roundtrip(&name, &format!("client {i}")).await })
+	});
+	let clients: Vec<_> = clients.collect();
+	for (i, client) in clients.into_iter().enumerate() {
+		let Ok(response) = client.await else {
+			bail!("client task panicked");
+		};
+		ensure_eq!(response?, Some(format!("client {i}")));
+	}
+	ensure!(
+		max_active.load(SeqCst) <= 2,
+		"more connections than the maximum were handled at once"
+	);
+
+	// Clients that never speak are disconnected.
+	let mut silent = Stream::connect(name.borrow()).await.opname("connect")?;
+	let read = timeout(Duration::from_secs(10), silent.read(&mut [0; 8])).await;
+	ensure_eq!(read.ok().map(|r| r.ok()), Some(Some(0)));
+
+	// Connections that don't finish in time are aborted on shutdown.
+	let mut hanging = BufReader::new(Stream::connect(name.borrow()).await.opname("connect")?);
+	hanging
+		.get_mut()
+		.write_all(b"hang\n")
+		.await
+		.opname("send")?;
+	sleep(Duration::from_millis(20)).await;
+	let _ = stop.send(());
+	let Ok(rslt) = timeout(Duration::from_secs(10), server).await else {
+		bail!("server didn't stop after the drain timeout");
+	};
+	let Ok(rslt) = rslt else {
+		bail!("server task panicked");
+	};
+	rslt.opname("serve")?;
+	ensure_eq!(Arc::strong_count(&live), 1);
+	let mut line = String::new();
+	ensure_eq!(hanging.read_line(&mut line).await.opname("receive")?, 0);
+	ensure!(
+		Stream::connect(name.borrow()).await.is_err(),
+		"listener still accepting after shutdown"
+	);
+	Ok(())
+}
+
+/// Checks that the handshake timeout still applies after the handler has written to a client which
+/// hasn't sent anything yet.
+pub async fn run_greeting(id: &'static str, path: bool) -> TestResult {
+	let (name, listener) = listen_and_pick_name(&mut namegen_local_socket(id, path), |nm| {
+		Listener::bind(nm.borrow())
+	})?;
+	let opts = ServerOptions::new().handshake_timeout(Some(Duration::from_millis(100)));
+	let handler = |conn: Connection| async move {
+		let mut conn = BufReader::new(conn);
+		if conn.get_mut().write_all(b"hello\n").await.is_ok() {
+			let _ = conn.read_line(&mut String::new()).await;
+		}
+	};
+	let (stop, stopped) = oneshot::channel::<()>();
+	let shutdown = async move {
+		let _ = stopped.await;
+	};
+	let server = ::tokio::spawn(serve(listener, handler, shutdown, opts));
+
+	let mut silent = BufReader::new(Stream::connect(name.borrow()).await.opname("connect")?);
+	let mut line = String::new();
+	silent.read_line(&mut line).await.opname("receive")?;
+	ensure_eq!(line, "hello\n");
+	let read = timeout(Duration::from_secs(10), silent.read(&mut [0; 8])).await;
+	ensure_eq!(read.ok().map(|r| r.ok()), Some(Some(0)));
+
+	let _ = stop.send(());
+	let Ok(rslt) = server.await else {
+		bail!("server task panicked");
+	};
+	rslt.opname("serve")
+}
@@ -0,0 +1,44 @@
+use crate::{
+	local_socket::{
+		framing::FramingOptions,
+		tokio::{
+			typed::{Format, TypedStream},
+			Stream,
+		},
+	},
+	tests::{
+		local_socket::typed::{requests, Request, Response},
+		util::{TestResult, WrapErrExt},
+	},
+};
+use color_eyre::eyre::bail;
+use tokio::try_join;
+
+pub async fn run<F: Format + Clone>(format: F) -> TestResult {
+	let opts = FramingOptions::new();
+	let (s1, s2) = Stream::pair().await.opname("pair creation")?;
+
+	let server_format = format.clone();
+	let server = async move {
+		let server = TypedStream::<Response, Request, F>::with_format(s2, server_format, opts);
+		let (mut recver, mut sender) = server.split();
+		while let Some(req) = recver.recv().await.opname("receive")? {
+			sender.send(&Response::to(&req)).await.opname("send")?;
+		}
+		let Ok(server) = TypedStream::reunite(recver, sender) else {
+			bail!("reunite failed");
+		};
+		drop(server);
+		TestResult::Ok(())
+	};
+	let client = async move {
+		let mut client = TypedStream::<Request, Response, F>::with_format(s1, format, opts);
+		for req in requests() {
+			client.send(&req).await.opname("send")?;
+			let resp = client.recv().await.opname("receive")?;
+			ensure_eq!(resp, Some(Response::to(&req)));
+		}
+		TestResult::Ok(())
+	};
+	try_join!(server, client).map(|_| ())
+}